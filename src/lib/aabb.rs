@@ -2,6 +2,7 @@ use crate::lib::{ray::*, vec3::*};
 
 use partial_min_max::*;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Copy, Clone, Default)]
 pub struct AABB {
     _min: Point3,
@@ -26,6 +27,11 @@ impl AABB {
     }
 
     pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.intersect(ray, t_min, t_max).is_some()
+    }
+
+    //the part of [t_min, t_max] the ray spends inside the box, if any
+    pub fn intersect(&self, ray: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
         let (mut t_0, mut t_1): (f64, f64);
         let mut inv_d: f64;
 
@@ -84,18 +90,17 @@ impl AABB {
                 _ => continue,
             }
 
-            //checking if the ray direction is reversed
-            let (t_0, t_1) = if inv_d < 0.0 { (t_1, t_0) } else { (t_0, t_1) };
-
-            let t_min = if t_0 > t_min { t_0 } else { t_min };
-            let t_max = if t_1 < t_max { t_1 } else { t_max };
+            //t_0 and t_1 are already ordered by min and max, whichever way the ray points. The
+            //interval has to shrink across all three axes, so it carries over between them
+            t_min = if t_0 > t_min { t_0 } else { t_min };
+            t_max = if t_1 < t_max { t_1 } else { t_max };
 
             if t_max <= t_min {
-                return false;
+                return None;
             }
         }
 
-        true
+        Some((t_min, t_max))
     }
 
     pub fn surrounding_box(box_0: &AABB, box_1: &AABB) -> AABB {
@@ -115,4 +120,21 @@ impl AABB {
 
         AABB::new(&small, &big)
     }
+
+    //the part both boxes cover
+    pub fn overlapping_box(box_0: &AABB, box_1: &AABB) -> AABB {
+        let small = Point3::new(
+            max(box_0._min.x(), box_1._min.x()),
+            max(box_0._min.y(), box_1._min.y()),
+            max(box_0._min.z(), box_1._min.z()),
+        );
+
+        let big = Point3::new(
+            min(box_0._max.x(), box_1._max.x()),
+            min(box_0._max.y(), box_1._max.y()),
+            min(box_0._max.z(), box_1._max.z()),
+        );
+
+        AABB::new(&small, &big)
+    }
 }
@@ -0,0 +1,107 @@
+use crate::lib::{color::*, framebuffer::Framebuffer};
+
+//stop sampling a pixel once its estimate is good enough, instead of always taking the same count
+#[derive(Copy, Clone, Debug)]
+pub struct AdaptiveParams {
+    pub min_samples: usize,
+    pub max_samples: usize,
+    pub threshold: f64, //largest accepted standard error, relative to the pixel's brightness
+}
+
+impl Default for AdaptiveParams {
+    fn default() -> Self {
+        Self {
+            min_samples: 16,
+            max_samples: 100,
+            threshold: 0.02,
+        }
+    }
+}
+
+//running mean and variance of a pixel's luminance (Welford's algorithm)
+#[derive(Copy, Clone, Default)]
+pub struct PixelStats {
+    count: usize,
+    mean: f64,
+    m2: f64, //sum of squared distances from the mean
+}
+
+impl PixelStats {
+    pub fn add(&mut self, sample: &Color) {
+        let value = sample.luminance();
+
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    //standard error of the mean divided by the mean - dark pixels get a floor so noise in the
+    //near-black doesn't keep them sampling forever
+    pub fn relative_error(&self) -> f64 {
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+        let variance = self.m2 / (self.count - 1) as f64;
+        (variance / self.count as f64).sqrt() / self.mean.max(0.05)
+    }
+
+    pub fn converged(&self, params: &AdaptiveParams) -> bool {
+        self.count >= params.max_samples
+            || (self.count >= params.min_samples && self.relative_error() <= params.threshold)
+    }
+}
+
+//samples taken per pixel, from blue (fewest) through green to red (max_samples)
+pub fn heatmap(
+    sample_counts: &[usize],
+    width: usize,
+    height: usize,
+    max_samples: usize,
+) -> Framebuffer {
+    let pixels = sample_counts
+        .iter()
+        .map(|&count| {
+            let t = count as f64 / max_samples.max(1) as f64;
+            if t < 0.5 {
+                Color::new(0.0, 2.0 * t, 1.0 - 2.0 * t)
+            } else {
+                Color::new(2.0 * t - 1.0, 2.0 - 2.0 * t, 0.0)
+            }
+        })
+        .collect();
+
+    Framebuffer::from_pixels(width, height, pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::adaptive::*;
+
+    #[test]
+    fn test_constant_pixel_converges_at_min_samples() {
+        let params = AdaptiveParams::default();
+        let mut stats = PixelStats::default();
+
+        while !stats.converged(&params) {
+            stats.add(&Color::new(0.5, 0.7, 1.0));
+        }
+        assert_eq!(stats.count(), params.min_samples);
+    }
+
+    #[test]
+    fn test_noisy_pixel_runs_to_max_samples() {
+        let params = AdaptiveParams::default();
+        let mut stats = PixelStats::default();
+
+        while !stats.converged(&params) {
+            let value = if stats.count() % 2 == 0 { 0.0 } else { 1.0 };
+            stats.add(&Color::new(value, value, value));
+        }
+        assert_eq!(stats.count(), params.max_samples);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::lib::{aabb::*, hittable::*, ray::Ray, texture::Texture};
+use std::sync::Arc;
+
+//cuts holes in whatever it wraps - texels with an opacity below the cutoff aren't there, so rays
+//carry on to the next surface of the object (or past it), which lets leaves and fences be flat cards
+pub struct AlphaMask {
+    object: Arc<dyn Hittable>,
+    opacity: Arc<dyn Texture>,
+    cutoff: f64,
+}
+
+#[allow(dead_code)]
+impl AlphaMask {
+    pub fn new_hittable(
+        object: Arc<dyn Hittable>,
+        opacity: Arc<dyn Texture>,
+        cutoff: f64,
+    ) -> Arc<dyn Hittable> {
+        Arc::new(Self {
+            object,
+            opacity,
+            cutoff,
+        })
+    }
+
+    fn is_opaque(&self, hit_rec: &HitRecord) -> bool {
+        self.opacity
+            .value(hit_rec.u(), hit_rec.v(), &hit_rec.point())
+            .luminance()
+            >= self.cutoff
+    }
+}
+
+impl Hittable for AlphaMask {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
+        //every masked hit moves t_min forward, so this ends once the object runs out of surfaces
+        let mut t_min = t_min;
+        while self.object.hit(ray, t_min, t_max, hit_rec) {
+            if self.is_opaque(hit_rec) {
+                return true;
+            }
+            t_min = hit_rec.t() + INTERVAL_EPSILON * hit_rec.t().abs().max(1.0);
+        }
+        false
+    }
+
+    fn bounding_box(&self, t_0: f64, t_1: f64, output_box: &mut AABB) -> bool {
+        self.object.bounding_box(t_0, t_1, output_box)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::{
+        alpha_mask::*,
+        color::Color,
+        hittable_list::HittableList,
+        material::Material,
+        sdf::{Sdf, SdfObject},
+        sphere::Sphere,
+        vec3::*,
+    };
+
+    //the half of the surface with u below 0.5 is cut out
+    struct HalfMask;
+
+    impl Texture for HalfMask {
+        fn value(&self, u: f64, _v: f64, _point: &Point3) -> Color {
+            if u < 0.5 {
+                Color::new(0.0, 0.0, 0.0)
+            } else {
+                Color::new(1.0, 1.0, 1.0)
+            }
+        }
+    }
+
+    #[test]
+    fn test_rays_pass_through_transparent_texels() {
+        let mut world = HittableList::new();
+        world.add(AlphaMask::new_hittable(
+            Sphere::new_hittable(Point3::new(0.0, 0.0, 0.0), 1.0, Material::default()),
+            Arc::new(HalfMask),
+            0.5,
+        ));
+        world.add(Sphere::new_hittable(
+            Point3::new(-10.0, 0.0, 0.0),
+            1.0,
+            Material::default(),
+        ));
+
+        //the -z side of the sphere is opaque
+        let mut hit_rec = HitRecord::new_invalid();
+        let ray = Ray::new(
+            &Point3::new(5.0, 0.0, -0.3),
+            &Vec3::new(-1.0, 0.0, 0.0),
+            0.0,
+        );
+        assert!(world.hit(&ray, 0.001, f64::INFINITY, &mut hit_rec));
+        assert_eq!(hit_rec.object_id(), 1);
+        assert!(hit_rec.t() < 5.0);
+
+        //both the front and the back of the +z side are cut out, so the ray reaches the sphere behind
+        let ray = Ray::new(&Point3::new(5.0, 0.0, 0.3), &Vec3::new(-1.0, 0.0, 0.0), 0.0);
+        assert!(world.hit(&ray, 0.001, f64::INFINITY, &mut hit_rec));
+        assert_eq!(hit_rec.object_id(), 2);
+        assert!(hit_rec.t() > 10.0);
+    }
+
+    //only the -x half of space is opaque, for objects without surface coordinates
+    struct FarHalf;
+
+    impl Texture for FarHalf {
+        fn value(&self, _u: f64, _v: f64, point: &Point3) -> Color {
+            if point.x() < 0.0 {
+                Color::new(1.0, 1.0, 1.0)
+            } else {
+                Color::new(0.0, 0.0, 0.0)
+            }
+        }
+    }
+
+    #[test]
+    fn test_masked_sdf_finds_its_far_side() {
+        //a ray march restarted on the cut out front has to get off the surface to find the back
+        let masked = AlphaMask::new_hittable(
+            SdfObject::new_hittable(
+                Sdf::sphere(Point3::new(0.0, 0.0, 0.0), 1.0),
+                Material::default(),
+            ),
+            Arc::new(FarHalf),
+            0.5,
+        );
+
+        let mut hit_rec = HitRecord::new_invalid();
+        let ray = Ray::new(&Point3::new(5.0, 0.0, 0.0), &Vec3::new(-1.0, 0.0, 0.0), 0.0);
+        assert!(masked.hit(&ray, 0.001, f64::INFINITY, &mut hit_rec));
+        assert!((hit_rec.t() - 6.0).abs() < 1e-4);
+        assert!(!hit_rec.front_face());
+    }
+}
@@ -0,0 +1,116 @@
+use crate::lib::{camera::Camera, color::*, framebuffer::Framebuffer, hittable::*, vec3::*};
+
+use std::io;
+use std::path::Path;
+
+//arbitrary output variables - what the camera sees first, rendered alongside the beauty image
+pub struct AovBuffers {
+    pub depth: Framebuffer,
+    pub normal: Framebuffer,
+    pub albedo: Framebuffer,
+    pub object_id: Framebuffer,
+}
+
+//the AOV values of a single camera ray - empty sky leaves everything at zero
+#[derive(Copy, Clone, Default)]
+pub struct AovSample {
+    pub depth: f64,
+    pub normal: Vec3,
+    pub albedo: Color,
+    pub object_id: usize,
+}
+
+impl AovSample {
+    //from the first hit the integrator found for the camera ray, None if it escaped
+    pub fn from_hit(first_hit: Option<&HitRecord>, camera: &Camera) -> Self {
+        let hit_rec = match first_hit {
+            Some(hit_rec) => hit_rec,
+            None => return AovSample::default(),
+        };
+
+        AovSample {
+            depth: camera.depth(&hit_rec.point()),
+            normal: hit_rec.normal(),
+            albedo: hit_rec.material().albedo(),
+            object_id: hit_rec.object_id(),
+        }
+    }
+}
+
+impl AovBuffers {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            depth: Framebuffer::new(width, height),
+            normal: Framebuffer::new(width, height),
+            albedo: Framebuffer::new(width, height),
+            object_id: Framebuffer::new(width, height),
+        }
+    }
+
+    //one PFM per pass, named after the pass
+    pub fn write_pfm<P: AsRef<Path>>(&self, directory: P) -> io::Result<()> {
+        let directory = directory.as_ref();
+        std::fs::create_dir_all(directory)?;
+
+        self.depth
+            .write_pfm_grayscale(directory.join("depth.pfm"))?;
+        self.normal.write_pfm(directory.join("normal.pfm"))?;
+        self.albedo.write_pfm(directory.join("albedo.pfm"))?;
+        self.object_id
+            .write_pfm_grayscale(directory.join("object_id.pfm"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::{
+        aov::*, hittable_list::HittableList, integrator::*, material::Material, ray::Ray,
+        sampler::RandomSampler, sphere::Sphere,
+    };
+
+    #[test]
+    fn test_aovs_come_from_the_integrators_first_hit() {
+        let mut world = HittableList::new();
+        world.add(Sphere::new_hittable(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Material::lambertian(&Color::new(0.2, 0.4, 0.6)),
+        ));
+        let camera = Camera::new(
+            &Point3::new(0.0, 0.0, 3.0),
+            &Point3::new(0.0, 0.0, 0.0),
+            &Vec3::new(0.0, 1.0, 0.0),
+            30.0,
+            1.0,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        );
+        let integrator = PathTracer::new(4);
+        let mut sampler = RandomSampler::new();
+
+        let mut first_hit = None;
+        let ray = Ray::new(&Point3::new(0.0, 0.0, 3.0), &Vec3::new(0.0, 0.0, -1.0), 0.0);
+        integrator.li(&ray, &world, &mut sampler, &mut first_hit);
+        let aov = AovSample::from_hit(first_hit.as_ref(), &camera);
+        assert!((aov.depth - 2.0).abs() < 1e-9);
+        assert!((aov.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+        assert_eq!(
+            (aov.albedo.r(), aov.albedo.g(), aov.albedo.b()),
+            (0.2, 0.4, 0.6)
+        );
+        assert_eq!(aov.object_id, 1);
+
+        //the sky leaves every pass at zero
+        let mut first_hit = None;
+        let ray = Ray::new(&Point3::new(0.0, 0.0, 3.0), &Vec3::new(0.0, 1.0, 0.0), 0.0);
+        integrator.li(&ray, &world, &mut sampler, &mut first_hit);
+        assert!(first_hit.is_none());
+        let aov = AovSample::from_hit(first_hit.as_ref(), &camera);
+        assert_eq!(aov.depth, 0.0);
+        assert_eq!(aov.normal, Vec3::default());
+        assert_eq!(aov.albedo.luminance(), 0.0);
+        assert_eq!(aov.object_id, 0);
+    }
+}
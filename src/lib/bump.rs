@@ -0,0 +1,188 @@
+use crate::lib::{aabb::*, hittable::*, onb::Onb, ray::Ray, texture::Texture, vec3::*};
+use std::sync::Arc;
+
+//perturbs the shading normal of whatever it wraps with a tangent-space normal map - the usual
+//encoding where (0.5, 0.5, 1) is the unchanged normal and red and green tilt it along u and v
+pub struct NormalMap {
+    object: Arc<dyn Hittable>,
+    normals: Arc<dyn Texture>,
+}
+
+//perturbs the shading normal as if the surface were displaced along it by a height texture,
+//without moving the geometry (Blinn, "Simulation of Wrinkled Surfaces")
+pub struct BumpMap {
+    object: Arc<dyn Hittable>,
+    heights: Arc<dyn Texture>,
+    scale: f64, //displacement for a height of 1
+}
+
+//step in surface coordinates for the finite differences of the height
+const BUMP_DELTA: f64 = 1e-3;
+
+#[allow(dead_code)]
+impl NormalMap {
+    pub fn new_hittable(object: Arc<dyn Hittable>, normals: Arc<dyn Texture>) -> Arc<dyn Hittable> {
+        Arc::new(Self { object, normals })
+    }
+}
+
+impl Hittable for NormalMap {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
+        if !self.object.hit(ray, t_min, t_max, hit_rec) {
+            return false;
+        }
+
+        let normal = hit_rec.outward_normal();
+        let frame = Onb::from_normal_and_tangent(&normal, &hit_rec.dpdu());
+        //mirrored uvs flip the bitangent
+        let bitangent = if frame.v().dot(&hit_rec.dpdv()) < 0.0 {
+            -frame.v()
+        } else {
+            frame.v()
+        };
+
+        let encoded = self
+            .normals
+            .value(hit_rec.u(), hit_rec.v(), &hit_rec.point());
+        let shading_normal = (2.0 * encoded.r() - 1.0) * frame.u()
+            + (2.0 * encoded.g() - 1.0) * bitangent
+            + (2.0 * encoded.b() - 1.0) * normal;
+
+        if shading_normal.length_squared() > 1e-12 {
+            hit_rec.set_shading_normal(&shading_normal.unit_vector());
+        }
+        true
+    }
+
+    fn bounding_box(&self, t_0: f64, t_1: f64, output_box: &mut AABB) -> bool {
+        self.object.bounding_box(t_0, t_1, output_box)
+    }
+}
+
+#[allow(dead_code)]
+impl BumpMap {
+    pub fn new_hittable(
+        object: Arc<dyn Hittable>,
+        heights: Arc<dyn Texture>,
+        scale: f64,
+    ) -> Arc<dyn Hittable> {
+        Arc::new(Self {
+            object,
+            heights,
+            scale,
+        })
+    }
+
+    fn height(&self, u: f64, v: f64, point: &Point3) -> f64 {
+        self.scale * self.heights.value(u, v, point).luminance()
+    }
+}
+
+impl Hittable for BumpMap {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
+        if !self.object.hit(ray, t_min, t_max, hit_rec) {
+            return false;
+        }
+
+        let (u, v, point) = (hit_rec.u(), hit_rec.v(), hit_rec.point());
+        let (dpdu, dpdv) = (hit_rec.dpdu(), hit_rec.dpdv());
+        let normal = hit_rec.outward_normal();
+
+        //the displaced point is p + h(u, v) * n - differentiating it (and ignoring how the normal
+        //itself bends) gives the new tangents
+        let height = self.height(u, v, &point);
+        let height_du =
+            (self.height(u + BUMP_DELTA, v, &(point + BUMP_DELTA * dpdu)) - height) / BUMP_DELTA;
+        let height_dv =
+            (self.height(u, v + BUMP_DELTA, &(point + BUMP_DELTA * dpdv)) - height) / BUMP_DELTA;
+
+        let bumped_dpdu = dpdu + height_du * normal;
+        let bumped_dpdv = dpdv + height_dv * normal;
+        let mut shading_normal = bumped_dpdu.cross(&bumped_dpdv);
+        if shading_normal.length_squared() < 1e-24 {
+            //no tangent frame to bump along
+            return true;
+        }
+        if shading_normal.dot(&normal) < 0.0 {
+            shading_normal = -shading_normal;
+        }
+
+        hit_rec.set_shading_normal(&shading_normal.unit_vector());
+        hit_rec.set_tangents(&bumped_dpdu, &bumped_dpdv);
+        true
+    }
+
+    fn bounding_box(&self, t_0: f64, t_1: f64, output_box: &mut AABB) -> bool {
+        self.object.bounding_box(t_0, t_1, output_box)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::{bump::*, color::Color, material::Material, texture::*, triangle::Triangle};
+
+    //a triangle in the xy plane facing +z, with u along x and v along y
+    fn flat_triangle() -> Arc<dyn Hittable> {
+        Arc::new(
+            Triangle::new(
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+                Material::default(),
+            )
+            .with_uvs([(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]),
+        )
+    }
+
+    fn hit_from_above(object: &dyn Hittable) -> HitRecord {
+        let mut hit_rec = HitRecord::new_invalid();
+        let ray = Ray::new(
+            &Point3::new(0.25, 0.25, 1.0),
+            &Vec3::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        assert!(object.hit(&ray, 0.001, f64::INFINITY, &mut hit_rec));
+        hit_rec
+    }
+
+    #[test]
+    fn test_normal_map_tilts_along_tangents() {
+        let flat = NormalMap::new_hittable(
+            flat_triangle(),
+            SolidColor::new_texture(&Color::new(0.5, 0.5, 1.0)),
+        );
+        let normal = hit_from_above(flat.as_ref()).normal();
+        assert!((normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+
+        //full red tilts the normal 45 degrees towards +u
+        let tilted = NormalMap::new_hittable(
+            flat_triangle(),
+            SolidColor::new_texture(&Color::new(1.0, 0.5, 1.0)),
+        );
+        let normal = hit_from_above(tilted.as_ref()).normal();
+        assert!((normal - Vec3::new(1.0, 0.0, 1.0).unit_vector()).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_bump_map_follows_the_slope() {
+        //height rising along u leans the normal back towards -u
+        let ramp = ImageTexture::new(
+            2,
+            1,
+            vec![Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0)],
+        );
+        let bumped = BumpMap::new_hittable(flat_triangle(), Arc::new(ramp), 0.1);
+        let normal = hit_from_above(bumped.as_ref()).normal();
+        assert!(normal.x() < 0.0);
+        assert!(normal.y().abs() < 1e-9);
+        assert!(normal.z() > 0.0);
+
+        let constant = BumpMap::new_hittable(
+            flat_triangle(),
+            SolidColor::new_texture(&Color::new(0.5, 0.5, 0.5)),
+            1.0,
+        );
+        let normal = hit_from_above(constant.as_ref()).normal();
+        assert!((normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+    }
+}
@@ -1,143 +1,237 @@
-use crate::lib::{aabb::*, hittable::*, hittable_list::*, ray::*, vec3::*};
-
-use rand::prelude::*;
+use crate::lib::{aabb::*, hittable::*, ray::*};
 
 use std::cmp::Ordering;
 use std::sync::Arc;
 
 enum BVHNode {
     Branch {
-        left: Arc<dyn Hittable>,
-        right: Arc<dyn Hittable>,
+        left: Box<BVHNode>,
+        right: Box<BVHNode>,
+        //using the word container since 'box' is a reserved keyword in Rust
+        container: AABB,
+    },
+    Leaf {
+        object: Arc<dyn Hittable>,
+        object_id: usize,
+        container: AABB,
     },
-    Leaf(Arc<dyn Hittable>),
 }
+
+//bounding volume hierarchy over a list of objects. Objects without a bounding box (eg. infinite
+//planes) can't go in the tree, so they are kept to one side and tested on every ray
+#[allow(clippy::upper_case_acronyms)]
 pub struct BVH {
-    tree: BVHNode,
-    //using the word container since 'box' is a reserved keyword in Rust
-    pub container: AABB,
+    root: Option<BVHNode>,
+    unbounded: Vec<(usize, Arc<dyn Hittable>)>,
 }
 
+#[allow(dead_code)]
 impl BVH {
-    pub fn new(
-        mut objects: Vec<Arc<dyn Hittable>>,
-        time_0: f64,
-        time_1: f64,
-        start: usize,
-        end: usize,
-    ) -> Self {
-        //returning Ordering because the standard library's vector sort function expects an Ordering function
-        #[allow(unused_assignments)]
-        fn box_compare(
-            a: Arc<dyn Hittable>,
-            b: Arc<dyn Hittable>,
-            time_0: f64,
-            time_1: f64,
-            axis_index: usize,
-        ) -> Ordering {
-            let mut x: bool;
-
-            let mut a_container = AABB::default();
-            x = a.bounding_box(time_0, time_1, &mut a_container);
-            let mut b_container = AABB::default();
-            x = b.bounding_box(time_0, time_1, &mut b_container);
-
-            let ac: f64;
-            let bc: f64;
-
-            if axis_index == 1 {
-                ac = a_container.min().x() + a_container.max().x();
-                bc = b_container.min().x() + b_container.max().x();
-            } else if axis_index == 2 {
-                ac = a_container.min().y() + a_container.max().y();
-                bc = b_container.min().y() + b_container.max().y();
+    //objects get the same ids a HittableList holding them in this order would give them
+    pub fn new(objects: Vec<Arc<dyn Hittable>>, time_0: f64, time_1: f64) -> Self {
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+
+        for (index, object) in objects.into_iter().enumerate() {
+            let mut container = AABB::default();
+            //ids start at 1 so that 0 can stand for the background
+            if object.bounding_box(time_0, time_1, &mut container) {
+                bounded.push((index + 1, object, container));
             } else {
-                ac = a_container.min().z() + a_container.max().z();
-                bc = b_container.min().z() + b_container.max().z();
+                unbounded.push((index + 1, object));
             }
-
-            ac.partial_cmp(&bc).unwrap()
         }
 
-        let mut rng = rand::thread_rng();
-
-        let axis_index: usize = rng.gen_range(0, 3);
-        let object_span = end - start;
-        let left: Arc<dyn Hittable>;
-        let right: Arc<dyn Hittable>;
-
-        if object_span == 1 {
-            left = objects[start].clone();
-            right = left.clone();
-        } else if object_span == 2 {
-            if box_compare(
-                objects[start].clone(),
-                objects[start + 1].clone(),
-                time_0,
-                time_1,
-                axis_index,
-            ) == Ordering::Greater
-            {
-                left = objects[start].clone();
-                right = objects[start + 1].clone();
-            } else {
-                left = objects[start + 1].clone();
-                right = objects[start].clone();
-            }
+        let root = if bounded.is_empty() {
+            None
         } else {
-            objects.sort_unstable_by(|a, b| {
-                box_compare(a.clone(), b.clone(), time_0, time_1, axis_index)
-            });
-
-            let mid = start + object_span / 2;
-
-            right = Arc::new(BVH::new(
-                objects.drain(objects.len() / 2..).collect(),
-                time_0,
-                time_1,
-                mid,
-                end,
-            ));
-            left = Arc::new(BVH::new(objects, time_0, time_1, start, mid));
+            Some(BVHNode::build(bounded))
         };
 
-        let mut box_left = AABB::default();
-        let mut box_right = AABB::default();
+        BVH { root, unbounded }
+    }
+}
 
-        if !left.bounding_box(time_0, time_1, &mut box_left)
-            || !right.bounding_box(time_0, time_1, &mut box_right)
-        {
-            panic!("No bounding box in BVHNode constructor \n");
-        };
+impl BVHNode {
+    fn container(&self) -> AABB {
+        match self {
+            BVHNode::Branch { container, .. } => *container,
+            BVHNode::Leaf { container, .. } => *container,
+        }
+    }
 
-        let container = AABB::surrounding_box(&box_left, &box_right);
+    //splits the objects in half along the axis their centers are most spread out on
+    fn build(mut objects: Vec<(usize, Arc<dyn Hittable>, AABB)>) -> Self {
+        if objects.len() == 1 {
+            let (object_id, object, container) = objects.pop().unwrap();
+            return BVHNode::Leaf {
+                object,
+                object_id,
+                container,
+            };
+        }
+
+        let center = |container: &AABB| container.min() + container.max();
+        let axis = {
+            let (mut low, mut high) = ([f64::INFINITY; 3], [f64::NEG_INFINITY; 3]);
+            for (_, _, container) in &objects {
+                let center = center(container);
+                for (axis, value) in [center.x(), center.y(), center.z()].iter().enumerate() {
+                    low[axis] = low[axis].min(*value);
+                    high[axis] = high[axis].max(*value);
+                }
+            }
+            (0..3)
+                .max_by(|a, b| {
+                    (high[*a] - low[*a])
+                        .partial_cmp(&(high[*b] - low[*b]))
+                        .unwrap_or(Ordering::Equal)
+                })
+                .unwrap()
+        };
 
-        BVH {
-            tree: BVHNode::Branch { left, right },
+        let component = |container: &AABB| {
+            let center = center(container);
+            match axis {
+                0 => center.x(),
+                1 => center.y(),
+                _ => center.z(),
+            }
+        };
+        objects.sort_unstable_by(|a, b| {
+            component(&a.2)
+                .partial_cmp(&component(&b.2))
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let right = objects.split_off(objects.len() / 2);
+        let left = BVHNode::build(objects);
+        let right = BVHNode::build(right);
+        let container = AABB::surrounding_box(&left.container(), &right.container());
+
+        BVHNode::Branch {
+            left: Box::new(left),
+            right: Box::new(right),
             container,
         }
     }
+
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
+        if !self.container().hit(ray, t_min, t_max) {
+            return false;
+        }
+
+        match self {
+            BVHNode::Leaf {
+                object, object_id, ..
+            } => {
+                //a miss may still have written to the record, so only copy out real hits
+                let mut temp_hit_rec = HitRecord::new_invalid();
+                if object.hit(ray, t_min, t_max, &mut temp_hit_rec) {
+                    *hit_rec = temp_hit_rec;
+                    hit_rec.set_object_id(*object_id);
+                    true
+                } else {
+                    false
+                }
+            }
+            BVHNode::Branch { left, right, .. } => {
+                //the right side only has to beat whatever the left side hit
+                let hit_left = left.hit(ray, t_min, t_max, hit_rec);
+                let closest_so_far = if hit_left { hit_rec.t() } else { t_max };
+                let hit_right = right.hit(ray, t_min, closest_so_far, hit_rec);
+
+                hit_left || hit_right
+            }
+        }
+    }
 }
 
 impl Hittable for BVH {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
-        if !self.container.hit(ray, t_min, t_max) {
-            false
-        } else {
-            match &self.tree {
-                BVHNode::Leaf(leaf) => leaf.hit(ray, t_min, t_max, hit_rec),
-                BVHNode::Branch { left, right } => {
-                    let hit_left = left.hit(ray, t_min, t_max, hit_rec);
-                    let hit_right = right.hit(ray, t_min, t_max, hit_rec);
+        let mut temp_hit_rec = HitRecord::new_invalid();
+        let mut hit_anything = false;
+        let mut closest_so_far = t_max;
+
+        for (object_id, object) in &self.unbounded {
+            if object.hit(ray, t_min, closest_so_far, &mut temp_hit_rec) {
+                hit_anything = true;
+                closest_so_far = temp_hit_rec.t();
+                *hit_rec = temp_hit_rec;
+                hit_rec.set_object_id(*object_id);
+            }
+        }
 
-                    hit_left || hit_right
-                }
+        if let Some(root) = &self.root {
+            if root.hit(ray, t_min, closest_so_far, hit_rec) {
+                hit_anything = true;
             }
         }
+
+        hit_anything
     }
 
-    fn bounding_box(&self, t_0: f64, t_1: f64, output_box: &mut AABB) -> bool {
-        *output_box = self.container;
-        true
+    //only bounded when everything in it is
+    fn bounding_box(&self, _t_0: f64, _t_1: f64, output_box: &mut AABB) -> bool {
+        match &self.root {
+            Some(root) if self.unbounded.is_empty() => {
+                *output_box = root.container();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::{
+        bvh::*, hittable_list::HittableList, material::Material, plane::Plane, sphere::Sphere,
+        vec3::*,
+    };
+
+    #[test]
+    fn test_bvh_matches_list() {
+        let mut objects: Vec<Arc<dyn Hittable>> = vec![Plane::new_hittable(
+            Point3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Material::default(),
+        )];
+        for index in 0..20 {
+            let x = (index % 5) as f64 * 1.5 - 3.0;
+            let z = -((index / 5) as f64) * 1.5 - 2.0;
+            objects.push(Sphere::new_hittable(
+                Point3::new(x, 0.0, z),
+                0.5,
+                Material::default(),
+            ));
+        }
+
+        let mut list = HittableList::new();
+        for object in &objects {
+            list.add(object.clone());
+        }
+        let bvh = BVH::new(objects, 0.0, 1.0);
+
+        //unbounded objects make the whole thing unbounded, but don't stop it being built
+        let mut bounds = AABB::default();
+        assert!(!bvh.bounding_box(0.0, 1.0, &mut bounds));
+        assert!(!list.bounding_box(0.0, 1.0, &mut bounds));
+
+        for column in -10..=10 {
+            for row in -10..=10 {
+                let direction = Vec3::new(column as f64 * 0.05, row as f64 * 0.05, -1.0);
+                let ray = Ray::new(&Point3::new(0.0, 0.5, 3.0), &direction, 0.0);
+
+                let mut list_hit = HitRecord::new_invalid();
+                let mut bvh_hit = HitRecord::new_invalid();
+                let hit = list.hit(&ray, 0.001, f64::INFINITY, &mut list_hit);
+                assert_eq!(hit, bvh.hit(&ray, 0.001, f64::INFINITY, &mut bvh_hit));
+                if hit {
+                    assert!((list_hit.t() - bvh_hit.t()).abs() < 1e-9);
+                    assert_eq!(list_hit.object_id(), bvh_hit.object_id());
+                }
+            }
+        }
     }
 }
@@ -1,6 +1,4 @@
-use crate::lib::{ray::*, rt_math::*, vec3::*};
-
-use rand::prelude::*;
+use crate::lib::{ray::*, rt_math::*, sampler::Sampler, vec3::*};
 
 #[allow(dead_code)]
 pub struct Camera {
@@ -58,12 +56,17 @@ impl Camera {
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rand_in_disk = self.lens_radius * Vec3::random_in_unit_disk();
+    //distance of a point in front of the camera along the viewing direction
+    pub fn depth(&self, point: &Point3) -> f64 {
+        (*point - self.origin).dot(&-self.w)
+    }
+
+    //the sampler provides the lens position and the time within the shutter interval
+    pub fn get_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Ray {
+        let rand_in_disk = self.lens_radius * Vec3::in_unit_disk(sampler.next_2d());
         let offset = self.u * rand_in_disk.x() + self.v * rand_in_disk.y();
 
-        let mut rng = rand::thread_rng();
-        let time = rng.gen::<f64>() * (self.time_1 - self.time_0);
+        let time = self.time_0 + sampler.next_1d() * (self.time_1 - self.time_0);
 
         Ray::new(
             &(self.origin + offset),
@@ -25,6 +25,11 @@ impl Color {
         self.b
     }
 
+    //perceived brightness, using the Rec. 709 weights
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
     pub fn random() -> Self {
         let mut rng = rand::thread_rng();
 
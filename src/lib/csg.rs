@@ -0,0 +1,310 @@
+use crate::lib::{aabb::*, hittable::*, ray::Ray};
+use std::{cmp::Ordering, sync::Arc};
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    //the left solid with the right one cut out of it
+    Difference,
+}
+
+//boolean combination of two solids, built from the stretches of the ray spent inside each one.
+//The children need to be closed, or to override hit_intervals() with something sensible
+pub struct Csg {
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    operation: CsgOperation,
+}
+
+#[allow(dead_code)]
+impl CsgOperation {
+    fn inside(&self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Csg {
+    pub fn new(operation: CsgOperation, left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        Self {
+            left,
+            right,
+            operation,
+        }
+    }
+
+    pub fn new_hittable(
+        operation: CsgOperation,
+        left: Arc<dyn Hittable>,
+        right: Arc<dyn Hittable>,
+    ) -> Arc<dyn Hittable> {
+        Arc::new(Csg::new(operation, left, right))
+    }
+
+    pub fn union(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Arc<dyn Hittable> {
+        Csg::new_hittable(CsgOperation::Union, left, right)
+    }
+
+    pub fn intersection(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Arc<dyn Hittable> {
+        Csg::new_hittable(CsgOperation::Intersection, left, right)
+    }
+
+    pub fn difference(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Arc<dyn Hittable> {
+        Csg::new_hittable(CsgOperation::Difference, left, right)
+    }
+}
+
+impl Hittable for Csg {
+    //the first boundary of the combined solid that's strictly inside the range
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
+        let mut intervals = Vec::new();
+        if !self.hit_intervals(ray, t_min, t_max, &mut intervals) {
+            return false;
+        }
+
+        let first = intervals
+            .iter()
+            .flat_map(|(entry, exit)| [*entry, *exit])
+            .find(|boundary| boundary.t() > t_min && boundary.t() < t_max);
+
+        match first {
+            Some(boundary) => {
+                *hit_rec = boundary;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self, t_0: f64, t_1: f64, output_box: &mut AABB) -> bool {
+        let mut left = AABB::default();
+        let mut right = AABB::default();
+        let has_left = self.left.bounding_box(t_0, t_1, &mut left);
+        let has_right = self.right.bounding_box(t_0, t_1, &mut right);
+
+        match self.operation {
+            CsgOperation::Union if has_left && has_right => {
+                *output_box = AABB::surrounding_box(&left, &right);
+                true
+            }
+            CsgOperation::Union => false,
+            CsgOperation::Intersection => match (has_left, has_right) {
+                (true, true) => {
+                    *output_box = AABB::overlapping_box(&left, &right);
+                    true
+                }
+                (true, false) => {
+                    *output_box = left;
+                    true
+                }
+                (false, true) => {
+                    *output_box = right;
+                    true
+                }
+                (false, false) => false,
+            },
+            CsgOperation::Difference => {
+                *output_box = left;
+                has_left
+            }
+        }
+    }
+
+    //sweeps through both children's entries and exits in order, keeping the ones where being
+    //inside the combined solid changes
+    fn hit_intervals(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        intervals: &mut Vec<(HitRecord, HitRecord)>,
+    ) -> bool {
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        self.left.hit_intervals(ray, t_min, t_max, &mut left);
+        self.right.hit_intervals(ray, t_min, t_max, &mut right);
+
+        //(record, from the left child, entering that child)
+        let mut events: Vec<(HitRecord, bool, bool)> = Vec::new();
+        for (children, is_left) in [(left, true), (right, false)] {
+            for (entry, exit) in children {
+                events.push((entry, is_left, true));
+                events.push((exit, is_left, false));
+            }
+        }
+        events.sort_by(|a, b| a.0.t().partial_cmp(&b.0.t()).unwrap_or(Ordering::Equal));
+
+        let found = intervals.len();
+        let (mut in_left, mut in_right) = (false, false);
+        let mut entry: Option<HitRecord> = None;
+
+        for (mut boundary, is_left, entering) in events {
+            let was_inside = self.operation.inside(in_left, in_right);
+            if is_left {
+                in_left = entering;
+            } else {
+                in_right = entering;
+            }
+            let inside = self.operation.inside(in_left, in_right);
+            if inside == was_inside {
+                continue;
+            }
+
+            //eg. leaving the cut out solid is entering the difference, so the surface faces the
+            //other way
+            if entering != inside {
+                boundary.flip_face();
+            }
+            if inside {
+                entry = Some(boundary);
+            } else if let Some(entry) = entry.take() {
+                intervals.push((entry, boundary));
+            }
+        }
+
+        intervals.len() > found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::{
+        csg::*,
+        material::Material,
+        sdf::{Sdf, SdfObject},
+        sphere::Sphere,
+        vec3::*,
+    };
+
+    fn spheres() -> (Arc<dyn Hittable>, Arc<dyn Hittable>) {
+        (
+            Sphere::new_hittable(Point3::new(-0.5, 0.0, 0.0), 1.0, Material::default()),
+            Sphere::new_hittable(Point3::new(0.5, 0.0, 0.0), 1.0, Material::default()),
+        )
+    }
+
+    #[test]
+    fn test_csg_along_axis() {
+        let (left, right) = spheres();
+        let union = Csg::union(left.clone(), right.clone());
+        let intersection = Csg::intersection(left.clone(), right.clone());
+        let difference = Csg::difference(left, right);
+
+        let origin = Point3::new(-5.0, 0.0, 0.0);
+        let direction = Vec3::new(1.0, 0.0, 0.0);
+        let mut intervals = Vec::new();
+        let ray = Ray::new(&origin, &direction, 0.0);
+
+        //union is one stretch from -1.5 to 1.5
+        assert!(union.hit_intervals(&ray, 0.001, f64::INFINITY, &mut intervals));
+        assert_eq!(intervals.len(), 1);
+        assert!((intervals[0].0.t() - 3.5).abs() < 1e-9);
+        assert!((intervals[0].1.t() - 6.5).abs() < 1e-9);
+
+        //intersection is the lens from -0.5 to 0.5
+        let lens = trace(&*intersection, origin, direction).unwrap();
+        assert!((lens.t() - 4.5).abs() < 1e-9);
+        assert!(lens.front_face());
+        assert!((lens.normal() - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-9);
+
+        //the difference ends where the right sphere begins, and the surface there faces right,
+        //out of the cut
+        intervals.clear();
+        assert!(difference.hit_intervals(&ray, 0.001, f64::INFINITY, &mut intervals));
+        assert_eq!(intervals.len(), 1);
+        let exit = intervals[0].1;
+        assert!((exit.t() - 4.5).abs() < 1e-9);
+        assert!(!exit.front_face());
+        assert!((exit.outward_normal() - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+
+        //from the far side the first thing hit is the inside of the cut
+        let cut = trace(&*difference, Point3::new(5.0, 0.0, 0.0), -direction).unwrap();
+        assert!((cut.t() - 5.5).abs() < 1e-9);
+        assert!(cut.front_face());
+        assert!((cut.normal() - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+        let tangent_normal = cut.dpdu().cross(&cut.dpdv()).unit_vector();
+        assert!((tangent_normal - cut.outward_normal()).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_csg_from_inside() {
+        let (left, right) = spheres();
+        let difference = Csg::difference(left.clone(), right.clone());
+
+        //starting inside the left sphere, in the part that's kept
+        let origin = Point3::new(-1.0, 0.0, 0.0);
+        let back = trace(&*difference, origin, Vec3::new(-1.0, 0.0, 0.0)).unwrap();
+        assert!((back.t() - 0.5).abs() < 1e-9);
+        assert!(!back.front_face());
+        let forward = trace(&*difference, origin, Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!((forward.t() - 0.5).abs() < 1e-9);
+        assert!(!forward.front_face());
+
+        //inside the part that was cut away nothing is hit going right
+        assert!(trace(
+            &*difference,
+            Point3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0)
+        )
+        .is_none());
+
+        //putting the lens back gives the whole left sphere again
+        let nested = Csg::union(difference, Csg::intersection(left, right));
+        let whole = trace(
+            &*nested,
+            Point3::new(5.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!((whole.t() - 4.5).abs() < 1e-9);
+        assert!(whole.front_face());
+
+        let mut bounds = AABB::default();
+        assert!(nested.bounding_box(0.0, 1.0, &mut bounds));
+        assert!((bounds.max().x() - 0.5).abs() < 1e-9);
+        assert!((bounds.min().x() + 1.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_csg_with_sdf_child() {
+        //the same cut as above with the right sphere ray marched - its intervals have to match
+        //the analytic sphere's for the operations to work
+        let (left, _) = spheres();
+        let right = SdfObject::new_hittable(
+            Sdf::sphere(Point3::new(0.5, 0.0, 0.0), 1.0),
+            Material::default(),
+        );
+
+        let direction = Vec3::new(1.0, 0.0, 0.0);
+        let ray = Ray::new(&Point3::new(-5.0, 0.0, 0.0), &direction, 0.0);
+        let mut intervals = Vec::new();
+        assert!(right.hit_intervals(&ray, 0.001, f64::INFINITY, &mut intervals));
+        assert_eq!(intervals.len(), 1);
+        assert!((intervals[0].0.t() - 4.5).abs() < 1e-4);
+        assert!((intervals[0].1.t() - 6.5).abs() < 1e-4);
+        assert!(!intervals[0].1.front_face());
+
+        //from inside, the interval starts where the ray does
+        intervals.clear();
+        let inside = Ray::new(&Point3::new(0.5, 0.0, 0.0), &direction, 0.0);
+        assert!(right.hit_intervals(&inside, 0.001, f64::INFINITY, &mut intervals));
+        assert_eq!(intervals.len(), 1);
+        assert!((intervals[0].0.t() - 0.001).abs() < 1e-9);
+        assert!((intervals[0].1.t() - 1.0).abs() < 1e-4);
+
+        let difference = Csg::difference(left.clone(), right.clone());
+        let cut = trace(&*difference, Point3::new(5.0, 0.0, 0.0), -direction).unwrap();
+        assert!((cut.t() - 5.5).abs() < 1e-4);
+        assert!(cut.front_face());
+        assert!((cut.normal() - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-3);
+
+        let intersection = Csg::intersection(left, right);
+        let lens = trace(&*intersection, Point3::new(-5.0, 0.0, 0.0), direction).unwrap();
+        assert!((lens.t() - 4.5).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,353 @@
+use crate::lib::{aabb::*, hittable::*, material::Material, onb::Onb, ray::Ray, vec3::*};
+use std::sync::Arc;
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CurveKind {
+    //flat strip that always turns to face the ray, for hair and fur
+    Ribbon,
+    //round tube, shaded as if it had a circular cross section - only hit from the outside
+    Tube,
+}
+
+//cubic Bézier segment whose width goes linearly from 'widths.0' to 'widths.1'. It's intersected
+//directly by splitting it in half until the pieces are close to straight, so a whole strand only
+//takes up one slot in a BVH. u runs along the curve, v across it
+pub struct Curve {
+    control_points: [Point3; 4],
+    widths: (f64, f64),
+    kind: CurveKind,
+    material: Material,
+}
+
+//the part of the curve a recursive step is looking at, in ray space
+struct Segment {
+    points: [Vec3; 4],
+    u_0: f64,
+    u_1: f64,
+}
+
+#[allow(dead_code)]
+impl Curve {
+    pub fn new(control_points: [Point3; 4], widths: (f64, f64), material: Material) -> Self {
+        Self {
+            control_points,
+            widths,
+            kind: CurveKind::Ribbon,
+            material,
+        }
+    }
+
+    pub fn new_hittable(
+        control_points: [Point3; 4],
+        widths: (f64, f64),
+        material: Material,
+    ) -> Arc<dyn Hittable> {
+        Arc::new(Curve::new(control_points, widths, material))
+    }
+
+    pub fn with_kind(self, kind: CurveKind) -> Self {
+        Self { kind, ..self }
+    }
+
+    pub fn point(&self, u: f64) -> Point3 {
+        let [p0, p1, p2, p3] = self.control_points;
+        let s = 1.0 - u;
+        s * s * s * p0 + 3.0 * s * s * u * p1 + 3.0 * s * u * u * p2 + u * u * u * p3
+    }
+
+    pub fn tangent(&self, u: f64) -> Vec3 {
+        let [p0, p1, p2, p3] = self.control_points;
+        let s = 1.0 - u;
+        3.0 * s * s * (p1 - p0) + 6.0 * s * u * (p2 - p1) + 3.0 * u * u * (p3 - p2)
+    }
+
+    fn width(&self, u: f64) -> f64 {
+        self.widths.0 + (self.widths.1 - self.widths.0) * u
+    }
+
+    //the closest hit in a piece of the curve - distance along the ray, u and the center of the
+    //curve there - splitting it until it's flat enough to be treated as a straight line
+    fn hit_segment(
+        &self,
+        segment: &Segment,
+        depth: usize,
+        z_min: f64,
+        z_max: f64,
+        ray_space: &[Vec3; 4],
+    ) -> Option<(f64, f64, Vec3)> {
+        let half_width = 0.5 * self.widths.0.max(self.widths.1);
+        let points = &segment.points;
+
+        //the ray is the z axis, so it can only hit if the origin is inside the piece's xy box
+        let (mut low, mut high) = (
+            Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        );
+        for point in points.iter() {
+            low = Vec3::new(
+                low.x().min(point.x()),
+                low.y().min(point.y()),
+                low.z().min(point.z()),
+            );
+            high = Vec3::new(
+                high.x().max(point.x()),
+                high.y().max(point.y()),
+                high.z().max(point.z()),
+            );
+        }
+        if low.x() - half_width > 0.0
+            || high.x() + half_width < 0.0
+            || low.y() - half_width > 0.0
+            || high.y() + half_width < 0.0
+            || low.z() - half_width > z_max
+            || high.z() + half_width < z_min
+        {
+            return None;
+        }
+
+        if depth > 0 {
+            let [first, second] = split(segment);
+            let near = self.hit_segment(&first, depth - 1, z_min, z_max, ray_space);
+            let z_max = near.map_or(z_max, |(z, ..)| z);
+            return self
+                .hit_segment(&second, depth - 1, z_min, z_max, ray_space)
+                .or(near);
+        }
+
+        //the origin has to be between the lines through the ends, square to the curve
+        let [p0, p1, p2, p3] = *points;
+        if (p1.y() - p0.y()) * -p0.y() + p0.x() * (p0.x() - p1.x()) < 0.0
+            || (p2.y() - p3.y()) * -p3.y() + p3.x() * (p3.x() - p2.x()) < 0.0
+        {
+            return None;
+        }
+
+        //closest point to the origin on the straight line between the ends
+        let line = Vec3::new(p3.x() - p0.x(), p3.y() - p0.y(), 0.0);
+        let length_squared = line.length_squared();
+        if length_squared == 0.0 {
+            return None;
+        }
+        let w = (-(p0.x() * line.x() + p0.y() * line.y()) / length_squared).clamp(0.0, 1.0);
+        let u = segment.u_0 + (segment.u_1 - segment.u_0) * w;
+
+        let center = bezier(ray_space, u);
+        let half_width = 0.5 * self.width(u);
+        let distance_squared = center.x() * center.x() + center.y() * center.y();
+        if distance_squared > half_width * half_width {
+            return None;
+        }
+
+        //a tube's surface is in front of its center line
+        let z = match self.kind {
+            CurveKind::Ribbon => center.z(),
+            CurveKind::Tube => center.z() - (half_width * half_width - distance_squared).sqrt(),
+        };
+        if z <= z_min || z >= z_max {
+            return None;
+        }
+        Some((z, u, center))
+    }
+}
+
+fn bezier(points: &[Vec3; 4], u: f64) -> Vec3 {
+    let s = 1.0 - u;
+    s * s * s * points[0]
+        + 3.0 * s * s * u * points[1]
+        + 3.0 * s * u * u * points[2]
+        + u * u * u * points[3]
+}
+
+//de Casteljau split at the middle
+fn split(segment: &Segment) -> [Segment; 2] {
+    let [p0, p1, p2, p3] = segment.points;
+    let (a, b, c) = (0.5 * (p0 + p1), 0.5 * (p1 + p2), 0.5 * (p2 + p3));
+    let (d, e) = (0.5 * (a + b), 0.5 * (b + c));
+    let middle = 0.5 * (d + e);
+    let u_middle = 0.5 * (segment.u_0 + segment.u_1);
+
+    [
+        Segment {
+            points: [p0, a, d, middle],
+            u_0: segment.u_0,
+            u_1: u_middle,
+        },
+        Segment {
+            points: [middle, e, c, p3],
+            u_0: u_middle,
+            u_1: segment.u_1,
+        },
+    ]
+}
+
+impl Hittable for Curve {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
+        //looking down the ray, distances along it measured in world units
+        let length = ray.direction().length();
+        let frame = Onb::from_normal(&(ray.direction() / length));
+        let mut ray_space = [Vec3::default(); 4];
+        for (local, point) in ray_space.iter_mut().zip(self.control_points.iter()) {
+            *local = frame.world_to_local(&(*point - ray.origin()));
+        }
+
+        //split until the pieces are within a twentieth of the width of a straight line
+        let flatness = (0..2)
+            .map(|i| {
+                let bend = ray_space[i] - 2.0 * ray_space[i + 1] + ray_space[i + 2];
+                bend.x().abs().max(bend.y().abs()).max(bend.z().abs())
+            })
+            .fold(0.0, f64::max);
+        let epsilon = 0.05 * self.widths.0.max(self.widths.1);
+        let depth =
+            ((2f64.sqrt() * 6.0 * flatness / (8.0 * epsilon)).log2() / 2.0).clamp(0.0, 10.0);
+
+        let segment = Segment {
+            points: ray_space,
+            u_0: 0.0,
+            u_1: 1.0,
+        };
+        let (z, u, center) = match self.hit_segment(
+            &segment,
+            depth as usize,
+            t_min * length,
+            t_max * length,
+            &ray_space,
+        ) {
+            Some(hit) => hit,
+            None => return false,
+        };
+
+        //facing is the view direction square to the curve, side goes across it
+        let t = z / length;
+        let tangent = self.tangent(u);
+        let along = tangent.unit_vector();
+        let view = -ray.direction() / length;
+        let facing = view - view.dot(&along) * along;
+        let facing = if facing.length_squared() > 1e-12 {
+            facing.unit_vector()
+        } else {
+            Onb::from_normal(&along).u()
+        };
+        let side = facing.cross(&along);
+
+        //how far across the curve the ray passed, from -1 to 1
+        let half_width = 0.5 * self.width(u);
+        let offset = frame.local_to_world(&Vec3::new(-center.x(), -center.y(), 0.0));
+        let across = (offset.dot(&side) / half_width).clamp(-1.0, 1.0);
+
+        let outward_normal = match self.kind {
+            CurveKind::Ribbon => facing,
+            CurveKind::Tube => across * side + (1.0 - across * across).sqrt() * facing,
+        };
+
+        hit_rec.set_t(t);
+        hit_rec.set_p(ray.at(t));
+        hit_rec.set_face_normal(ray, &outward_normal);
+        hit_rec.set_uv(u, 0.5 * (across + 1.0));
+        hit_rec.set_tangents(&tangent, &(2.0 * half_width * side));
+        hit_rec.set_material(self.material);
+        true
+    }
+
+    //the curve stays inside the hull of its control points
+    fn bounding_box(&self, _t_0: f64, _t_1: f64, output_box: &mut AABB) -> bool {
+        let half_width = 0.5 * self.widths.0.max(self.widths.1);
+        let padding = Vec3::new(half_width, half_width, half_width);
+        let [p0, p1, p2, p3] = self.control_points;
+        let ends = AABB::surrounding_box(&AABB::new(&p0, &p0), &AABB::new(&p3, &p3));
+        let middle = AABB::surrounding_box(&AABB::new(&p1, &p1), &AABB::new(&p2, &p2));
+        let hull = AABB::surrounding_box(&ends, &middle);
+        *output_box = AABB::new(&(hull.min() - padding), &(hull.max() + padding));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::curve::*;
+
+    //along x from -2 to 2 at an even speed, tapering from 0.4 wide to 0.2
+    fn straight() -> Curve {
+        Curve::new(
+            [
+                Point3::new(-2.0, 0.0, 0.0),
+                Point3::new(-2.0 / 3.0, 0.0, 0.0),
+                Point3::new(2.0 / 3.0, 0.0, 0.0),
+                Point3::new(2.0, 0.0, 0.0),
+            ],
+            (0.4, 0.2),
+            Material::default(),
+        )
+    }
+
+    #[test]
+    fn test_straight_curve() {
+        let ribbon = straight();
+        let down = Vec3::new(0.0, 0.0, -2.0);
+
+        let center = trace(&ribbon, Point3::new(0.0, 0.0, 4.0), down).unwrap();
+        assert!((center.t() - 2.0).abs() < 1e-9);
+        assert!((center.u() - 0.5).abs() < 1e-6);
+        assert!((center.v() - 0.5).abs() < 1e-6);
+        assert!((center.normal() - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+        assert!((center.dpdu().unit_vector() - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+
+        //the width tapers, so the same offset is inside at the thick end and outside at the thin end
+        assert!(trace(&ribbon, Point3::new(-1.5, 0.15, 4.0), down).is_some());
+        assert!(trace(&ribbon, Point3::new(1.5, 0.15, 4.0), down).is_none());
+        assert!(trace(&ribbon, Point3::new(2.5, 0.0, 4.0), down).is_none());
+
+        //a tube is hit in front of its center line, with the normal turned out to the side
+        let tube = straight().with_kind(CurveKind::Tube);
+        let edge = trace(&tube, Point3::new(-0.8, 0.1, 4.0), down).unwrap();
+        let radius: f64 = 0.5 * (0.4 - 0.2 * 0.3);
+        let height = (radius * radius - 0.01).sqrt();
+        assert!((edge.point().z() - height).abs() < 1e-6);
+        assert!((edge.normal() - Vec3::new(0.0, 0.1, height) / radius).length() < 1e-6);
+        let tangent_normal = edge.dpdu().cross(&edge.dpdv());
+        assert!(tangent_normal.dot(&Vec3::new(0.0, 0.0, 1.0)) > 0.0);
+
+        //starting on the surface and leaving it doesn't hit it again
+        assert!(trace(&tube, edge.point(), Vec3::new(0.0, 1.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn test_bent_curve() {
+        //an arch over the origin, peaking at y = 0.75
+        let arch = Curve::new(
+            [
+                Point3::new(-1.0, 0.0, 0.0),
+                Point3::new(-1.0, 1.0, 0.0),
+                Point3::new(1.0, 1.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+            ],
+            (0.05, 0.05),
+            Material::default(),
+        );
+        let hit = trace(
+            &arch,
+            Point3::new(0.0, 0.76, 3.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        )
+        .unwrap();
+        assert!((hit.t() - 3.0).abs() < 1e-6);
+        assert!((hit.u() - 0.5).abs() < 1e-3);
+
+        //inside the arch, where a straight line between the ends would have been hit
+        assert!(trace(&arch, Point3::new(0.0, 0.2, 3.0), Vec3::new(0.0, 0.0, -1.0)).is_none());
+
+        //side on, looking along the curve's plane
+        let side = trace(
+            &arch,
+            Point3::new(0.0, 0.75, -3.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        )
+        .unwrap();
+        assert!((side.t() - 3.0).abs() < 1e-6);
+
+        let mut bounds = AABB::default();
+        assert!(arch.bounding_box(0.0, 1.0, &mut bounds));
+        assert!(bounds.max().y() >= 0.75);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::lib::{color::*, framebuffer::Framebuffer, vec3::*};
+
+use rayon::prelude::*;
+
+//joint bilateral filter - averages each pixel with its neighbours, but only with those that look like
+//the same surface in the albedo and normal AOVs, so edges and texture detail survive
+#[derive(Copy, Clone, Debug)]
+pub struct DenoiseParams {
+    pub radius: usize, //half width of the filter window in pixels
+    pub sigma_spatial: f64,
+    pub sigma_color: f64,  //how different the noisy illumination may be
+    pub sigma_normal: f64, //how far apart normals may point (1 - cos)
+    pub sigma_albedo: f64,
+}
+
+impl Default for DenoiseParams {
+    fn default() -> Self {
+        Self {
+            radius: 6,
+            sigma_spatial: 3.0,
+            sigma_color: 0.6,
+            sigma_normal: 0.1,
+            sigma_albedo: 0.1,
+        }
+    }
+}
+
+//smallest albedo component divided out before filtering, so black surfaces don't blow up
+const MIN_ALBEDO: f64 = 0.01;
+
+pub fn denoise(
+    beauty: &Framebuffer,
+    albedo: &Framebuffer,
+    normal: &Framebuffer,
+    params: &DenoiseParams,
+) -> Framebuffer {
+    let width = beauty.width();
+    let height = beauty.height();
+
+    //filter the illumination rather than the final color, then put the surface colors back on top
+    let illumination: Vec<Color> = beauty
+        .pixels()
+        .iter()
+        .zip(albedo.pixels())
+        .map(|(color, albedo)| demodulate(color, albedo))
+        .collect();
+
+    //pixels whose camera ray hit nothing have a zero normal - they keep their color and aren't
+    //blended into anything
+    let normals: Vec<Option<Vec3>> = normal
+        .pixels()
+        .iter()
+        .map(|normal| {
+            let normal = as_vec3(normal);
+            if normal.length_squared() > 0.0 {
+                Some(normal.unit_vector())
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let radius = params.radius as isize;
+    let two_sigma_spatial2 = 2.0 * params.sigma_spatial * params.sigma_spatial;
+    let two_sigma_color2 = 2.0 * params.sigma_color * params.sigma_color;
+    let two_sigma_normal2 = 2.0 * params.sigma_normal * params.sigma_normal;
+    let two_sigma_albedo2 = 2.0 * params.sigma_albedo * params.sigma_albedo;
+
+    let mut filtered = vec![Color::default(); width * height];
+
+    filtered
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(index, pixel)| {
+            let (column, row) = ((index % width) as isize, (index / width) as isize);
+
+            let center_normal = match normals[index] {
+                Some(normal) => normal,
+                None => {
+                    *pixel = beauty.pixels()[index];
+                    return;
+                }
+            };
+            let center_light = illumination[index];
+            let center_albedo = albedo.pixels()[index];
+
+            let mut sum = Color::default();
+            let mut weight_sum = 0.0;
+
+            for dy in -radius..=radius {
+                let y = row + dy;
+                if y < 0 || y >= height as isize {
+                    continue;
+                }
+                for dx in -radius..=radius {
+                    let x = column + dx;
+                    if x < 0 || x >= width as isize {
+                        continue;
+                    }
+                    let neighbour = y as usize * width + x as usize;
+                    let neighbour_normal = match normals[neighbour] {
+                        Some(normal) => normal,
+                        None => continue,
+                    };
+
+                    let light = illumination[neighbour];
+                    let light_distance = (light - center_light).length_squared();
+                    let normal_distance = 1.0 - center_normal.dot(&neighbour_normal);
+                    let albedo_distance =
+                        (albedo.pixels()[neighbour] - center_albedo).length_squared();
+
+                    let weight = (-((dx * dx + dy * dy) as f64) / two_sigma_spatial2
+                        - light_distance / two_sigma_color2
+                        - normal_distance * normal_distance / two_sigma_normal2
+                        - albedo_distance / two_sigma_albedo2)
+                        .exp();
+
+                    sum = sum + &light * weight;
+                    weight_sum += weight;
+                }
+            }
+
+            //the center pixel is compared with itself and gets a weight of 1, so weight_sum can't
+            //be zero
+            *pixel = remodulate(&(&sum / weight_sum), &center_albedo);
+        });
+
+    Framebuffer::from_pixels(width, height, filtered)
+}
+
+fn as_vec3(color: &Color) -> Vec3 {
+    Vec3::new(color.r(), color.g(), color.b())
+}
+
+fn demodulate(color: &Color, albedo: &Color) -> Color {
+    Color::new(
+        color.r() / albedo.r().max(MIN_ALBEDO),
+        color.g() / albedo.g().max(MIN_ALBEDO),
+        color.b() / albedo.b().max(MIN_ALBEDO),
+    )
+}
+
+fn remodulate(light: &Color, albedo: &Color) -> Color {
+    Color::new(
+        light.r() * albedo.r().max(MIN_ALBEDO),
+        light.g() * albedo.g().max(MIN_ALBEDO),
+        light.b() * albedo.b().max(MIN_ALBEDO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::denoise::*;
+
+    fn filled(width: usize, height: usize, color: Color) -> Framebuffer {
+        Framebuffer::from_pixels(width, height, vec![color; width * height])
+    }
+
+    #[test]
+    fn test_denoise_reduces_noise_on_flat_surface() {
+        let (width, height) = (16, 16);
+        //alternating bright and dark pixels average out to 0.5
+        let pixels = (0..width * height)
+            .map(|i| {
+                let value = if (i % width + i / width) % 2 == 0 {
+                    0.4
+                } else {
+                    0.6
+                };
+                Color::new(value, value, value)
+            })
+            .collect();
+        let beauty = Framebuffer::from_pixels(width, height, pixels);
+        let albedo = filled(width, height, Color::new(1.0, 1.0, 1.0));
+        let normal = filled(width, height, Color::new(0.0, 1.0, 0.0));
+
+        let result = denoise(&beauty, &albedo, &normal, &DenoiseParams::default());
+        let center = result.get(8, 8);
+        assert!((center.r() - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_denoise_keeps_normal_edges() {
+        let (width, height) = (16, 16);
+        //left half faces up and is bright, right half faces sideways and is dark
+        let mut beauty = filled(width, height, Color::new(0.1, 0.1, 0.1));
+        let albedo = filled(width, height, Color::new(1.0, 1.0, 1.0));
+        let mut normal = filled(width, height, Color::new(1.0, 0.0, 0.0));
+        for row in 0..height {
+            for column in 0..width / 2 {
+                beauty.set(column, row, Color::new(0.9, 0.9, 0.9));
+                normal.set(column, row, Color::new(0.0, 1.0, 0.0));
+            }
+        }
+
+        let result = denoise(&beauty, &albedo, &normal, &DenoiseParams::default());
+        assert!((result.get(7, 8).r() - 0.9).abs() < 1e-3);
+        assert!((result.get(8, 8).r() - 0.1).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_denoise_leaves_sky_alone() {
+        let (width, height) = (16, 16);
+        //the left half is sky with no AOV hit, the right half a grey surface facing the camera
+        let sky = Color::new(0.5, 0.7, 1.0);
+        let mut beauty = filled(width, height, Color::new(0.25, 0.25, 0.25));
+        let mut albedo = filled(width, height, Color::new(0.5, 0.5, 0.5));
+        let mut normal = filled(width, height, Color::new(0.0, 0.0, 1.0));
+        for row in 0..height {
+            for column in 0..width / 2 {
+                beauty.set(column, row, sky);
+                albedo.set(column, row, Color::default());
+                normal.set(column, row, Color::default());
+            }
+        }
+
+        let result = denoise(&beauty, &albedo, &normal, &DenoiseParams::default());
+        for row in 0..height {
+            let edge_sky = result.get(7, row);
+            assert_eq!((edge_sky.r(), edge_sky.g(), edge_sky.b()), (0.5, 0.7, 1.0));
+            assert!((result.get(8, row).r() - 0.25).abs() < 1e-9);
+            assert!((result.get(8, row).b() - 0.25).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::lib::{color::*, filter::Filter, framebuffer::Framebuffer};
+
+//accumulates filtered samples - each sample is splatted onto every pixel the filter reaches, and
+//each pixel ends up as the weighted average of what landed on it. A film can cover just part of the
+//image (a tile plus the margin its filter reaches into) and be merged into the full image later
+pub struct Film {
+    x0: isize, //image position of the film's first pixel
+    y0: isize,
+    width: usize,
+    height: usize,
+    filter: Filter,
+    weighted_colors: Vec<Color>,
+    weights: Vec<f64>,
+}
+
+impl Film {
+    pub fn new(x0: isize, y0: isize, width: usize, height: usize, filter: Filter) -> Self {
+        Self {
+            x0,
+            y0,
+            width,
+            height,
+            filter,
+            weighted_colors: vec![Color::default(); width * height],
+            weights: vec![0.0; width * height],
+        }
+    }
+
+    //a film covering the given pixels and everything the filter can reach from samples inside them
+    pub fn for_tile(x0: usize, y0: usize, width: usize, height: usize, filter: Filter) -> Self {
+        let margin = filter.radius().ceil() as usize;
+
+        Film::new(
+            x0 as isize - margin as isize,
+            y0 as isize - margin as isize,
+            width + 2 * margin,
+            height + 2 * margin,
+            filter,
+        )
+    }
+
+    //(x, y) is the continuous image position of the sample, with y growing down the image
+    pub fn add_sample(&mut self, x: f64, y: f64, color: &Color) {
+        let radius = self.filter.radius();
+
+        //pixel centers sit at half pixel offsets
+        let first_x = ((x - 0.5 - radius).ceil() as isize).max(self.x0);
+        let last_x = ((x - 0.5 + radius).floor() as isize).min(self.x0 + self.width as isize - 1);
+        let first_y = ((y - 0.5 - radius).ceil() as isize).max(self.y0);
+        let last_y = ((y - 0.5 + radius).floor() as isize).min(self.y0 + self.height as isize - 1);
+
+        for pixel_y in first_y..=last_y {
+            for pixel_x in first_x..=last_x {
+                let weight = self
+                    .filter
+                    .evaluate(x - (pixel_x as f64 + 0.5), y - (pixel_y as f64 + 0.5));
+                if weight == 0.0 {
+                    continue;
+                }
+
+                let index =
+                    (pixel_y - self.y0) as usize * self.width + (pixel_x - self.x0) as usize;
+                self.weighted_colors[index] = self.weighted_colors[index] + color * weight;
+                self.weights[index] += weight;
+            }
+        }
+    }
+
+    //add another film's sums onto the pixels the two have in common
+    pub fn merge(&mut self, other: &Film) {
+        for row in 0..other.height {
+            let y = other.y0 + row as isize - self.y0;
+            if y < 0 || y >= self.height as isize {
+                continue;
+            }
+            for column in 0..other.width {
+                let x = other.x0 + column as isize - self.x0;
+                if x < 0 || x >= self.width as isize {
+                    continue;
+                }
+
+                let from = row * other.width + column;
+                let to = y as usize * self.width + x as usize;
+                self.weighted_colors[to] = self.weighted_colors[to] + other.weighted_colors[from];
+                self.weights[to] += other.weights[from];
+            }
+        }
+    }
+
+    pub fn to_framebuffer(&self) -> Framebuffer {
+        let pixels = self
+            .weighted_colors
+            .iter()
+            .zip(&self.weights)
+            .map(|(color, &weight)| {
+                //filters with negative lobes can cancel out completely
+                if weight.abs() < 1e-12 {
+                    Color::default()
+                } else {
+                    color / weight
+                }
+            })
+            .collect();
+
+        Framebuffer::from_pixels(self.width, self.height, pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::film::*;
+
+    #[test]
+    fn test_box_filter_keeps_samples_in_their_pixel() {
+        let mut film = Film::new(0, 0, 3, 1, Filter::default());
+        film.add_sample(1.2, 0.5, &Color::new(1.0, 1.0, 1.0));
+        film.add_sample(1.9, 0.5, &Color::new(0.0, 0.0, 0.0));
+
+        let image = film.to_framebuffer();
+        assert_eq!(image.get(0, 0).r(), 0.0);
+        assert_eq!(image.get(1, 0).r(), 0.5);
+        assert_eq!(image.get(2, 0).r(), 0.0);
+    }
+
+    #[test]
+    fn test_tile_merge_matches_single_film() {
+        let filter = Filter::Tent { radius: 1.0 };
+        let samples = [(1.3, 1.7, 1.0), (2.1, 0.4, 0.25), (3.8, 1.2, 0.5)];
+
+        let mut whole = Film::new(0, 0, 4, 2, filter);
+        let mut merged = Film::new(0, 0, 4, 2, filter);
+        let mut left = Film::for_tile(0, 0, 2, 2, filter);
+        let mut right = Film::for_tile(2, 0, 2, 2, filter);
+
+        for &(x, y, value) in &samples {
+            let color = Color::new(value, value, value);
+            whole.add_sample(x, y, &color);
+            if x < 2.0 {
+                left.add_sample(x, y, &color);
+            } else {
+                right.add_sample(x, y, &color);
+            }
+        }
+        merged.merge(&left);
+        merged.merge(&right);
+
+        let (whole, merged) = (whole.to_framebuffer(), merged.to_framebuffer());
+        for (a, b) in whole.pixels().iter().zip(merged.pixels()) {
+            assert!((a.r() - b.r()).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+//pixel reconstruction filters - how much a sample counts towards each pixel around it, by its
+//distance from the pixel center. All of them are separable: w(x, y) = w(x) * w(y)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Filter {
+    Box { radius: f64 },
+    Tent { radius: f64 },
+    Gaussian { radius: f64, alpha: f64 }, //alpha sets how quickly the bell falls off
+    Mitchell { radius: f64, b: f64, c: f64 },
+    Lanczos { radius: f64 }, //sinc windowed by a sinc as wide as the radius
+}
+
+impl Default for Filter {
+    //a half pixel box only ever counts a sample towards the pixel it was taken in
+    fn default() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    pub fn radius(&self) -> f64 {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius } => radius,
+        }
+    }
+
+    pub fn with_radius(&self, radius: f64) -> Self {
+        match *self {
+            Filter::Box { .. } => Filter::Box { radius },
+            Filter::Tent { .. } => Filter::Tent { radius },
+            Filter::Gaussian { alpha, .. } => Filter::Gaussian { radius, alpha },
+            Filter::Mitchell { b, c, .. } => Filter::Mitchell { radius, b, c },
+            Filter::Lanczos { .. } => Filter::Lanczos { radius },
+        }
+    }
+
+    //(x, y) is the offset of the sample from the pixel center, in pixels
+    pub fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+
+    fn evaluate_1d(&self, x: f64) -> f64 {
+        match *self {
+            //half open, so a sample on the border between two pixels only counts once
+            Filter::Box { radius } => {
+                if x >= -radius && x < radius {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Filter::Tent { radius } => (radius - x.abs()).max(0.0),
+            Filter::Gaussian { radius, alpha } => {
+                //shifted down so it reaches zero at the radius instead of being cut off
+                ((-alpha * x * x).exp() - (-alpha * radius * radius).exp()).max(0.0)
+            }
+            Filter::Mitchell { radius, b, c } => {
+                if x.abs() >= radius {
+                    0.0
+                } else {
+                    mitchell_1d(2.0 * x / radius, b, c)
+                }
+            }
+            Filter::Lanczos { radius } => {
+                if x.abs() >= radius {
+                    0.0
+                } else {
+                    sinc(x) * sinc(x / radius)
+                }
+            }
+        }
+    }
+}
+
+//Mitchell-Netravali cubic on [-2, 2]
+fn mitchell_1d(x: f64, b: f64, c: f64) -> f64 {
+    let x = x.abs();
+    if x > 1.0 {
+        ((-b - 6.0 * c) * x * x * x
+            + (6.0 * b + 30.0 * c) * x * x
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0
+    } else {
+        ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+            + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+            + (6.0 - 2.0 * b))
+            / 6.0
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    //each filter with its usual radius and shape
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "box" => Ok(Filter::Box { radius: 0.5 }),
+            "tent" => Ok(Filter::Tent { radius: 1.0 }),
+            "gaussian" => Ok(Filter::Gaussian {
+                radius: 1.5,
+                alpha: 2.0,
+            }),
+            "mitchell" => Ok(Filter::Mitchell {
+                radius: 2.0,
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            }),
+            "lanczos" => Ok(Filter::Lanczos { radius: 2.0 }),
+            _ => Err(format!("unknown filter {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::filter::*;
+
+    #[test]
+    fn test_filters_vanish_at_radius() {
+        for name in &["box", "tent", "gaussian", "mitchell", "lanczos"] {
+            let filter: Filter = name.parse().unwrap();
+            let radius = filter.radius();
+            assert_eq!(filter.evaluate(radius, 0.0), 0.0, "{}", name);
+            assert!(filter.evaluate(0.0, 0.0) > 0.0, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_mitchell_reference_values() {
+        //B = C = 1/3 gives 8/9 at the center and 1/18 one pixel out
+        let filter: Filter = "mitchell".parse().unwrap();
+        assert!((filter.evaluate(0.0, 0.0) - (8.0 / 9.0) * (8.0 / 9.0)).abs() < 1e-12);
+        assert!((filter.evaluate(1.0, 0.0) - (1.0 / 18.0) * (8.0 / 9.0)).abs() < 1e-12);
+    }
+}
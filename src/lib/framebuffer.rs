@@ -0,0 +1,150 @@
+use crate::lib::{color::*, tonemap::ToneMapping};
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+//a grid of linear, unclamped colors - row 0 is the top of the image
+#[derive(Clone)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+#[allow(dead_code)]
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::default(); width * height],
+        }
+    }
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(pixels.len(), width * height);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    pub fn get(&self, column: usize, row: usize) -> Color {
+        self.pixels[row * self.width + column]
+    }
+
+    pub fn set(&mut self, column: usize, row: usize, color: Color) {
+        self.pixels[row * self.width + column] = color;
+    }
+
+    //plain text PPM, tone mapped down to 8 bit sRGB
+    pub fn write_ppm<W: Write>(&self, out: &mut W, tone_mapping: &ToneMapping) -> io::Result<()> {
+        writeln!(out, "P3\n{} {} \n255\n", self.width, self.height)?;
+
+        let mut rng = rand::thread_rng();
+        for pixel_color in &self.pixels {
+            let (r, g, b) = tone_mapping.srgb8(pixel_color, &mut rng);
+            writeln!(out, "{} {} {}", r, g, b)?;
+        }
+        Ok(())
+    }
+
+    pub fn save_ppm<P: AsRef<Path>>(&self, path: P, tone_mapping: &ToneMapping) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_ppm(&mut out, tone_mapping)?;
+        out.flush()
+    }
+
+    //Portable Float Map - keeps the full float range of each pixel
+    pub fn write_pfm<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_pfm_channels(path, true)
+    }
+
+    //single channel Portable Float Map, written from the red component
+    pub fn write_pfm_grayscale<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_pfm_channels(path, false)
+    }
+
+    fn write_pfm_channels<P: AsRef<Path>>(&self, path: P, rgb: bool) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+
+        //a negative scale marks the data as little endian
+        let kind = if rgb { "PF" } else { "Pf" };
+        write!(out, "{}\n{} {}\n-1.0\n", kind, self.width, self.height)?;
+
+        //PFM stores its rows from the bottom of the image up
+        for row in (0..self.height).rev() {
+            for pixel in &self.pixels[row * self.width..(row + 1) * self.width] {
+                out.write_all(&(pixel.r() as f32).to_le_bytes())?;
+                if rgb {
+                    out.write_all(&(pixel.g() as f32).to_le_bytes())?;
+                    out.write_all(&(pixel.b() as f32).to_le_bytes())?;
+                }
+            }
+        }
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::framebuffer::*;
+
+    fn read_floats(bytes: &[u8]) -> Vec<f32> {
+        bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_pfm_round_trip() {
+        //two columns, two rows, every channel different
+        let pixels = vec![
+            Color::new(0.0, 0.5, 1.0),
+            Color::new(2.0, 3.0, 4.0),
+            Color::new(-1.0, 0.25, 8.0),
+            Color::new(16.0, 32.0, 64.0),
+        ];
+        let framebuffer = Framebuffer::from_pixels(2, 2, pixels);
+        let path = std::env::temp_dir().join(format!("framebuffer-{}.pfm", std::process::id()));
+
+        framebuffer.write_pfm(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        //little endian, bottom row first
+        assert_eq!(
+            read_floats(&bytes[header.len()..]),
+            vec![-1.0, 0.25, 8.0, 16.0, 32.0, 64.0, 0.0, 0.5, 1.0, 2.0, 3.0, 4.0]
+        );
+
+        framebuffer.write_pfm_grayscale(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let header = b"Pf\n2 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(
+            read_floats(&bytes[header.len()..]),
+            vec![-1.0, 16.0, 0.0, 2.0]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,958 @@
+use crate::lib::{
+    camera::Camera, color::Color, hittable::Hittable, json::Json, material::Material,
+    mesh::TriangleMesh, principled::Principled, vec3::*,
+};
+use std::{fs, io, path::Path, sync::Arc};
+
+//a camera placed in the scene, in the terms Camera::new takes
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub struct GltfCamera {
+    pub look_from: Point3,
+    pub look_at: Point3,
+    pub v_up: Vec3,
+    pub v_fov: f64,                //vertical field of view, in degrees
+    pub aspect_ratio: Option<f64>, //what the file asks for, if anything
+}
+
+//the meshes and cameras of a glTF 2.0 scene with the node hierarchy flattened into world space.
+//Materials keep their metallic-roughness factors, textures aren't read
+#[derive(Clone, Default)]
+pub struct GltfScene {
+    meshes: Vec<(TriangleMesh, Principled)>,
+    cameras: Vec<GltfCamera>,
+}
+
+//row-major, applied to column vectors
+type Matrix = [[f64; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+//what the parts of a primitive are drawn as
+const TRIANGLES: usize = 4;
+const TRIANGLE_STRIP: usize = 5;
+const TRIANGLE_FAN: usize = 6;
+
+//extensions that can be ignored without getting the geometry wrong
+const KNOWN_EXTENSIONS: [&str; 3] = [
+    "KHR_mesh_quantization",
+    "KHR_texture_transform",
+    "KHR_materials_",
+];
+
+//the parsed json and the contents of every buffer it refers to
+struct Document {
+    json: Json,
+    buffers: Vec<Vec<u8>>,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[allow(dead_code)]
+impl GltfCamera {
+    //aspect_ratio is the image's, which may not be what the file asked for
+    pub fn camera(
+        &self,
+        aspect_ratio: f64,
+        aperture: f64,
+        focus: f64,
+        time_0: f64,
+        time_1: f64,
+    ) -> Camera {
+        Camera::new(
+            &self.look_from,
+            &self.look_at,
+            &self.v_up,
+            self.v_fov,
+            aspect_ratio,
+            aperture,
+            focus,
+            time_0,
+            time_1,
+        )
+    }
+}
+
+#[allow(dead_code)]
+impl GltfScene {
+    //a .gltf with its buffers embedded or next to it, or a self-contained .glb
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let directory = path.as_ref().parent().unwrap_or_else(|| Path::new(""));
+        read_gltf(&fs::read(path.as_ref())?, directory)
+    }
+
+    pub fn meshes(&self) -> &[(TriangleMesh, Principled)] {
+        &self.meshes
+    }
+
+    pub fn cameras(&self) -> &[GltfCamera] {
+        &self.cameras
+    }
+
+    //every mesh as triangles ready for the BVH, with vertex colors tinting the base color
+    pub fn triangles(&self) -> Vec<Arc<dyn Hittable>> {
+        self.meshes
+            .iter()
+            .flat_map(|(mesh, principled)| {
+                mesh.colored_triangles(&|color| {
+                    Material::principled(&Principled {
+                        base_color: Color::new(
+                            principled.base_color.r() * color.r(),
+                            principled.base_color.g() * color.g(),
+                            principled.base_color.b() * color.b(),
+                        ),
+                        ..*principled
+                    })
+                })
+            })
+            .collect()
+    }
+}
+
+//json or binary glTF, told apart by the magic number. External buffers are looked for relative to
+//'directory'
+pub fn read_gltf(data: &[u8], directory: &Path) -> io::Result<GltfScene> {
+    let (text, binary) = if data.starts_with(b"glTF") {
+        split_glb(data)?
+    } else {
+        (data, None)
+    };
+    let text = std::str::from_utf8(text).map_err(|_| invalid("glTF json isn't valid utf-8"))?;
+    let json = Json::parse(text.trim_start_matches('\u{feff}'))?;
+
+    let version = json
+        .get("asset")
+        .and_then(|asset| asset.get("version"))
+        .and_then(Json::as_str)
+        .ok_or_else(|| invalid("glTF asset has no version"))?;
+    if !version.starts_with("2.") {
+        return Err(invalid("only glTF 2.0 is supported"));
+    }
+    for extension in json
+        .get("extensionsRequired")
+        .map_or(&[][..], Json::as_array)
+    {
+        let name = extension.as_str().unwrap_or("");
+        if !KNOWN_EXTENSIONS.iter().any(|known| name.starts_with(known)) {
+            return Err(invalid(&format!(
+                "required glTF extension {} is not supported",
+                name
+            )));
+        }
+    }
+
+    let mut buffers = Vec::new();
+    for (index, buffer) in array(&json, "buffers").iter().enumerate() {
+        let contents = match buffer.get("uri").and_then(Json::as_str) {
+            Some(uri) if uri.starts_with("data:") => {
+                let start = uri
+                    .find(";base64,")
+                    .ok_or_else(|| invalid("glTF data uri isn't base64"))?;
+                decode_base64(&uri[start + 8..])?
+            }
+            Some(uri) => fs::read(directory.join(decode_uri(uri)))?,
+            //the first buffer of a .glb can be its binary chunk
+            None => match binary {
+                Some(binary) if index == 0 => binary.to_vec(),
+                _ => return Err(invalid("glTF buffer has no data")),
+            },
+        };
+        let length = index_of(buffer, "byteLength")?;
+        if contents.len() < length {
+            return Err(invalid("glTF buffer is shorter than its byteLength"));
+        }
+        buffers.push(contents);
+    }
+
+    let document = Document { json, buffers };
+    let mut scene = GltfScene::default();
+    for node in document.root_nodes()? {
+        document.visit(node, &IDENTITY, 0, &mut scene)?;
+    }
+    Ok(scene)
+}
+
+//the json and binary chunks of a .glb
+fn split_glb(data: &[u8]) -> io::Result<(&[u8], Option<&[u8]>)> {
+    let word = |offset: usize| -> io::Result<usize> {
+        data.get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+            .ok_or_else(|| invalid("glb file is truncated"))
+    };
+    if word(4)? != 2 {
+        return Err(invalid("only glTF 2.0 is supported"));
+    }
+    let length = word(8)?.min(data.len());
+
+    let mut chunks = Vec::new();
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let (chunk_length, kind) = (word(offset)?, word(offset + 4)?);
+        let chunk = data
+            .get(offset + 8..offset + 8 + chunk_length)
+            .ok_or_else(|| invalid("glb file is truncated"))?;
+        chunks.push((kind, chunk));
+        offset += 8 + chunk_length;
+    }
+    match chunks.as_slice() {
+        [(0x4e4f_534a, text), rest @ ..] => Ok((
+            text,
+            rest.iter()
+                .find(|(kind, _)| *kind == 0x004e_4942)
+                .map(|(_, binary)| *binary),
+        )),
+        _ => Err(invalid("glb file doesn't start with a json chunk")),
+    }
+}
+
+fn decode_base64(text: &str) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut bits, mut count) = (0u32, 0);
+    for character in text.bytes().take_while(|character| *character != b'=') {
+        let value = match character {
+            b'A'..=b'Z' => character - b'A',
+            b'a'..=b'z' => character - b'a' + 26,
+            b'0'..=b'9' => character - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err(invalid("bad character in base64 data")),
+        };
+        bits = ((bits << 6) | value as u32) & 0xffff;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+//uris are percent-encoded, file names usually aren't
+fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = bytes
+            .get(index + 1..index + 3)
+            .filter(|_| bytes[index] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+//a top level array of the document, empty if it isn't there
+fn array<'a>(json: &'a Json, key: &str) -> &'a [Json] {
+    json.get(key).map_or(&[], Json::as_array)
+}
+
+fn index_of(json: &Json, key: &str) -> io::Result<usize> {
+    json.get(key)
+        .and_then(Json::as_usize)
+        .ok_or_else(|| invalid(&format!("glTF {} is missing or not an index", key)))
+}
+
+//where something starts in its buffer or view, 0 if it doesn't say
+fn offset_of(json: &Json) -> io::Result<usize> {
+    match json.get("byteOffset") {
+        Some(_) => index_of(json, "byteOffset"),
+        None => Ok(0),
+    }
+}
+
+fn number_or(json: Option<&Json>, key: &str, default: f64) -> f64 {
+    json.and_then(|json| json.get(key))
+        .and_then(Json::as_f64)
+        .unwrap_or(default)
+}
+
+//a fixed length list of numbers, eg. a translation
+fn numbers_or(json: &Json, key: &str, default: &[f64]) -> io::Result<Vec<f64>> {
+    match json.get(key) {
+        None => Ok(default.to_vec()),
+        Some(value) => {
+            let numbers: Option<Vec<f64>> = value.as_array().iter().map(Json::as_f64).collect();
+            numbers
+                .filter(|numbers| numbers.len() == default.len())
+                .ok_or_else(|| invalid(&format!("glTF {} has the wrong length", key)))
+        }
+    }
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut product = [[0.0; 4]; 4];
+    for (row, product_row) in product.iter_mut().enumerate() {
+        for (column, value) in product_row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[row][k] * b[k][column]).sum();
+        }
+    }
+    product
+}
+
+fn transform(matrix: &Matrix, vector: &Vec3, w: f64) -> Vec3 {
+    let row = |i: usize| {
+        matrix[i][0] * vector.x()
+            + matrix[i][1] * vector.y()
+            + matrix[i][2] * vector.z()
+            + matrix[i][3] * w
+    };
+    Vec3::new(row(0), row(1), row(2))
+}
+
+//columns of the upper 3x3 - the cofactor matrix built from them is the inverse transpose times
+//the determinant, which is what normals go through
+fn normal_matrix(matrix: &Matrix) -> ([Vec3; 3], f64) {
+    let column = |j: usize| Vec3::new(matrix[0][j], matrix[1][j], matrix[2][j]);
+    let (a, b, c) = (column(0), column(1), column(2));
+    let cofactors = [b.cross(&c), c.cross(&a), a.cross(&b)];
+    (cofactors, a.dot(&b.cross(&c)))
+}
+
+//a node's own transform, either a matrix or translation, rotation and scale
+fn local_matrix(node: &Json) -> io::Result<Matrix> {
+    if node.get("matrix").is_some() {
+        //stored column by column
+        let values = numbers_or(node, "matrix", &IDENTITY.concat())?;
+        let mut matrix = IDENTITY;
+        for (index, value) in values.iter().enumerate() {
+            matrix[index % 4][index / 4] = *value;
+        }
+        return Ok(matrix);
+    }
+
+    let t = numbers_or(node, "translation", &[0.0, 0.0, 0.0])?;
+    let q = numbers_or(node, "rotation", &[0.0, 0.0, 0.0, 1.0])?;
+    let s = numbers_or(node, "scale", &[1.0, 1.0, 1.0])?;
+    let length = q.iter().map(|q| q * q).sum::<f64>().sqrt();
+    let (x, y, z, w) = if length > 0.0 {
+        (q[0] / length, q[1] / length, q[2] / length, q[3] / length)
+    } else {
+        (0.0, 0.0, 0.0, 1.0)
+    };
+    let rotation = [
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - z * w),
+            2.0 * (x * z + y * w),
+        ],
+        [
+            2.0 * (x * y + z * w),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - x * w),
+        ],
+        [
+            2.0 * (x * z - y * w),
+            2.0 * (y * z + x * w),
+            1.0 - 2.0 * (x * x + y * y),
+        ],
+    ];
+    let mut matrix = IDENTITY;
+    for row in 0..3 {
+        for column in 0..3 {
+            matrix[row][column] = rotation[row][column] * s[column];
+        }
+        matrix[row][3] = t[row];
+    }
+    Ok(matrix)
+}
+
+//size in bytes of one component
+fn component_size(component_type: usize) -> io::Result<usize> {
+    match component_type {
+        5120 | 5121 => Ok(1),
+        5122 | 5123 => Ok(2),
+        5125 | 5126 => Ok(4),
+        _ => Err(invalid("unknown glTF component type")),
+    }
+}
+
+//one little endian component as a float, normalized integers mapped to [0,1] or [-1,1]
+fn read_component(bytes: &[u8], component_type: usize, normalized: bool) -> f64 {
+    let (value, range) = match component_type {
+        5120 => (bytes[0] as i8 as f64, 127.0),
+        5121 => (bytes[0] as f64, 255.0),
+        5122 => (i16::from_le_bytes([bytes[0], bytes[1]]) as f64, 32767.0),
+        5123 => (u16::from_le_bytes([bytes[0], bytes[1]]) as f64, 65535.0),
+        5125 => (
+            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            4_294_967_295.0,
+        ),
+        _ => (
+            f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            1.0,
+        ),
+    };
+    if normalized && component_type != 5126 {
+        (value / range).max(-1.0)
+    } else {
+        value
+    }
+}
+
+//the metallic-roughness factors on the principled material, which has a parameter for each. The
+//defaults are glTF's, a rough white metal
+fn principled(material: Option<&Json>) -> io::Result<Principled> {
+    let pbr = material.and_then(|material| material.get("pbrMetallicRoughness"));
+    let base_color = match pbr {
+        Some(pbr) => numbers_or(pbr, "baseColorFactor", &[1.0; 4])?,
+        None => vec![1.0; 4],
+    };
+    let extension = |name: &str| {
+        material
+            .and_then(|material| material.get("extensions"))
+            .and_then(|extensions| extensions.get(name))
+    };
+
+    //the reflectance straight on that goes with the ior, where 0.5 is 4%
+    let ior = number_or(extension("KHR_materials_ior"), "ior", 1.5);
+    let f0 = ((ior - 1.0) / (ior + 1.0)).powi(2);
+    Ok(Principled {
+        base_color: Color::new(base_color[0], base_color[1], base_color[2]),
+        metallic: number_or(pbr, "metallicFactor", 1.0),
+        roughness: number_or(pbr, "roughnessFactor", 1.0),
+        specular: (f0 / 0.08).min(1.0),
+        clearcoat: number_or(extension("KHR_materials_clearcoat"), "clearcoatFactor", 0.0),
+        clearcoat_roughness: number_or(
+            extension("KHR_materials_clearcoat"),
+            "clearcoatRoughnessFactor",
+            0.0,
+        ),
+        transmission: number_or(
+            extension("KHR_materials_transmission"),
+            "transmissionFactor",
+            0.0,
+        ),
+        ior,
+        ..Principled::default()
+    })
+}
+
+impl Document {
+    //the nodes of the default scene - or without any scenes, every node that isn't a child
+    fn root_nodes(&self) -> io::Result<Vec<usize>> {
+        let scenes = array(&self.json, "scenes");
+        if !scenes.is_empty() {
+            let scene = match self.json.get("scene") {
+                Some(scene) => scene.as_usize().ok_or_else(|| invalid("bad glTF scene"))?,
+                None => 0,
+            };
+            let scene = scenes
+                .get(scene)
+                .ok_or_else(|| invalid("glTF scene out of range"))?;
+            return array(scene, "nodes")
+                .iter()
+                .map(|node| node.as_usize().ok_or_else(|| invalid("bad glTF node")))
+                .collect();
+        }
+
+        let nodes = array(&self.json, "nodes");
+        let mut is_child = vec![false; nodes.len()];
+        for node in nodes {
+            for child in array(node, "children").iter().filter_map(Json::as_usize) {
+                if let Some(is_child) = is_child.get_mut(child) {
+                    *is_child = true;
+                }
+            }
+        }
+        Ok((0..nodes.len()).filter(|node| !is_child[*node]).collect())
+    }
+
+    fn visit(
+        &self,
+        index: usize,
+        parent: &Matrix,
+        depth: usize,
+        scene: &mut GltfScene,
+    ) -> io::Result<()> {
+        let nodes = array(&self.json, "nodes");
+        let node = nodes
+            .get(index)
+            .ok_or_else(|| invalid("glTF node out of range"))?;
+        if depth > nodes.len() {
+            return Err(invalid("glTF node hierarchy has a cycle"));
+        }
+        let world = multiply(parent, &local_matrix(node)?);
+
+        if node.get("mesh").is_some() {
+            let mesh = array(&self.json, "meshes")
+                .get(index_of(node, "mesh")?)
+                .ok_or_else(|| invalid("glTF mesh out of range"))?;
+            for primitive in array(mesh, "primitives") {
+                if let Some(mesh) = self.primitive(primitive, &world)? {
+                    scene.meshes.push(mesh);
+                }
+            }
+        }
+        if node.get("camera").is_some() {
+            let camera = array(&self.json, "cameras")
+                .get(index_of(node, "camera")?)
+                .ok_or_else(|| invalid("glTF camera out of range"))?;
+            //orthographic cameras have nothing to map onto
+            if let Some(perspective) = camera.get("perspective") {
+                let look_from = transform(&world, &Point3::default(), 1.0);
+                let forward = transform(&world, &Vec3::new(0.0, 0.0, -1.0), 0.0);
+                scene.cameras.push(GltfCamera {
+                    look_from,
+                    look_at: look_from + forward.unit_vector(),
+                    v_up: transform(&world, &Vec3::new(0.0, 1.0, 0.0), 0.0),
+                    v_fov: number_or(Some(perspective), "yfov", 0.8).to_degrees(),
+                    aspect_ratio: perspective.get("aspectRatio").and_then(Json::as_f64),
+                });
+            }
+        }
+
+        for child in array(node, "children") {
+            let child = child.as_usize().ok_or_else(|| invalid("bad glTF node"))?;
+            self.visit(child, &world, depth + 1, scene)?;
+        }
+        Ok(())
+    }
+
+    //one part of a mesh in world space, None for points and lines
+    fn primitive(
+        &self,
+        primitive: &Json,
+        world: &Matrix,
+    ) -> io::Result<Option<(TriangleMesh, Principled)>> {
+        let mode = match primitive.get("mode") {
+            Some(mode) => mode.as_usize().ok_or_else(|| invalid("bad glTF mode"))?,
+            None => TRIANGLES,
+        };
+        if ![TRIANGLES, TRIANGLE_STRIP, TRIANGLE_FAN].contains(&mode) {
+            return Ok(None);
+        }
+        let attributes = primitive
+            .get("attributes")
+            .ok_or_else(|| invalid("glTF primitive has no attributes"))?;
+        let attribute = |name: &str| -> io::Result<Option<(Vec<f64>, usize)>> {
+            match attributes.get(name) {
+                Some(_) => self.accessor(index_of(attributes, name)?).map(Some),
+                None => Ok(None),
+            }
+        };
+
+        let values = match attribute("POSITION")? {
+            Some((values, 3)) => values,
+            Some(_) => return Err(invalid("glTF positions aren't 3d vectors")),
+            None => return Err(invalid("glTF primitive has no positions")),
+        };
+        let positions: Vec<Point3> = values
+            .chunks_exact(3)
+            .map(|p| transform(world, &Point3::new(p[0], p[1], p[2]), 1.0))
+            .collect();
+
+        let indices: Vec<usize> = match primitive.get("indices") {
+            Some(_) => {
+                let (indices, _) = self.accessor(index_of(primitive, "indices")?)?;
+                indices.iter().map(|index| *index as usize).collect()
+            }
+            None => (0..positions.len()).collect(),
+        };
+        if indices.iter().any(|index| *index >= positions.len()) {
+            return Err(invalid("glTF vertex index out of range"));
+        }
+        //a mirroring transform turns the faces inside out
+        let (cofactors, determinant) = normal_matrix(world);
+        let face = |a: usize, b: usize, c: usize| {
+            if determinant < 0.0 {
+                [indices[a], indices[c], indices[b]]
+            } else {
+                [indices[a], indices[b], indices[c]]
+            }
+        };
+        let count = indices.len();
+        let faces: Vec<[usize; 3]> = match mode {
+            TRIANGLES => (0..count / 3)
+                .map(|i| face(3 * i, 3 * i + 1, 3 * i + 2))
+                .collect(),
+            TRIANGLE_STRIP => (0..count.saturating_sub(2))
+                .map(|i| {
+                    if i % 2 == 0 {
+                        face(i, i + 1, i + 2)
+                    } else {
+                        face(i, i + 2, i + 1)
+                    }
+                })
+                .collect(),
+            _ => (0..count.saturating_sub(2))
+                .map(|i| face(i + 1, i + 2, 0))
+                .collect(),
+        };
+        let mut mesh = TriangleMesh::new(positions, faces);
+
+        let length = mesh.positions().len();
+        if let Some((values, 3)) = attribute("NORMAL")? {
+            let sign = determinant.signum();
+            let normals = values
+                .chunks_exact(3)
+                .map(|n| {
+                    let normal =
+                        sign * (n[0] * cofactors[0] + n[1] * cofactors[1] + n[2] * cofactors[2]);
+                    normal.unit_vector()
+                })
+                .collect::<Vec<Vec3>>();
+            if normals.len() == length {
+                mesh = mesh.with_normals(normals);
+            }
+        }
+        //glTF puts the origin of a texture at the top left
+        if let Some((values, 2)) = attribute("TEXCOORD_0")? {
+            let uvs: Vec<(f64, f64)> = values.chunks_exact(2).map(|t| (t[0], 1.0 - t[1])).collect();
+            if uvs.len() == length {
+                mesh = mesh.with_uvs(uvs);
+            }
+        }
+        if let Some((values, components)) = attribute("COLOR_0")? {
+            if components >= 3 {
+                let colors: Vec<Color> = values
+                    .chunks_exact(components)
+                    .map(|c| Color::new(c[0], c[1], c[2]))
+                    .collect();
+                if colors.len() == length {
+                    mesh = mesh.with_colors(colors);
+                }
+            }
+        }
+
+        let material = match primitive.get("material") {
+            Some(_) => Some(
+                array(&self.json, "materials")
+                    .get(index_of(primitive, "material")?)
+                    .ok_or_else(|| invalid("glTF material out of range"))?,
+            ),
+            None => None,
+        };
+        Ok(Some((mesh, principled(material)?)))
+    }
+
+    //an accessor's elements flattened, along with the number of components in each
+    fn accessor(&self, index: usize) -> io::Result<(Vec<f64>, usize)> {
+        let accessor = array(&self.json, "accessors")
+            .get(index)
+            .ok_or_else(|| invalid("glTF accessor out of range"))?;
+        let components = match accessor.get("type").and_then(Json::as_str) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err(invalid("unknown glTF accessor type")),
+        };
+        let component_type = index_of(accessor, "componentType")?;
+        let normalized = accessor
+            .get("normalized")
+            .and_then(Json::as_bool)
+            .unwrap_or(false);
+        let count = index_of(accessor, "count")?;
+
+        //without a buffer view everything starts out as zero - nothing in the file backs those, so
+        //it can't ask for more of them than its buffers have bytes, or a tiny file could take all
+        //the memory there is
+        let mut values = match accessor.get("bufferView") {
+            Some(_) => self.read(
+                index_of(accessor, "bufferView")?,
+                offset_of(accessor)?,
+                count,
+                components,
+                component_type,
+                normalized,
+            )?,
+            None => {
+                let limit: usize = self.buffers.iter().map(Vec::len).sum();
+                let length = count
+                    .checked_mul(components)
+                    .filter(|length| *length <= limit)
+                    .ok_or_else(|| invalid("glTF accessor without a buffer view is too large"))?;
+                vec![0.0; length]
+            }
+        };
+
+        //sparse accessors replace some of the elements
+        if let Some(sparse) = accessor.get("sparse") {
+            let sparse_count = index_of(sparse, "count")?;
+            let (indices, values_json) = match (sparse.get("indices"), sparse.get("values")) {
+                (Some(indices), Some(values)) => (indices, values),
+                _ => return Err(invalid("glTF sparse accessor is incomplete")),
+            };
+            let indices = self.read(
+                index_of(indices, "bufferView")?,
+                offset_of(indices)?,
+                sparse_count,
+                1,
+                index_of(indices, "componentType")?,
+                false,
+            )?;
+            let replacements = self.read(
+                index_of(values_json, "bufferView")?,
+                offset_of(values_json)?,
+                sparse_count,
+                components,
+                component_type,
+                normalized,
+            )?;
+            for (index, replacement) in indices.iter().zip(replacements.chunks_exact(components)) {
+                let start = *index as usize * components;
+                values
+                    .get_mut(start..start + components)
+                    .ok_or_else(|| invalid("glTF sparse index out of range"))?
+                    .copy_from_slice(replacement);
+            }
+        }
+        Ok((values, components))
+    }
+
+    //'count' elements from a buffer view, which may interleave them with other data
+    fn read(
+        &self,
+        view: usize,
+        offset: usize,
+        count: usize,
+        components: usize,
+        component_type: usize,
+        normalized: bool,
+    ) -> io::Result<Vec<f64>> {
+        let view = array(&self.json, "bufferViews")
+            .get(view)
+            .ok_or_else(|| invalid("glTF buffer view out of range"))?;
+        let buffer = self
+            .buffers
+            .get(index_of(view, "buffer")?)
+            .ok_or_else(|| invalid("glTF buffer out of range"))?;
+        let start = offset_of(view)?;
+        let bytes = start
+            .checked_add(index_of(view, "byteLength")?)
+            .and_then(|end| buffer.get(start..end))
+            .ok_or_else(|| invalid("glTF buffer view runs past its buffer"))?;
+
+        let size = component_size(component_type)?;
+        let stride = match view.get("byteStride").and_then(Json::as_usize) {
+            Some(stride) if stride > 0 => stride,
+            _ => size * components,
+        };
+        //the counts come from the file, so the arithmetic mustn't overflow either
+        let end = match count.checked_sub(1) {
+            Some(last) => last
+                .checked_mul(stride)
+                .and_then(|last| last.checked_add(size * components))
+                .and_then(|length| length.checked_add(offset)),
+            None => Some(offset),
+        };
+        match end {
+            Some(end) if end <= bytes.len() => (),
+            _ => return Err(invalid("glTF accessor runs past its buffer view")),
+        }
+
+        let mut values = Vec::with_capacity(count * components);
+        for element in 0..count {
+            for component in 0..components {
+                let at = offset + element * stride + component * size;
+                values.push(read_component(&bytes[at..], component_type, normalized));
+            }
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::gltf::*;
+
+    //a glb with the given json and binary chunk, padded to four bytes the way the format wants
+    fn glb(json: &str, binary: &[u8]) -> Vec<u8> {
+        let mut text = json.as_bytes().to_vec();
+        while !text.len().is_multiple_of(4) {
+            text.push(b' ');
+        }
+        let mut binary = binary.to_vec();
+        while !binary.len().is_multiple_of(4) {
+            binary.push(0);
+        }
+
+        let mut data = b"glTF".to_vec();
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&((28 + text.len() + binary.len()) as u32).to_le_bytes());
+        data.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        data.extend_from_slice(b"JSON");
+        data.extend_from_slice(&text);
+        data.extend_from_slice(&(binary.len() as u32).to_le_bytes());
+        data.extend_from_slice(b"BIN\0");
+        data.extend_from_slice(&binary);
+        data
+    }
+
+    fn close(a: &Vec3, b: &Vec3) -> bool {
+        (*a - *b).length() < 1e-9
+    }
+
+    #[test]
+    fn test_glb_hierarchy() {
+        //a quad as a strip, with normals and 8 bit colors interleaved with the positions
+        let mut binary = Vec::new();
+        let corners = [
+            [0.0f32, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 1.0, 0.0],
+        ];
+        for (index, corner) in corners.iter().enumerate() {
+            for value in corner.iter().chain(&[0.0f32, 0.0, 1.0]) {
+                binary.extend_from_slice(&value.to_le_bytes());
+            }
+            binary.extend_from_slice(&[255, 0, 51 * index as u8, 255]);
+        }
+        for index in &[0u16, 1, 2, 3] {
+            binary.extend_from_slice(&index.to_le_bytes());
+        }
+
+        //the quad is scaled by the parent, turned a quarter about y and moved by the child. The
+        //camera sits at z = 5 looking back at the origin
+        let json = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "scene": 0,
+                "scenes": [{{ "nodes": [0, 2] }}],
+                "nodes": [
+                    {{ "scale": [2, 2, 2], "children": [1] }},
+                    {{ "mesh": 0, "translation": [0, 0, 1],
+                       "rotation": [0, 0.7071067811865476, 0, 0.7071067811865476] }},
+                    {{ "camera": 0, "translation": [0, 0, 5] }}
+                ],
+                "meshes": [{{ "primitives": [{{
+                    "attributes": {{ "POSITION": 0, "NORMAL": 1, "COLOR_0": 2 }},
+                    "indices": 3, "mode": 5, "material": 0
+                }}] }}],
+                "materials": [{{
+                    "pbrMetallicRoughness": {{ "baseColorFactor": [0.5, 0.5, 0.5, 1],
+                                               "metallicFactor": 0, "roughnessFactor": 0.25 }},
+                    "extensions": {{ "KHR_materials_ior": {{ "ior": 1.5 }} }}
+                }}],
+                "cameras": [{{ "type": "perspective",
+                               "perspective": {{ "yfov": 0.5, "aspectRatio": 1.5, "znear": 0.1 }} }}],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3" }},
+                    {{ "bufferView": 0, "byteOffset": 12, "componentType": 5126, "count": 4,
+                       "type": "VEC3" }},
+                    {{ "bufferView": 0, "byteOffset": 24, "componentType": 5121, "count": 4,
+                       "type": "VEC4", "normalized": true }},
+                    {{ "bufferView": 1, "componentType": 5123, "count": 4, "type": "SCALAR" }}
+                ],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteLength": 112, "byteStride": 28 }},
+                    {{ "buffer": 0, "byteOffset": 112, "byteLength": 8 }}
+                ],
+                "buffers": [{{ "byteLength": {} }}]
+            }}"#,
+            binary.len()
+        );
+        let scene = read_gltf(&glb(&json, &binary), Path::new("")).unwrap();
+
+        assert_eq!(scene.meshes().len(), 1);
+        let (mesh, principled) = &scene.meshes()[0];
+        //x maps to -z and the whole thing is doubled, translation included
+        assert!(close(&mesh.positions()[0], &Point3::new(0.0, 0.0, 2.0)));
+        assert!(close(&mesh.positions()[1], &Point3::new(0.0, 0.0, 0.0)));
+        assert!(close(&mesh.positions()[3], &Point3::new(0.0, 2.0, 0.0)));
+        assert!(close(&mesh.normals()[2], &Vec3::new(1.0, 0.0, 0.0)));
+        assert!((mesh.colors()[1].b() - 0.2).abs() < 1e-9);
+        assert_eq!(mesh.faces(), &[[0, 1, 2], [1, 3, 2]]);
+        //the faces still wind counter-clockwise about the normal
+        let [a, b, c] = mesh.faces()[1];
+        let p = mesh.positions();
+        let winding = (p[b] - p[a]).cross(&(p[c] - p[a]));
+        assert!(winding.dot(&mesh.normals()[a]) > 0.0);
+
+        assert_eq!(principled.metallic, 0.0);
+        assert_eq!(principled.roughness, 0.25);
+        assert!((principled.specular - 0.5).abs() < 1e-9);
+        assert_eq!(scene.triangles().len(), 2);
+
+        assert_eq!(scene.cameras().len(), 1);
+        let camera = scene.cameras()[0];
+        assert!(close(&camera.look_from, &Point3::new(0.0, 0.0, 5.0)));
+        assert!(close(&camera.look_at, &Point3::new(0.0, 0.0, 4.0)));
+        assert!(close(&camera.v_up, &Vec3::new(0.0, 1.0, 0.0)));
+        assert!((camera.v_fov - 0.5f64.to_degrees()).abs() < 1e-9);
+        assert_eq!(camera.aspect_ratio, Some(1.5));
+    }
+
+    #[test]
+    fn test_gltf_embedded() {
+        //one triangle in a base64 data uri, no scenes, a mirroring matrix and the default material
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "nodes": [{ "mesh": 0, "matrix": [-1,0,0,0, 0,1,0,0, 0,0,1,0, 0,0,0,1] }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+            "accessors": [{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                            "max": [1, 1, 0], "min": [0, 0, 0] }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+            "buffers": [{ "byteLength": 36, "uri":
+                "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA" }]
+        }"#;
+        let scene = read_gltf(json.as_bytes(), Path::new("")).unwrap();
+        let (mesh, principled) = &scene.meshes()[0];
+        assert!(close(&mesh.positions()[1], &Point3::new(-1.0, 0.0, 0.0)));
+        assert_eq!(mesh.faces(), &[[0, 2, 1]]);
+        assert_eq!(principled.metallic, 1.0);
+        assert_eq!(principled.roughness, 1.0);
+        assert!(scene.cameras().is_empty());
+
+        //broken files are errors rather than panics
+        let old = json.replace("\"2.0\"", "\"1.0\"");
+        assert!(read_gltf(old.as_bytes(), Path::new("")).is_err());
+        let short = json.replace("\"byteLength\": 36 }]", "\"byteLength\": 48 }]");
+        assert!(read_gltf(short.as_bytes(), Path::new("")).is_err());
+        let compressed = json.replace(
+            "\"nodes\"",
+            "\"extensionsRequired\": [\"KHR_draco_mesh_compression\"], \"nodes\"",
+        );
+        assert!(read_gltf(compressed.as_bytes(), Path::new("")).is_err());
+        let cycle = json
+            .replace("\"mesh\": 0,", "\"mesh\": 0, \"children\": [0],")
+            .replace(
+                "\"nodes\": [{",
+                "\"scenes\": [{ \"nodes\": [0] }], \"nodes\": [{",
+            );
+        assert!(read_gltf(cycle.as_bytes(), Path::new("")).is_err());
+        assert!(read_gltf(b"glTF\x02\0\0\0", Path::new("")).is_err());
+        let huge = json.replace("\"count\": 3", "\"count\": 1e300");
+        assert!(read_gltf(huge.as_bytes(), Path::new("")).is_err());
+        let overflowing = json.replace(
+            "\"count\": 3",
+            "\"count\": 4294967295, \"byteOffset\": 4294967295",
+        );
+        assert!(read_gltf(overflowing.as_bytes(), Path::new("")).is_err());
+        let far = json.replace(
+            "\"byteLength\": 36 }]",
+            "\"byteLength\": 36, \"byteOffset\": 4294967295 }]",
+        );
+        assert!(read_gltf(far.as_bytes(), Path::new("")).is_err());
+        let unbacked = json.replace(
+            "\"bufferView\": 0, \"componentType\": 5126, \"count\": 3",
+            "\"componentType\": 5126, \"count\": 4294967295",
+        );
+        assert!(read_gltf(unbacked.as_bytes(), Path::new("")).is_err());
+        let flat = json.replace("\"VEC3\"", "\"VEC2\"");
+        assert!(read_gltf(flat.as_bytes(), Path::new("")).is_err());
+        let surrogate = json.replace("\"asset\"", "\"name\": \"\\ud800\\u0000\", \"asset\"");
+        assert!(read_gltf(surrogate.as_bytes(), Path::new("")).is_ok());
+    }
+}
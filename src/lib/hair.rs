@@ -0,0 +1,280 @@
+use crate::lib::{
+    color::*, hittable::HitRecord, microfacet::fresnel_dielectric, onb::Onb, ray::Ray,
+    sampler::Sampler, vec3::*,
+};
+
+use std::f64::consts::PI;
+
+//hair fibre scattering after Chiang et al., "A Practical and Controllable Hair and Fur Model for
+//Production Path Tracing" - light reflects off the fibre (R), goes through it (TT), reflects once
+//inside (TRT), with everything after lumped together. Meant for thin curves, where the hit
+//record's dpdu runs along the fibre and v goes across it
+#[derive(Copy, Clone)]
+pub struct Hair {
+    pub color: Color,             //what the pigment looks like on a head of hair
+    pub roughness: f64,           //spread along the fibre, 0 to 1
+    pub azimuthal_roughness: f64, //spread around the fibre, 0 to 1
+    pub tilt: f64,                //angle of the cuticle scales in degrees, shifts the highlights
+    pub ior: f64,
+}
+
+impl Default for Hair {
+    fn default() -> Self {
+        Self {
+            color: Color::new(0.35, 0.2, 0.1),
+            roughness: 0.3,
+            azimuthal_roughness: 0.3,
+            tilt: 2.0,
+            ior: 1.55,
+        }
+    }
+}
+
+//reflection, transmission, one internal reflection and the rest
+const LOBES: usize = 4;
+
+#[allow(dead_code)]
+impl Hair {
+    pub fn new(color: &Color, roughness: f64) -> Self {
+        Self {
+            color: *color,
+            roughness,
+            ..Hair::default()
+        }
+    }
+
+    //absorption inside the fibre that gives roughly 'color' after multiple scattering
+    fn absorption(&self) -> Color {
+        let b = self.azimuthal_roughness.clamp(0.01, 1.0);
+        let scale = 5.969 - 0.215 * b + 2.532 * b.powi(2) - 10.73 * b.powi(3)
+            + 5.574 * b.powi(4)
+            + 0.245 * b.powi(5);
+        let sigma = |c: f64| (c.clamp(1e-4, 1.0).ln() / scale).powi(2);
+        Color::new(
+            sigma(self.color.r()),
+            sigma(self.color.g()),
+            sigma(self.color.b()),
+        )
+    }
+
+    //how much light each lobe carries
+    fn attenuations(&self, cos_theta_o: f64, h: f64, transmittance: &Color) -> [Color; LOBES] {
+        let cos_gamma_o = (1.0 - h * h).max(0.0).sqrt();
+        let fresnel = fresnel_dielectric(cos_theta_o * cos_gamma_o, 1.0 / self.ior);
+        let per_channel = |t: f64| {
+            let reflected = fresnel;
+            let transmitted = (1.0 - fresnel).powi(2) * t;
+            let internal = transmitted * t * fresnel;
+            let rest = internal * fresnel * t / (1.0 - t * fresnel);
+            [reflected, transmitted, internal, rest]
+        };
+
+        let (r, g, b) = (
+            per_channel(transmittance.r()),
+            per_channel(transmittance.g()),
+            per_channel(transmittance.b()),
+        );
+        let mut lobes = [Color::default(); LOBES];
+        for (p, lobe) in lobes.iter_mut().enumerate() {
+            *lobe = Color::new(r[p], g[p], b[p]);
+        }
+        lobes
+    }
+
+    //picks a lobe by how much light it carries, samples its longitudinal and azimuthal spread and
+    //divides by the probability of having picked it
+    pub fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        //x runs along the fibre, z towards the viewer and y across the fibre
+        let wo_world = -ray_in.direction().unit_vector();
+        let along = if hit_rec.dpdu().length_squared() > 0.0 {
+            hit_rec.dpdu().unit_vector()
+        } else {
+            Onb::from_normal(&hit_rec.normal()).u()
+        };
+        let facing = wo_world - wo_world.dot(&along) * along;
+        let facing = if facing.length_squared() > 1e-12 {
+            facing.unit_vector()
+        } else {
+            Onb::from_normal(&along).u()
+        };
+        let frame = Onb::from_normal_and_tangent(&facing, &along);
+        let wo = frame.world_to_local(&wo_world);
+
+        let sin_theta_o = wo.x().clamp(-1.0, 1.0);
+        let cos_theta_o = (1.0 - sin_theta_o * sin_theta_o).max(1e-6).sqrt();
+        let phi_o = wo.z().atan2(wo.y());
+        let h = (2.0 * hit_rec.v() - 1.0).clamp(-1.0, 1.0);
+        let gamma_o = h.asin();
+
+        //the path through the fibre, seen from the side and from the end
+        let eta = self.ior;
+        let sin_theta_t = sin_theta_o / eta;
+        let cos_theta_t = (1.0 - sin_theta_t * sin_theta_t).max(1e-6).sqrt();
+        let eta_projected = (eta * eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o;
+        let sin_gamma_t = (h / eta_projected).clamp(-1.0, 1.0);
+        let cos_gamma_t = (1.0 - sin_gamma_t * sin_gamma_t).sqrt();
+        let gamma_t = sin_gamma_t.asin();
+
+        let absorption = self.absorption();
+        let path = 2.0 * cos_gamma_t / cos_theta_t;
+        let transmittance = Color::new(
+            (-absorption.r() * path).exp(),
+            (-absorption.g() * path).exp(),
+            (-absorption.b() * path).exp(),
+        );
+        let lobes = self.attenuations(cos_theta_o, h, &transmittance);
+
+        let weights: Vec<f64> = lobes.iter().map(|lobe| lobe.luminance()).collect();
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return false;
+        }
+        let mut pick = sampler.next_1d() * total;
+        let mut p = LOBES - 1;
+        for (lobe, weight) in weights.iter().enumerate() {
+            if pick < *weight {
+                p = lobe;
+                break;
+            }
+            pick -= weight;
+        }
+        if weights[p] <= 0.0 {
+            return false;
+        }
+
+        //the cuticle scales tilt R up the fibre and TT and TRT down it
+        let alpha = self.tilt.to_radians();
+        let mut sin_2k_alpha = [alpha.sin(), 0.0, 0.0];
+        let mut cos_2k_alpha = [alpha.cos(), 0.0, 0.0];
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2.0 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1].powi(2) - sin_2k_alpha[i - 1].powi(2);
+        }
+        let (sin_theta_op, cos_theta_op) = match p {
+            0 => (
+                sin_theta_o * cos_2k_alpha[1] - cos_theta_o * sin_2k_alpha[1],
+                cos_theta_o * cos_2k_alpha[1] + sin_theta_o * sin_2k_alpha[1],
+            ),
+            1 => (
+                sin_theta_o * cos_2k_alpha[0] + cos_theta_o * sin_2k_alpha[0],
+                cos_theta_o * cos_2k_alpha[0] - sin_theta_o * sin_2k_alpha[0],
+            ),
+            2 => (
+                sin_theta_o * cos_2k_alpha[2] + cos_theta_o * sin_2k_alpha[2],
+                cos_theta_o * cos_2k_alpha[2] - sin_theta_o * sin_2k_alpha[2],
+            ),
+            _ => (sin_theta_o, cos_theta_o),
+        };
+
+        //longitudinal spread, wider for the lobes that travel through the fibre
+        let beta_m = self.roughness.clamp(0.01, 1.0);
+        let variance = (0.726 * beta_m + 0.812 * beta_m.powi(2) + 3.7 * beta_m.powi(20)).powi(2);
+        let variance = match p {
+            0 => variance,
+            1 => 0.25 * variance,
+            _ => 4.0 * variance,
+        };
+        let (u_1, u_2) = sampler.next_2d();
+        let u_1 = u_1.max(1e-5);
+        let cos_theta = 1.0 + variance * (u_1 + (1.0 - u_1) * (-2.0 / variance).exp()).ln();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let sin_theta_i = (-cos_theta * sin_theta_op
+            + sin_theta * (2.0 * PI * u_2).cos() * cos_theta_op)
+            .clamp(-1.0, 1.0);
+        let cos_theta_i = (1.0 - sin_theta_i * sin_theta_i).sqrt();
+
+        //azimuthal spread about the direction the lobe leaves the fibre in
+        let beta_n = self.azimuthal_roughness.clamp(0.01, 1.0);
+        let scale =
+            (PI / 8.0).sqrt() * (0.265 * beta_n + 1.194 * beta_n.powi(2) + 5.372 * beta_n.powi(22));
+        let u_3 = sampler.next_1d();
+        let delta_phi = if p < LOBES - 1 {
+            let lobe = p as f64;
+            2.0 * lobe * gamma_t - 2.0 * gamma_o
+                + lobe * PI
+                + sample_trimmed_logistic(u_3, scale, -PI, PI)
+        } else {
+            2.0 * PI * u_3
+        };
+        let phi_i = phi_o + delta_phi;
+
+        let wi = Vec3::new(
+            sin_theta_i,
+            cos_theta_i * phi_i.cos(),
+            cos_theta_i * phi_i.sin(),
+        );
+        *attenuation = &lobes[p] * (total / weights[p]);
+        *scattered = Ray::new(&hit_rec.point(), &frame.local_to_world(&wi), ray_in.time());
+        true
+    }
+}
+
+fn logistic_cdf(x: f64, scale: f64) -> f64 {
+    1.0 / (1.0 + (-x / scale).exp())
+}
+
+//logistic distribution cut down to [low, high]
+fn sample_trimmed_logistic(u: f64, scale: f64, low: f64, high: f64) -> f64 {
+    let k = logistic_cdf(high, scale) - logistic_cdf(low, scale);
+    let x = -scale * (1.0 / (u * k + logistic_cdf(low, scale)) - 1.0).ln();
+    x.clamp(low, high)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::{hair::*, sampler::RandomSampler};
+
+    //average light returned by one bounce off a fibre along x, seen from the side
+    fn furnace(hair: &Hair) -> Color {
+        let mut sampler = RandomSampler::new();
+        let ray = Ray::new(
+            &Point3::new(0.3, 0.0, 1.0),
+            &Vec3::new(-0.3, 0.0, -1.0),
+            0.0,
+        );
+
+        let samples = 20_000;
+        let mut sum = Color::default();
+        for index in 0..samples {
+            //spread across the width of the fibre
+            let mut hit_rec = HitRecord::new_invalid();
+            hit_rec.set_face_normal(&ray, &Vec3::new(0.0, 0.0, 1.0));
+            hit_rec.set_uv(0.5, (index as f64 + 0.5) / samples as f64);
+            hit_rec.set_tangents(&Vec3::new(1.0, 0.0, 0.0), &Vec3::new(0.0, 1.0, 0.0));
+
+            let mut attenuation = Color::default();
+            let mut scattered = Ray::default();
+            if hair.scatter(
+                &ray,
+                &hit_rec,
+                &mut attenuation,
+                &mut scattered,
+                &mut sampler,
+            ) {
+                assert!((scattered.direction().length() - 1.0).abs() < 1e-9);
+                sum = sum + attenuation;
+            }
+        }
+        &sum / samples as f64
+    }
+
+    #[test]
+    fn test_hair_conserves_energy() {
+        //without pigment nothing is absorbed, so all the light comes back out
+        let white = furnace(&Hair::new(&Color::new(1.0, 1.0, 1.0), 0.3));
+        assert!((white.luminance() - 1.0).abs() < 0.02);
+
+        //pigmented hair absorbs some of it on the way through, more of the blue than the red. One
+        //fibre only gets a tint, the full color comes from light bouncing between many
+        let brown = furnace(&Hair::default());
+        assert!(brown.luminance() < white.luminance() - 0.05);
+        assert!(brown.r() > brown.g() && brown.g() > brown.b());
+    }
+}
@@ -0,0 +1,428 @@
+use crate::lib::{
+    aabb::*, hittable::*, material::Material, ray::Ray, texture::ImageTexture,
+    triangle::intersect_triangle, vec3::*,
+};
+use std::sync::Arc;
+
+//terrain over a grid of height samples, 'columns' along x by 'rows' along z, covering 'size.x' by
+//'size.z' from 'corner'. Heights are scaled by 'size.y', so an image's 0 to 1 spans the full
+//height. Each grid cell is split into two triangles, found by walking the cells the ray crosses
+//instead of testing them all, and shaded with normals interpolated from the vertices
+pub struct Heightfield {
+    corner: Point3,
+    size: Vec3,
+    columns: usize,
+    rows: usize,
+    heights: Vec<f64>, //row by row, starting at corner.z
+    normals: Vec<Vec3>,
+    lowest: f64,
+    highest: f64,
+    material: Material,
+}
+
+#[allow(dead_code)]
+impl Heightfield {
+    pub fn new(
+        corner: Point3,
+        size: Vec3,
+        columns: usize,
+        rows: usize,
+        heights: Vec<f64>,
+        material: Material,
+    ) -> Self {
+        assert!(columns >= 2 && rows >= 2);
+        assert_eq!(heights.len(), columns * rows);
+
+        let lowest = heights.iter().cloned().fold(f64::INFINITY, f64::min);
+        let highest = heights.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let mut heightfield = Self {
+            corner,
+            size,
+            columns,
+            rows,
+            heights,
+            normals: Vec::new(),
+            lowest,
+            highest,
+            material,
+        };
+        heightfield.normals = (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .map(|(column, row)| heightfield.vertex_normal(column, row))
+            .collect();
+        heightfield
+    }
+
+    pub fn new_hittable(
+        corner: Point3,
+        size: Vec3,
+        columns: usize,
+        rows: usize,
+        heights: Vec<f64>,
+        material: Material,
+    ) -> Arc<dyn Hittable> {
+        Arc::new(Heightfield::new(
+            corner, size, columns, rows, heights, material,
+        ))
+    }
+
+    //one sample per pixel from the brightness, with the top row of the image at corner.z so an
+    //ImageTexture of the same picture lines up with the uvs
+    pub fn from_image(
+        image: &ImageTexture,
+        corner: Point3,
+        size: Vec3,
+        material: Material,
+    ) -> Self {
+        let heights = (0..image.height())
+            .flat_map(|row| (0..image.width()).map(move |column| (column, row)))
+            .map(|(column, row)| image.pixel(column, row).luminance())
+            .collect();
+        Heightfield::new(
+            corner,
+            size,
+            image.width(),
+            image.height(),
+            heights,
+            material,
+        )
+    }
+
+    fn cell_size(&self) -> (f64, f64) {
+        (
+            self.size.x() / (self.columns - 1) as f64,
+            self.size.z() / (self.rows - 1) as f64,
+        )
+    }
+
+    fn height(&self, column: usize, row: usize) -> f64 {
+        self.heights[row * self.columns + column] * self.size.y()
+    }
+
+    fn vertex(&self, column: usize, row: usize) -> Point3 {
+        let (dx, dz) = self.cell_size();
+        self.corner
+            + Vec3::new(
+                column as f64 * dx,
+                self.height(column, row),
+                row as f64 * dz,
+            )
+    }
+
+    //central differences, one sided at the edges
+    fn vertex_normal(&self, column: usize, row: usize) -> Vec3 {
+        let (dx, dz) = self.cell_size();
+        let (left, right) = (column.saturating_sub(1), (column + 1).min(self.columns - 1));
+        let (back, front) = (row.saturating_sub(1), (row + 1).min(self.rows - 1));
+
+        let slope_x =
+            (self.height(right, row) - self.height(left, row)) / ((right - left) as f64 * dx);
+        let slope_z =
+            (self.height(column, front) - self.height(column, back)) / ((front - back) as f64 * dz);
+        Vec3::new(-slope_x, 1.0, -slope_z).unit_vector()
+    }
+
+    //the two triangles of a cell, wound so their normals point up, with the grid positions of
+    //their vertices
+    fn cell_triangles(&self, column: usize, row: usize) -> [[(usize, usize); 3]; 2] {
+        let (near, far) = (row, row + 1);
+        let (left, right) = (column, column + 1);
+        [
+            [(left, near), (left, far), (right, far)],
+            [(left, near), (right, far), (right, near)],
+        ]
+    }
+
+    fn hit_cell(
+        &self,
+        ray: &Ray,
+        column: usize,
+        row: usize,
+        t_min: f64,
+        t_max: f64,
+        hit_rec: &mut HitRecord,
+    ) -> bool {
+        let triangles = self.cell_triangles(column, row);
+        let mut closest: Option<(f64, f64, f64, usize)> = None;
+        for (index, corners) in triangles.iter().enumerate() {
+            let vertices = [
+                self.vertex(corners[0].0, corners[0].1),
+                self.vertex(corners[1].0, corners[1].1),
+                self.vertex(corners[2].0, corners[2].1),
+            ];
+            let closest_so_far = closest.map_or(t_max, |(t, ..)| t);
+            if let Some((t, b1, b2)) = intersect_triangle(ray, &vertices, t_min, closest_so_far) {
+                closest = Some((t, b1, b2, index));
+            }
+        }
+
+        let (t, b1, b2, corners) = match closest {
+            Some((t, b1, b2, index)) => (t, b1, b2, triangles[index]),
+            None => return false,
+        };
+        let b0 = 1.0 - b1 - b2;
+        let normal_at = |(column, row): (usize, usize)| self.normals[row * self.columns + column];
+        let vertex = |(column, row): (usize, usize)| self.vertex(column, row);
+
+        let geometric_normal = (vertex(corners[1]) - vertex(corners[0]))
+            .cross(&(vertex(corners[2]) - vertex(corners[0])))
+            .unit_vector();
+        let smooth_normal =
+            (b0 * normal_at(corners[0]) + b1 * normal_at(corners[1]) + b2 * normal_at(corners[2]))
+                .unit_vector();
+
+        //u along x, v from 1 at corner.z down to 0 at the far edge like an image's rows, which
+        //also makes dpdu x dpdv point up
+        let point = ray.at(t);
+        let offset = point - self.corner;
+        let dpdu = self.size.x() * Vec3::new(1.0, -smooth_normal.x() / smooth_normal.y(), 0.0);
+        let dpdv = self.size.z() * Vec3::new(0.0, smooth_normal.z() / smooth_normal.y(), -1.0);
+
+        hit_rec.set_t(t);
+        hit_rec.set_p(point);
+        hit_rec.set_face_normal(ray, &geometric_normal);
+        hit_rec.set_shading_normal(&smooth_normal);
+        hit_rec.set_uv(offset.x() / self.size.x(), 1.0 - offset.z() / self.size.z());
+        hit_rec.set_tangents(&dpdu, &dpdv);
+        hit_rec.set_material(self.material);
+        true
+    }
+
+    fn bounds(&self) -> AABB {
+        //padded so a flat field still has some thickness
+        let padding = 1e-4;
+        AABB::new(
+            &Point3::new(
+                self.corner.x(),
+                self.corner.y() + self.lowest * self.size.y() - padding,
+                self.corner.z(),
+            ),
+            &Point3::new(
+                self.corner.x() + self.size.x(),
+                self.corner.y() + self.highest * self.size.y() + padding,
+                self.corner.z() + self.size.z(),
+            ),
+        )
+    }
+}
+
+impl Hittable for Heightfield {
+    //steps through the cells under the ray in order (Amanatides-Woo), skipping the ones where
+    //the ray passes entirely above or below the cell's heights
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
+        let (t_enter, t_exit) = match self.bounds().intersect(ray, t_min, t_max) {
+            Some(range) => range,
+            None => return false,
+        };
+
+        let (dx, dz) = self.cell_size();
+        let (origin, direction) = (ray.origin(), ray.direction());
+        let start = ray.at(t_enter) - self.corner;
+        let cell = |offset: f64, cell_size: f64, cells: usize| {
+            ((offset / cell_size).floor().max(0.0) as usize).min(cells - 2)
+        };
+        let (mut column, mut row) = (
+            cell(start.x(), dx, self.columns),
+            cell(start.z(), dz, self.rows),
+        );
+
+        //distance along the ray to the next cell boundary, and between boundaries
+        let next_boundary = |index: usize, cell_size: f64, origin: f64, direction: f64| {
+            if direction > 0.0 {
+                ((index + 1) as f64 * cell_size - origin) / direction
+            } else if direction < 0.0 {
+                (index as f64 * cell_size - origin) / direction
+            } else {
+                f64::INFINITY
+            }
+        };
+        let local_origin = origin - self.corner;
+        let mut next_x = next_boundary(column, dx, local_origin.x(), direction.x());
+        let mut next_z = next_boundary(row, dz, local_origin.z(), direction.z());
+        let step_x = (dx / direction.x()).abs();
+        let step_z = (dz / direction.z()).abs();
+
+        let mut cell_enter = t_enter;
+        loop {
+            let cell_exit = next_x.min(next_z).min(t_exit);
+
+            let corners = [(0, 0), (1, 0), (0, 1), (1, 1)];
+            let (cell_low, cell_high) =
+                corners
+                    .iter()
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), (i, j)| {
+                        let height = self.corner.y() + self.height(column + i, row + j);
+                        (low.min(height), high.max(height))
+                    });
+            let (y_0, y_1) = (ray.at(cell_enter).y(), ray.at(cell_exit).y());
+            let overlaps = y_0.min(y_1) <= cell_high + 1e-9 && y_0.max(y_1) >= cell_low - 1e-9;
+
+            if overlaps && self.hit_cell(ray, column, row, t_min, t_max, hit_rec) {
+                return true;
+            }
+            if cell_exit >= t_exit {
+                return false;
+            }
+
+            if next_x < next_z {
+                if (direction.x() > 0.0 && column + 2 >= self.columns)
+                    || (direction.x() < 0.0 && column == 0)
+                {
+                    return false;
+                }
+                column = if direction.x() > 0.0 {
+                    column + 1
+                } else {
+                    column - 1
+                };
+                cell_enter = next_x;
+                next_x += step_x;
+            } else {
+                if (direction.z() > 0.0 && row + 2 >= self.rows)
+                    || (direction.z() < 0.0 && row == 0)
+                {
+                    return false;
+                }
+                row = if direction.z() > 0.0 {
+                    row + 1
+                } else {
+                    row - 1
+                };
+                cell_enter = next_z;
+                next_z += step_z;
+            }
+        }
+    }
+
+    fn bounding_box(&self, _t_0: f64, _t_1: f64, output_box: &mut AABB) -> bool {
+        *output_box = self.bounds();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::{color::Color, heightfield::*};
+
+    //a ridge along z, peaking in the middle column
+    fn ridge() -> Heightfield {
+        let columns = 9;
+        let heights = (0..columns * columns)
+            .map(|index| {
+                let column = (index % columns) as f64;
+                1.0 - (column - 4.0).abs() / 4.0
+            })
+            .collect();
+        Heightfield::new(
+            Point3::new(-4.0, 0.0, -4.0),
+            Vec3::new(8.0, 2.0, 8.0),
+            columns,
+            columns,
+            heights,
+            Material::default(),
+        )
+    }
+
+    #[test]
+    fn test_heightfield_hits() {
+        let ridge = ridge();
+
+        //straight down onto the slope, where the height is linear
+        let down = trace(
+            &ridge,
+            Point3::new(-2.0, 5.0, 0.3),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert!((down.t() - 4.0).abs() < 1e-9);
+        assert!(down.front_face());
+        assert!((down.normal() - Vec3::new(-0.5, 1.0, 0.0).unit_vector()).length() < 1e-9);
+        assert!((down.u() - 0.25).abs() < 1e-9);
+        let tangent_normal = down.dpdu().cross(&down.dpdv()).unit_vector();
+        assert!((tangent_normal - down.normal()).length() < 1e-9);
+
+        //skimming along x from far away crosses many cells before meeting the slope
+        let across = trace(
+            &ridge,
+            Point3::new(-10.0, 1.0, 2.5),
+            Vec3::new(1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!((across.point().x() + 2.0).abs() < 1e-9);
+        assert!(across.front_face());
+
+        //the same but diagonally, and from below
+        let diagonal = trace(
+            &ridge,
+            Point3::new(-9.0, 1.0, -9.0),
+            Vec3::new(1.0, 0.0, 1.0),
+        )
+        .unwrap();
+        assert!((diagonal.point().x() + 2.0).abs() < 1e-9);
+        let below = trace(
+            &ridge,
+            Point3::new(2.0, -3.0, 1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+        .unwrap();
+        assert!((below.point().y() - 1.0).abs() < 1e-9);
+        assert!(!below.front_face());
+
+        //above the peak or outside the grid
+        assert!(trace(
+            &ridge,
+            Point3::new(-10.0, 2.5, 0.0),
+            Vec3::new(1.0, 0.0, 0.0)
+        )
+        .is_none());
+        assert!(trace(
+            &ridge,
+            Point3::new(5.0, 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0)
+        )
+        .is_none());
+
+        //the peak is smoothed over, the same normal on both sides of the crease
+        let peak = trace(
+            &ridge,
+            Point3::new(0.0, 5.0, 1.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert!((peak.normal() - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_heightfield_from_image() {
+        //brighter at the top of the image, which is the near edge of the terrain
+        let image = ImageTexture::new(
+            2,
+            2,
+            vec![
+                Color::new(1.0, 1.0, 1.0),
+                Color::new(1.0, 1.0, 1.0),
+                Color::new(0.0, 0.0, 0.0),
+                Color::new(0.0, 0.0, 0.0),
+            ],
+        );
+        let slope = Heightfield::from_image(
+            &image,
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 1.0),
+            Material::default(),
+        );
+
+        let hit = trace(
+            &slope,
+            Point3::new(0.5, 5.0, 0.25),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert!((hit.point().y() - 0.75).abs() < 1e-9);
+        assert!((hit.v() - 0.75).abs() < 1e-9);
+
+        let mut bounds = AABB::default();
+        assert!(slope.bounding_box(0.0, 1.0, &mut bounds));
+        assert!((bounds.max().y() - 1.0).abs() < 1e-3);
+    }
+}
@@ -6,6 +6,11 @@ pub struct HitRecord {
     material: Material,
     t: f64,
     front_face: bool,
+    object_id: usize, //index of the scene object that was hit, 0 if unknown
+    u: f64,           //surface coordinates, for textures
+    v: f64,
+    dpdu: Vec3, //how the point moves with u and v - the tangent frame for normal and bump maps
+    dpdv: Vec3,
 }
 
 #[allow(dead_code)]
@@ -18,6 +23,11 @@ impl HitRecord {
             material: Material::default(),
             t: -1.0,
             front_face: false,
+            object_id: 0,
+            u: 0.0,
+            v: 0.0,
+            dpdu: Vec3::default(),
+            dpdv: Vec3::default(),
         }
     }
 
@@ -41,6 +51,35 @@ impl HitRecord {
         self.front_face
     }
 
+    pub fn object_id(&self) -> usize {
+        self.object_id
+    }
+
+    pub fn u(&self) -> f64 {
+        self.u
+    }
+
+    pub fn v(&self) -> f64 {
+        self.v
+    }
+
+    pub fn dpdu(&self) -> Vec3 {
+        self.dpdu
+    }
+
+    pub fn dpdv(&self) -> Vec3 {
+        self.dpdv
+    }
+
+    //the normal on the outside of the surface, whichever side the ray came from
+    pub fn outward_normal(&self) -> Vec3 {
+        if self.front_face {
+            self.normal
+        } else {
+            -self.normal
+        }
+    }
+
     pub fn set_p(&mut self, point: Point3) {
         self.point = point;
     }
@@ -53,6 +92,37 @@ impl HitRecord {
         self.material = material;
     }
 
+    pub fn set_object_id(&mut self, object_id: usize) {
+        self.object_id = object_id;
+    }
+
+    pub fn set_uv(&mut self, u: f64, v: f64) {
+        self.u = u;
+        self.v = v;
+    }
+
+    pub fn set_tangents(&mut self, dpdu: &Vec3, dpdv: &Vec3) {
+        self.dpdu = *dpdu;
+        self.dpdv = *dpdv;
+    }
+
+    //replace the normal used for shading with a perturbed outward normal, keeping the side the
+    //ray hit from the geometric normal
+    pub fn set_shading_normal(&mut self, outward_normal: &Vec3) {
+        if self.front_face {
+            self.normal = *outward_normal;
+        } else {
+            self.normal = -*outward_normal;
+        }
+    }
+
+    //treat the hit as coming from the other side of the surface, eg. the inside of a solid that
+    //has been cut away. The normal still faces the ray, and the tangents follow the new outside
+    pub fn flip_face(&mut self) {
+        self.front_face = !self.front_face;
+        self.dpdv = -self.dpdv;
+    }
+
     pub fn set_face_normal(&mut self, ray: &Ray, outward_normal: &Vec3) {
         self.front_face = ray.direction().dot(outward_normal) < 0.0;
 
@@ -78,4 +148,58 @@ pub trait Hittable: Sync + Send {
     {
         false
     }
+
+    //every stretch of [t_min, t_max] the ray spends inside the object, in order, as (entry, exit)
+    //hit records. A stretch that is already inside at t_min or still inside at t_max gets a stand
+    //in record with t set to that end. The default walks from hit to hit and pairs up front and
+    //back faces, which works for any closed surface that doesn't overlap itself
+    fn hit_intervals(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        intervals: &mut Vec<(HitRecord, HitRecord)>,
+    ) -> bool {
+        let clipped = |t: f64| {
+            let mut hit_rec = HitRecord::new_invalid();
+            hit_rec.set_t(t);
+            hit_rec
+        };
+
+        let found = intervals.len();
+        let mut entry: Option<HitRecord> = None;
+        let mut hit_rec = HitRecord::new_invalid();
+        let mut start = t_min;
+
+        while self.hit(ray, start, t_max, &mut hit_rec) {
+            if hit_rec.front_face() {
+                entry.get_or_insert(hit_rec);
+            } else {
+                let entry = entry.take().unwrap_or_else(|| clipped(t_min));
+                intervals.push((entry, hit_rec));
+            }
+            //step past the surface so the same hit isn't found again
+            start = hit_rec.t() + INTERVAL_EPSILON * hit_rec.t().abs().max(1.0);
+        }
+        if let Some(entry) = entry {
+            intervals.push((entry, clipped(t_max)));
+        }
+
+        intervals.len() > found
+    }
 }
+
+//the closest hit of one ray fired from 'origin', for the shape tests
+#[cfg(test)]
+pub fn trace(object: &dyn Hittable, origin: Point3, direction: Vec3) -> Option<HitRecord> {
+    let mut hit_rec = HitRecord::new_invalid();
+    let ray = Ray::new(&origin, &direction, 0.0);
+    if object.hit(&ray, 0.001, f64::INFINITY, &mut hit_rec) {
+        Some(hit_rec)
+    } else {
+        None
+    }
+}
+
+//relative step that takes a restarted search just past the surface it last hit
+pub const INTERVAL_EPSILON: f64 = 1e-9;
@@ -21,7 +21,33 @@ impl HittableList {
         self.objects.clear();
     }
 
-    pub fn bounding_box(&self, t_0: f64, t_1: f64, output_box: &mut AABB) -> bool {
+    pub fn objects(&self) -> &[Arc<dyn Hittable>] {
+        &self.objects
+    }
+}
+
+impl Hittable for HittableList {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
+        //initializing temp_hit_rec with a value with the understanding that all implementations of the 'hit()' function will set it's own values for HitReacord instances before reading from them
+        let mut temp_hit_rec = HitRecord::new_invalid();
+        let mut hit_anything = false;
+        let mut closest_so_far = t_max;
+
+        for (index, object) in self.objects.iter().enumerate() {
+            //default implementation of hit() is false
+            if object.hit(ray, t_min, closest_so_far, &mut temp_hit_rec) {
+                hit_anything = true;
+                closest_so_far = temp_hit_rec.t();
+                *hit_rec = temp_hit_rec;
+                //ids start at 1 so that 0 can stand for the background
+                hit_rec.set_object_id(index + 1);
+            }
+        }
+        hit_anything
+    }
+
+    //unbounded as soon as any object in it is
+    fn bounding_box(&self, t_0: f64, t_1: f64, output_box: &mut AABB) -> bool {
         if self.objects.is_empty() {
             return false;
         }
@@ -51,22 +77,3 @@ impl HittableList {
         true
     }
 }
-
-impl Hittable for HittableList {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
-        //initializing temp_hit_rec with a value with the understanding that all implementations of the 'hit()' function will set it's own values for HitReacord instances before reading from them
-        let mut temp_hit_rec = HitRecord::new_invalid();
-        let mut hit_anything = false;
-        let mut closest_so_far = t_max;
-
-        for object in &self.objects {
-            //default implementation of hit() is false
-            if object.hit(ray, t_min, closest_so_far, &mut temp_hit_rec) {
-                hit_anything = true;
-                closest_so_far = temp_hit_rec.t();
-                *hit_rec = temp_hit_rec;
-            }
-        }
-        hit_anything
-    }
-}
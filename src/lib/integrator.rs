@@ -0,0 +1,361 @@
+use crate::lib::{
+    color::*, hittable::*, material::Material, ray::Ray, rt_math::*, sampler::Sampler, vec3::*,
+};
+
+//an integrator decides how much light arrives at the camera along a given ray
+pub trait Integrator: Sync + Send {
+    //light arriving along a camera ray. The first surface the ray hits is kept in 'first_hit' so
+    //the AOVs don't have to trace it again, it stays None if the ray escapes
+    fn li(
+        &self,
+        ray: &Ray,
+        world: &dyn Hittable,
+        sampler: &mut dyn Sampler,
+        first_hit: &mut Option<HitRecord>,
+    ) -> Color;
+}
+
+//full global illumination - follows each path bounce by bounce until it escapes or runs out of depth
+pub struct PathTracer {
+    max_depth: u16,
+}
+
+//what the debug integrator should show for the first surface hit
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DebugChannel {
+    Normals,
+    Albedo,
+    Depth,
+}
+
+//visualizes a property of the first hit instead of shading it
+pub struct DebugIntegrator {
+    channel: DebugChannel,
+    max_distance: f64, //distance mapped to white when showing depth
+}
+
+//fraction of the hemisphere above the first hit that is not blocked within 'distance'
+pub struct AmbientOcclusion {
+    samples: usize,
+    distance: f64,
+}
+
+//only light that reaches the first hit straight from the sky, with a single scattering event
+pub struct DirectLighting;
+
+pub fn sky_color(ray: &Ray) -> Color {
+    let unit_direction: Vec3 = Vec3::unit_vector(&ray.direction());
+    let t = 0.5 * (unit_direction.y() + 1.0); //blue to white blend
+    let start_value = Color::new(1.0, 1.0, 1.0);
+    let end_value = Color::new(0.5, 0.7, 1.0);
+
+    //linear blend
+    // blendedValue = (1 − t)⋅startValue + t⋅endValue
+    (1.0 - t) * &start_value + t * &end_value
+}
+
+impl PathTracer {
+    pub fn new(max_depth: u16) -> Self {
+        Self { max_depth }
+    }
+}
+
+impl Integrator for PathTracer {
+    fn li(
+        &self,
+        ray: &Ray,
+        world: &dyn Hittable,
+        sampler: &mut dyn Sampler,
+        first_hit: &mut Option<HitRecord>,
+    ) -> Color {
+        let mut hit_rec = HitRecord::new_invalid();
+        let mut ray = *ray;
+
+        //product of the attenuations of every bounce so far - scales whatever light the path gathers next
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        //light gathered along the path so far - no material emits yet, so the only light comes from the sky
+        let radiance = Color::default();
+
+        for depth in 0..self.max_depth {
+            if !world.hit(&ray, 0.001, INFINITY as f64, &mut hit_rec) {
+                return radiance + &throughput * &sky_color(&ray);
+            }
+            if depth == 0 {
+                *first_hit = Some(hit_rec);
+            }
+
+            let mut scattered = Ray::default();
+            let mut attenuation = Color::default();
+
+            if !Material::scatter(
+                &hit_rec.material(),
+                &ray,
+                &hit_rec,
+                &mut attenuation,
+                &mut scattered,
+                sampler,
+            ) {
+                return radiance;
+            }
+
+            throughput = &throughput * &attenuation; //not real vector multiplication - just scaling by the attenuation values
+
+            //materials scatter in RGB, the path keeps its wavelength
+            ray = scattered.with_wavelength(ray.wavelength());
+        }
+
+        //if we've exceeded the ray bounce limit, no more light is gathered
+        radiance
+    }
+}
+
+impl DebugIntegrator {
+    pub fn new(channel: DebugChannel, max_distance: f64) -> Self {
+        Self {
+            channel,
+            max_distance,
+        }
+    }
+}
+
+impl Integrator for DebugIntegrator {
+    fn li(
+        &self,
+        ray: &Ray,
+        world: &dyn Hittable,
+        _sampler: &mut dyn Sampler,
+        first_hit: &mut Option<HitRecord>,
+    ) -> Color {
+        let mut hit_rec = HitRecord::new_invalid();
+
+        if !world.hit(ray, 0.001, INFINITY as f64, &mut hit_rec) {
+            return Color::default();
+        }
+        *first_hit = Some(hit_rec);
+
+        match self.channel {
+            //map each component of the unit normal from [-1,1] to [0,1]
+            DebugChannel::Normals => 0.5 * &(hit_rec.normal_to_color() + Color::new(1.0, 1.0, 1.0)),
+            DebugChannel::Albedo => hit_rec.material().albedo(),
+            DebugChannel::Depth => {
+                let distance = hit_rec.t() * ray.direction().length();
+                let shade = 1.0 - (distance / self.max_distance).min(1.0);
+                Color::new(shade, shade, shade)
+            }
+        }
+    }
+}
+
+impl AmbientOcclusion {
+    pub fn new(samples: usize, distance: f64) -> Self {
+        Self { samples, distance }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn li(
+        &self,
+        ray: &Ray,
+        world: &dyn Hittable,
+        sampler: &mut dyn Sampler,
+        first_hit: &mut Option<HitRecord>,
+    ) -> Color {
+        let mut hit_rec = HitRecord::new_invalid();
+
+        if !world.hit(ray, 0.001, INFINITY as f64, &mut hit_rec) {
+            return Color::new(1.0, 1.0, 1.0);
+        }
+        *first_hit = Some(hit_rec);
+
+        let mut occlusion_rec = HitRecord::new_invalid();
+        let mut unoccluded = 0;
+
+        for _ in 0..self.samples {
+            //cosine weighted direction about the normal
+            let direction = hit_rec.normal() + Vec3::unit_vector_from_sample(sampler.next_2d());
+            let probe = Ray::new(&hit_rec.point(), &direction.unit_vector(), ray.time());
+
+            if !world.hit(&probe, 0.001, self.distance, &mut occlusion_rec) {
+                unoccluded += 1;
+            }
+        }
+
+        let visibility = unoccluded as f64 / self.samples.max(1) as f64;
+        Color::new(visibility, visibility, visibility)
+    }
+}
+
+impl Integrator for DirectLighting {
+    fn li(
+        &self,
+        ray: &Ray,
+        world: &dyn Hittable,
+        sampler: &mut dyn Sampler,
+        first_hit: &mut Option<HitRecord>,
+    ) -> Color {
+        let mut hit_rec = HitRecord::new_invalid();
+
+        if !world.hit(ray, 0.001, INFINITY as f64, &mut hit_rec) {
+            return sky_color(ray);
+        }
+        *first_hit = Some(hit_rec);
+
+        let mut scattered = Ray::default();
+        let mut attenuation = Color::default();
+
+        if !Material::scatter(
+            &hit_rec.material(),
+            ray,
+            &hit_rec,
+            &mut attenuation,
+            &mut scattered,
+            sampler,
+        ) {
+            return Color::default();
+        }
+
+        //the sky only counts if nothing blocks the scattered ray
+        let mut shadow_rec = HitRecord::new_invalid();
+        if world.hit(&scattered, 0.001, INFINITY as f64, &mut shadow_rec) {
+            Color::default()
+        } else {
+            &attenuation * &sky_color(&scattered)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::{hittable_list::*, integrator::*, sampler::RandomSampler, sphere::*};
+
+    fn single_sphere(material: Material) -> HittableList {
+        let mut world = HittableList::new();
+        world.add(Sphere::new_hittable(
+            Point3::new(0.0, 0.0, -2.0),
+            0.5,
+            material,
+        ));
+        world
+    }
+
+    fn toward_sphere() -> Ray {
+        Ray::new(&Point3::new(0.0, 0.0, 0.0), &Vec3::new(0.0, 0.0, -1.0), 0.0)
+    }
+
+    #[test]
+    fn test_path_tracer_miss_returns_sky() {
+        let world = HittableList::new();
+        let ray = Ray::new(&Point3::new(0.0, 0.0, 0.0), &Vec3::new(0.0, 1.0, 0.0), 0.0);
+
+        let color = PathTracer::new(50).li(&ray, &world, &mut RandomSampler::new(), &mut None);
+        assert_eq!((color.r(), color.g(), color.b()), (0.5, 0.7, 1.0));
+    }
+
+    #[test]
+    fn test_path_tracer_depth_limit() {
+        let world = single_sphere(Material::lambertian(&Color::new(0.5, 0.5, 0.5)));
+
+        let color = PathTracer::new(0).li(
+            &toward_sphere(),
+            &world,
+            &mut RandomSampler::new(),
+            &mut None,
+        );
+        assert_eq!((color.r(), color.g(), color.b()), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_path_tracer_glass_passes_sky_through() {
+        //an index of 1.0 means the sphere doesn't bend or reflect the ray, so it must see the same sky
+        let world = single_sphere(Material::dielectrtic(1.0));
+
+        let color = PathTracer::new(50).li(
+            &toward_sphere(),
+            &world,
+            &mut RandomSampler::new(),
+            &mut None,
+        );
+        let expected = sky_color(&toward_sphere());
+        assert!((color.r() - expected.r()).abs() < 1e-9);
+        assert!((color.g() - expected.g()).abs() < 1e-9);
+        assert!((color.b() - expected.b()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_debug_integrator_albedo() {
+        let world = single_sphere(Material::lambertian(&Color::new(0.2, 0.4, 0.6)));
+
+        let color = DebugIntegrator::new(DebugChannel::Albedo, 1.0).li(
+            &toward_sphere(),
+            &world,
+            &mut RandomSampler::new(),
+            &mut None,
+        );
+        assert_eq!((color.r(), color.g(), color.b()), (0.2, 0.4, 0.6));
+    }
+
+    #[test]
+    fn test_debug_integrator_normals() {
+        let world = single_sphere(Material::default());
+
+        //the ray hits the front of the sphere, whose normal points straight back at the camera
+        let color = DebugIntegrator::new(DebugChannel::Normals, 1.0).li(
+            &toward_sphere(),
+            &world,
+            &mut RandomSampler::new(),
+            &mut None,
+        );
+        assert_eq!((color.r(), color.g(), color.b()), (0.5, 0.5, 1.0));
+    }
+
+    fn from_center() -> Ray {
+        Ray::new(&Point3::new(0.0, 0.0, -2.0), &Vec3::new(0.0, 1.0, 0.0), 0.0)
+    }
+
+    #[test]
+    fn test_ambient_occlusion() {
+        let world = single_sphere(Material::default());
+        let ao = AmbientOcclusion::new(16, 2.0);
+        let mut sampler = RandomSampler::new();
+
+        //nothing but sky above the front of a lone sphere, and nothing at all for a miss
+        let mut first_hit = None;
+        let open = ao.li(&toward_sphere(), &world, &mut sampler, &mut first_hit);
+        assert_eq!((open.r(), open.g(), open.b()), (1.0, 1.0, 1.0));
+        assert!(first_hit.is_some());
+        let miss = Ray::new(&Point3::new(0.0, 0.0, 0.0), &Vec3::new(0.0, 1.0, 0.0), 0.0);
+        let sky = ao.li(&miss, &world, &mut sampler, &mut None);
+        assert_eq!((sky.r(), sky.g(), sky.b()), (1.0, 1.0, 1.0));
+
+        //inside the sphere every probe runs into the far wall, unless it's too far away to count
+        let closed = ao.li(&from_center(), &world, &mut sampler, &mut None);
+        assert_eq!((closed.r(), closed.g(), closed.b()), (0.0, 0.0, 0.0));
+        let near =
+            AmbientOcclusion::new(16, 1e-6).li(&from_center(), &world, &mut sampler, &mut None);
+        assert_eq!((near.r(), near.g(), near.b()), (1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_direct_lighting() {
+        let world = single_sphere(Material::lambertian(&Color::new(0.5, 0.5, 0.5)));
+        let mut sampler = RandomSampler::new();
+
+        let miss = Ray::new(&Point3::new(0.0, 0.0, 0.0), &Vec3::new(0.0, 1.0, 0.0), 0.0);
+        let sky = DirectLighting.li(&miss, &world, &mut sampler, &mut None);
+        assert_eq!((sky.r(), sky.g(), sky.b()), (0.5, 0.7, 1.0));
+
+        //the sky is fully blue in every direction, so one bounce off grey leaves exactly half. The
+        //other channels depend on where the bounce went
+        for _ in 0..16 {
+            let mut first_hit = None;
+            let lit = DirectLighting.li(&toward_sphere(), &world, &mut sampler, &mut first_hit);
+            assert!((lit.b() - 0.5).abs() < 1e-9);
+            assert!(lit.r() >= 0.25 && lit.r() <= 0.5);
+            assert!(first_hit.is_some());
+        }
+
+        //from inside, the bounce can only hit the sphere again
+        let shadowed = DirectLighting.li(&from_center(), &world, &mut sampler, &mut None);
+        assert_eq!((shadowed.r(), shadowed.g(), shadowed.b()), (0.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,325 @@
+use std::io;
+
+//just enough JSON to read scene descriptions - objects keep their keys in file order
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[allow(dead_code)]
+impl Json {
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            position: 0,
+        };
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.position != parser.bytes.len() {
+            return Err(invalid("trailing characters after JSON value"));
+        }
+        Ok(value)
+    }
+
+    //member of an object, None for anything else or a missing key
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    //non-negative whole numbers that fit in 32 bits only, for indices and counts
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(number)
+                if *number >= 0.0 && *number <= u32::MAX as f64 && number.fract() == 0.0 =>
+            {
+                Some(*number as usize)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    //elements of an array, empty for anything else
+    pub fn as_array(&self) -> &[Json] {
+        match self {
+            Json::Array(elements) => elements,
+            _ => &[],
+        }
+    }
+}
+
+//deeper than this is almost certainly a broken file, and would blow the stack
+const MAX_DEPTH: usize = 256;
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> io::Result<()> {
+        if self.peek() == Some(byte) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(invalid("unexpected character in JSON"))
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> io::Result<Json> {
+        if self.bytes[self.position..].starts_with(word.as_bytes()) {
+            self.position += word.len();
+            Ok(value)
+        } else {
+            Err(invalid("unexpected character in JSON"))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> io::Result<Json> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("JSON nested too deeply"));
+        }
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.object(depth),
+            Some(b'[') => self.array(depth),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            Some(_) => Err(invalid("unexpected character in JSON")),
+            None => Err(invalid("unexpected end of JSON")),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> io::Result<Json> {
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            members.push((key, self.value(depth + 1)?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(invalid("expected ',' or '}' in JSON object")),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> io::Result<Json> {
+        self.expect(b'[')?;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(Json::Array(elements));
+        }
+        loop {
+            elements.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(Json::Array(elements));
+                }
+                _ => return Err(invalid("expected ',' or ']' in JSON array")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> io::Result<Json> {
+        let start = self.position;
+        while let Some(b'-') | Some(b'+') | Some(b'.') | Some(b'e') | Some(b'E')
+        | Some(b'0'..=b'9') = self.peek()
+        {
+            self.position += 1;
+        }
+        //the input came from a &str and these are all ascii, so this can't fail
+        let text = std::str::from_utf8(&self.bytes[start..self.position]).unwrap();
+        text.parse()
+            .map(Json::Number)
+            .map_err(|_| invalid("bad number in JSON"))
+    }
+
+    fn hex_escape(&mut self) -> io::Result<u32> {
+        let digits = self
+            .bytes
+            .get(self.position..self.position + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| invalid("bad \\u escape in JSON string"))?;
+        self.position += 4;
+        Ok(digits)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        self.expect(b'"')?;
+        let mut text = String::new();
+        loop {
+            //copy everything up to the next quote or escape in one go
+            let start = self.position;
+            while let Some(byte) = self.peek() {
+                if byte == b'"' || byte == b'\\' {
+                    break;
+                }
+                self.position += 1;
+            }
+            //the input is valid utf-8 and we only stopped on ascii, so the slice is too
+            text.push_str(std::str::from_utf8(&self.bytes[start..self.position]).unwrap());
+
+            match self.peek() {
+                Some(b'"') => {
+                    self.position += 1;
+                    return Ok(text);
+                }
+                Some(b'\\') => self.position += 1,
+                _ => return Err(invalid("unterminated JSON string")),
+            }
+            let escape = self
+                .peek()
+                .ok_or_else(|| invalid("unterminated JSON string"))?;
+            self.position += 1;
+            let character = match escape {
+                b'"' => '"',
+                b'\\' => '\\',
+                b'/' => '/',
+                b'b' => '\u{8}',
+                b'f' => '\u{c}',
+                b'n' => '\n',
+                b'r' => '\r',
+                b't' => '\t',
+                b'u' => {
+                    let mut code = self.hex_escape()?;
+                    //characters outside the basic plane come as a pair of surrogates - a lone
+                    //one becomes a replacement character and whatever follows is read as usual
+                    if (0xd800..0xdc00).contains(&code)
+                        && self.bytes[self.position..].starts_with(b"\\u")
+                    {
+                        self.position += 2;
+                        match self.hex_escape()? {
+                            low @ 0xdc00..=0xdfff => {
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00)
+                            }
+                            _ => self.position -= 6,
+                        }
+                    }
+                    std::char::from_u32(code).unwrap_or('\u{fffd}')
+                }
+                _ => return Err(invalid("bad escape in JSON string")),
+            };
+            text.push(character);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::json::*;
+
+    #[test]
+    fn test_json() {
+        let json = Json::parse(
+            r#" { "name": "caf\u00e9 \"\ud83d\ude00\"\n", "values": [1, -2.5, 3e2, true, null],
+                  "empty": {}, "nested": { "list": [] } } "#,
+        )
+        .unwrap();
+        assert_eq!(
+            json.get("name").and_then(Json::as_str),
+            Some("café \"😀\"\n")
+        );
+        let values = json.get("values").unwrap().as_array();
+        assert_eq!(values.len(), 5);
+        assert_eq!(values[0].as_usize(), Some(1));
+        assert_eq!(values[1].as_f64(), Some(-2.5));
+        assert_eq!(values[1].as_usize(), None);
+        assert_eq!(values[2].as_f64(), Some(300.0));
+        assert_eq!(values[3].as_bool(), Some(true));
+        assert_eq!(values[4], Json::Null);
+        assert_eq!(json.get("empty"), Some(&Json::Object(Vec::new())));
+        assert!(json
+            .get("nested")
+            .and_then(|nested| nested.get("list"))
+            .unwrap()
+            .as_array()
+            .is_empty());
+        assert!(json.get("missing").is_none());
+
+        assert!(Json::parse("[1, 2").is_err());
+        assert!(Json::parse("{\"a\" 1}").is_err());
+        assert!(Json::parse("\"open").is_err());
+        assert!(Json::parse("[1] 2").is_err());
+        assert!(Json::parse("nul").is_err());
+        assert!(Json::parse(&"[".repeat(1000)).is_err());
+
+        //unpaired surrogates and numbers too big to index with don't take the parser down
+        assert_eq!(
+            Json::parse(r#""\ud800\u0000""#).unwrap(),
+            Json::String("\u{fffd}\u{0}".to_string())
+        );
+        assert_eq!(
+            Json::parse(r#""\udc00x""#).unwrap(),
+            Json::String("\u{fffd}x".to_string())
+        );
+        assert_eq!(Json::parse("1e300").unwrap().as_usize(), None);
+        assert_eq!(Json::parse("4294967296").unwrap().as_usize(), None);
+    }
+}
@@ -1,13 +1,37 @@
-use crate::lib::{color::*, hittable::HitRecord, ray::Ray, vec3::*};
+use crate::lib::{
+    color::*, hair::Hair, hittable::HitRecord, microfacet::*, onb::Onb, principled::Principled,
+    ray::Ray, sampler::Sampler, spectrum::Dispersion, vec3::*,
+};
 
 use partial_min_max::min;
-use rand::prelude::*;
 
 #[derive(Copy, Clone)]
 pub enum Material {
-    Lambertian { albedo: Color },
-    Metal { albedo: Color, fuzz: f64 },
-    Dielectric { refractive_index: f64 },
+    Lambertian {
+        albedo: Color,
+    },
+    Metal {
+        albedo: Color,
+        fuzz: f64,
+    },
+    //glass-like - roughness frosts the surface with a GGX distribution, absorption tints the
+    //inside by how much of each channel is lost per unit of distance travelled (Beer-Lambert).
+    //Dispersion only shows up in spectral mode, where rays carry a wavelength
+    Dielectric {
+        refractive_index: f64,
+        roughness: f64,
+        absorption: Color,
+        dispersion: Dispersion,
+    },
+    //rough metal with a GGX microfacet distribution - eta + ik is the complex index of refraction
+    //per color channel, roughness goes from 0 (mirror) to 1
+    Conductor {
+        eta: Color,
+        k: Color,
+        roughness: f64,
+    },
+    Principled(Principled),
+    Hair(Hair),
 }
 
 impl Default for Material {
@@ -24,15 +48,126 @@ impl Material {
         Material::Lambertian { albedo: *albedo }
     }
 
+    //fuzz is clamped to 1, past that the reflections stop looking like metal
     pub fn metal(albedo: &Color, fuzz: f64) -> Self {
         Material::Metal {
             albedo: *albedo,
-            fuzz,
+            fuzz: min(fuzz, 1.0),
         }
     }
 
+    pub fn principled(principled: &Principled) -> Self {
+        Material::Principled(*principled)
+    }
+
+    pub fn hair(hair: &Hair) -> Self {
+        Material::Hair(*hair)
+    }
+
+    pub fn conductor(eta: &Color, k: &Color, roughness: f64) -> Self {
+        Material::Conductor {
+            eta: *eta,
+            k: *k,
+            roughness,
+        }
+    }
+
+    //measured indices of refraction, sampled at roughly 650nm, 550nm and 450nm
+    pub fn gold(roughness: f64) -> Self {
+        Material::conductor(
+            &Color::new(0.143, 0.374, 1.442),
+            &Color::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> Self {
+        Material::conductor(
+            &Color::new(0.200, 0.924, 1.102),
+            &Color::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> Self {
+        Material::conductor(
+            &Color::new(0.155, 0.117, 0.138),
+            &Color::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> Self {
+        Material::conductor(
+            &Color::new(1.657, 0.880, 0.521),
+            &Color::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    //smooth, clear glass
     pub fn dielectrtic(refractive_index: f64) -> Self {
-        Material::Dielectric { refractive_index }
+        Material::glass(refractive_index, 0.0, &Color::default())
+    }
+
+    pub fn glass(refractive_index: f64, roughness: f64, absorption: &Color) -> Self {
+        Material::Dielectric {
+            refractive_index,
+            roughness,
+            absorption: *absorption,
+            dispersion: Dispersion::None,
+        }
+    }
+
+    //the same glass, with an index of refraction that varies with wavelength - other materials
+    //are returned unchanged
+    pub fn with_dispersion(self, dispersion: Dispersion) -> Self {
+        match self {
+            Material::Dielectric {
+                refractive_index,
+                roughness,
+                absorption,
+                ..
+            } => Material::Dielectric {
+                refractive_index,
+                roughness,
+                absorption,
+                dispersion,
+            },
+            other => other,
+        }
+    }
+
+    //glass that light comes out of with color 'tint' after travelling 'distance' through it
+    pub fn tinted_glass(
+        refractive_index: f64,
+        roughness: f64,
+        tint: &Color,
+        distance: f64,
+    ) -> Self {
+        let coefficient = |transmitted: f64| -transmitted.max(1e-6).ln() / distance;
+        Material::glass(
+            refractive_index,
+            roughness,
+            &Color::new(
+                coefficient(tint.r()),
+                coefficient(tint.g()),
+                coefficient(tint.b()),
+            ),
+        )
+    }
+
+    //the base color of the surface, ignoring how it scatters light
+    pub fn albedo(&self) -> Color {
+        match self {
+            Material::Lambertian { albedo } => *albedo,
+            Material::Metal { albedo, .. } => *albedo,
+            Material::Dielectric { .. } => Color::new(1.0, 1.0, 1.0),
+            //reflectance looking straight at the surface
+            Material::Conductor { eta, k, .. } => fresnel_conductor_color(1.0, eta, k),
+            Material::Principled(principled) => principled.base_color,
+            Material::Hair(hair) => hair.color,
+        }
     }
 
     pub fn schlick(cosine: f64, ref_idx: f64) -> f64 {
@@ -41,54 +176,110 @@ impl Material {
         r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
     }
 
-    pub fn scatter<'a>(
+    pub fn scatter(
         material: &Material,
         ray_in: &Ray,
         hit_rec: &HitRecord,
         attenuation: &mut Color,
-        scattered: &'a mut Ray,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
     ) -> bool {
         match material {
             //diffuse
             Material::Lambertian { albedo } => {
-                let scatter_direction = hit_rec.normal() + Vec3::random_unit_vector_lambertian();
-                *scattered = Ray::new(&hit_rec.point(), &scatter_direction, 0.0);
+                let scatter_direction =
+                    hit_rec.normal() + Vec3::unit_vector_from_sample(sampler.next_2d());
+                *scattered = Ray::new(&hit_rec.point(), &scatter_direction, ray_in.time());
                 *attenuation = *albedo;
                 true
             }
 
             //specular
             Material::Metal { albedo, fuzz } => {
-                if *fuzz > 1.0 {
-                    let reflected = &ray_in.direction().unit_vector().reflect(&hit_rec.normal());
-                    *scattered = Ray::new(
-                        &hit_rec.point(),
-                        &(*reflected + Vec3::random_in_unit_sphere()),
-                        0.0,
-                    );
-                    *attenuation = *albedo;
-                    scattered.direction().dot(&hit_rec.normal()) > 0.0
+                let fuzz = min(*fuzz, 1.0);
+                let reflected = &ray_in.direction().unit_vector().reflect(&hit_rec.normal());
+                *scattered = Ray::new(
+                    &hit_rec.point(),
+                    &(*reflected
+                        + (fuzz * Vec3::in_unit_sphere(sampler.next_2d(), sampler.next_1d()))),
+                    ray_in.time(),
+                );
+                *attenuation = *albedo;
+                scattered.direction().dot(&hit_rec.normal()) > 0.0
+            }
+
+            //microfacet metal
+            Material::Conductor { eta, k, roughness } => {
+                let ggx = Ggx::from_roughness(*roughness);
+                let frame = Onb::from_normal(&hit_rec.normal());
+                let wo = frame.world_to_local(&-ray_in.direction().unit_vector());
+                if wo.z() <= 0.0 {
+                    return false;
+                }
+
+                let microfacet_normal = if ggx.is_smooth() {
+                    Vec3::new(0.0, 0.0, 1.0)
                 } else {
-                    let reflected = &ray_in.direction().unit_vector().reflect(&hit_rec.normal());
-                    *scattered = Ray::new(
-                        &hit_rec.point(),
-                        &(*reflected + (*fuzz * Vec3::random_in_unit_sphere())),
-                        0.0,
-                    );
-                    *attenuation = *albedo;
-                    scattered.direction().dot(&hit_rec.normal()) > 0.0
+                    ggx.sample_visible_normal(&wo, sampler.next_2d())
+                };
+                let wi = (-wo).reflect(&microfacet_normal);
+                if wi.z() <= 0.0 {
+                    return false;
                 }
+
+                //sampling the visible normals cancels D and most of the geometry term out of
+                //f * cos / pdf, which leaves F * G2 / G1
+                let shadowing = if ggx.is_smooth() {
+                    1.0
+                } else {
+                    ggx.g2(&wo, &wi) / ggx.g1(&wo)
+                };
+                let fresnel = fresnel_conductor_color(wo.dot(&microfacet_normal), eta, k);
+
+                *attenuation = &fresnel * shadowing;
+                *scattered = Ray::new(&hit_rec.point(), &frame.local_to_world(&wi), ray_in.time());
+                true
             }
 
             //glass-like
-            Material::Dielectric { refractive_index } => {
-                //glass absorbs nothing
-                *attenuation = Color::new(1.0, 1.0, 1.0);
+            Material::Dielectric {
+                refractive_index,
+                roughness,
+                absorption,
+                dispersion,
+            } => {
+                let refractive_index =
+                    dispersion.refractive_index(*refractive_index, ray_in.wavelength());
+
+                //hitting the inside of the surface means the ray just crossed the medium
+                *attenuation = if hit_rec.front_face() {
+                    Color::new(1.0, 1.0, 1.0)
+                } else {
+                    let distance = hit_rec.t() * ray_in.direction().length();
+                    Color::new(
+                        (-absorption.r() * distance).exp(),
+                        (-absorption.g() * distance).exp(),
+                        (-absorption.b() * distance).exp(),
+                    )
+                };
+
+                let ggx = Ggx::from_roughness(*roughness);
+                if !ggx.is_smooth() {
+                    return scatter_rough_dielectric(
+                        &ggx,
+                        refractive_index,
+                        ray_in,
+                        hit_rec,
+                        attenuation,
+                        scattered,
+                        sampler,
+                    );
+                }
 
                 let eta_over_etaprime = if hit_rec.front_face() {
-                    1.0 / *refractive_index
+                    1.0 / refractive_index
                 } else {
-                    *refractive_index
+                    refractive_index
                 };
 
                 let ray_in_unit_direction = ray_in.direction().unit_vector();
@@ -105,11 +296,9 @@ impl Material {
                 } else {
                     //refract the ray
 
-                    let mut rng = rand::thread_rng();
-
                     //approximate varying reflectivity with angle
-                    let reflect_probability = Material::schlick(cos_theta, *refractive_index);
-                    if rng.gen::<f64>() < reflect_probability {
+                    let reflect_probability = Material::schlick(cos_theta, refractive_index);
+                    if sampler.next_1d() < reflect_probability {
                         let reflected = ray_in_unit_direction.reflect(&hit_rec.normal());
                         *scattered = Ray::new(&hit_rec.point(), &reflected, ray_in.time());
 
@@ -123,6 +312,70 @@ impl Material {
                     }
                 }
             }
+
+            Material::Principled(principled) => {
+                principled.scatter(ray_in, hit_rec, attenuation, scattered, sampler)
+            }
+
+            Material::Hair(hair) => hair.scatter(ray_in, hit_rec, attenuation, scattered, sampler),
         }
     }
 }
+
+fn fresnel_conductor_color(cos_i: f64, eta: &Color, k: &Color) -> Color {
+    Color::new(
+        fresnel_conductor(cos_i, eta.r(), k.r()),
+        fresnel_conductor(cos_i, eta.g(), k.g()),
+        fresnel_conductor(cos_i, eta.b(), k.b()),
+    )
+}
+
+//the same choice between reflection and refraction as smooth glass, but about a microfacet normal
+//sampled from the visible normals (Walter et al., "Microfacet Models for Refraction through Rough Surfaces")
+fn scatter_rough_dielectric(
+    ggx: &Ggx,
+    refractive_index: f64,
+    ray_in: &Ray,
+    hit_rec: &HitRecord,
+    attenuation: &mut Color,
+    scattered: &mut Ray,
+    sampler: &mut dyn Sampler,
+) -> bool {
+    let eta_over_etaprime = if hit_rec.front_face() {
+        1.0 / refractive_index
+    } else {
+        refractive_index
+    };
+
+    //the hit normal always faces the incoming ray, so wo is above the surface
+    let frame = Onb::from_normal(&hit_rec.normal());
+    let wo = frame.world_to_local(&-ray_in.direction().unit_vector());
+    if wo.z() <= 0.0 {
+        return false;
+    }
+
+    let microfacet_normal = ggx.sample_visible_normal(&wo, sampler.next_2d());
+    let cos_theta = wo.dot(&microfacet_normal);
+
+    //total internal reflection comes out of the Fresnel term as a reflectance of 1
+    let reflectance = fresnel_dielectric(cos_theta, eta_over_etaprime);
+
+    let wi = if sampler.next_1d() < reflectance {
+        let reflected = (-wo).reflect(&microfacet_normal);
+        if reflected.z() <= 0.0 {
+            return false;
+        }
+        reflected
+    } else {
+        let refracted = (-wo).refract(&microfacet_normal, eta_over_etaprime);
+        if refracted.z() >= 0.0 {
+            return false;
+        }
+        refracted
+    };
+
+    //choosing the lobe by its Fresnel weight cancels F, leaving the same G2 / G1 as the conductor
+    *attenuation = &*attenuation * (ggx.g2(&wo, &wi) / ggx.g1(&wo));
+    *scattered = Ray::new(&hit_rec.point(), &frame.local_to_world(&wi), ray_in.time());
+    true
+}
@@ -0,0 +1,400 @@
+use crate::lib::{
+    color::Color, hittable::Hittable, material::Material, ply::read_ply, stl::read_stl,
+    texture::Texture, triangle::Triangle, vec3::*,
+};
+use std::{collections::HashMap, fs, io, path::Path, sync::Arc};
+
+//indexed triangle mesh that can be refined before it's turned into triangles for the BVH. Uvs,
+//normals and colors are optional, one per vertex when present
+#[derive(Clone, Default)]
+pub struct TriangleMesh {
+    positions: Vec<Point3>,
+    uvs: Vec<(f64, f64)>,
+    normals: Vec<Vec3>,
+    colors: Vec<Color>,
+    faces: Vec<[usize; 3]>,
+}
+
+//what a new vertex on an edge needs to know - its index and the vertices across from the edge
+struct Edge {
+    vertex: usize,
+    opposite: Vec<usize>,
+}
+
+#[allow(dead_code)]
+impl TriangleMesh {
+    pub fn new(positions: Vec<Point3>, faces: Vec<[usize; 3]>) -> Self {
+        assert!(faces.iter().flatten().all(|index| *index < positions.len()));
+        Self {
+            positions,
+            faces,
+            ..TriangleMesh::default()
+        }
+    }
+
+    pub fn with_uvs(self, uvs: Vec<(f64, f64)>) -> Self {
+        assert_eq!(uvs.len(), self.positions.len());
+        Self { uvs, ..self }
+    }
+
+    pub fn with_normals(self, normals: Vec<Vec3>) -> Self {
+        assert_eq!(normals.len(), self.positions.len());
+        Self { normals, ..self }
+    }
+
+    pub fn with_colors(self, colors: Vec<Color>) -> Self {
+        assert_eq!(colors.len(), self.positions.len());
+        Self { colors, ..self }
+    }
+
+    //PLY or STL, going by the file extension
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let extension = path
+            .as_ref()
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("ply") => read_ply(&fs::read(path)?),
+            Some("stl") => read_stl(&fs::read(path)?),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unsupported mesh format".to_string(),
+            )),
+        }
+    }
+
+    //smooth normals from the faces around each vertex, weighted by their area
+    pub fn with_computed_normals(self) -> Self {
+        let normals = self.vertex_normals();
+        Self { normals, ..self }
+    }
+
+    pub fn positions(&self) -> &[Point3] {
+        &self.positions
+    }
+
+    pub fn uvs(&self) -> &[(f64, f64)] {
+        &self.uvs
+    }
+
+    pub fn normals(&self) -> &[Vec3] {
+        &self.normals
+    }
+
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+
+    pub fn faces(&self) -> &[[usize; 3]] {
+        &self.faces
+    }
+
+    fn vertex_normals(&self) -> Vec<Vec3> {
+        let mut normals = vec![Vec3::default(); self.positions.len()];
+        for [a, b, c] in &self.faces {
+            //the cross product's length is twice the area
+            let face_normal = (self.positions[*b] - self.positions[*a])
+                .cross(&(self.positions[*c] - self.positions[*a]));
+            for index in [a, b, c].iter() {
+                normals[**index] = normals[**index] + face_normal;
+            }
+        }
+        normals
+            .into_iter()
+            .map(|normal| {
+                if normal.length_squared() > 0.0 {
+                    normal.unit_vector()
+                } else {
+                    normal
+                }
+            })
+            .collect()
+    }
+
+    //Loop subdivision, 'levels' times - every triangle is split in four and the vertices are
+    //moved towards a smooth surface. Open edges are smoothed as curves of their own. Uvs and
+    //colors are interpolated linearly and normals are recomputed if the mesh had them
+    pub fn subdivide(self, levels: usize) -> Self {
+        (0..levels).fold(self, |mesh, _| mesh.subdivide_once())
+    }
+
+    fn subdivide_once(&self) -> Self {
+        let vertex_count = self.positions.len();
+        let mut edges: HashMap<(usize, usize), Edge> = HashMap::new();
+        let mut neighbors: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+
+        for [a, b, c] in &self.faces {
+            for (start, end, opposite) in [(*a, *b, *c), (*b, *c, *a), (*c, *a, *b)].iter() {
+                let key = ((*start).min(*end), (*start).max(*end));
+                let next_vertex = vertex_count + edges.len();
+                edges
+                    .entry(key)
+                    .or_insert_with(|| Edge {
+                        vertex: next_vertex,
+                        opposite: Vec::new(),
+                    })
+                    .opposite
+                    .push(*opposite);
+
+                if !neighbors[*start].contains(end) {
+                    neighbors[*start].push(*end);
+                    neighbors[*end].push(*start);
+                }
+            }
+        }
+
+        //edges with a face on only one side are on the boundary
+        let mut boundary_neighbors: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+        for ((a, b), edge) in &edges {
+            if edge.opposite.len() == 1 {
+                boundary_neighbors[*a].push(*b);
+                boundary_neighbors[*b].push(*a);
+            }
+        }
+
+        let new_count = vertex_count + edges.len();
+        let mut positions = vec![Point3::default(); new_count];
+        let mut uvs = if self.uvs.is_empty() {
+            Vec::new()
+        } else {
+            vec![(0.0, 0.0); new_count]
+        };
+        let mut colors = if self.colors.is_empty() {
+            Vec::new()
+        } else {
+            vec![Color::default(); new_count]
+        };
+
+        for (index, position) in self.positions.iter().enumerate() {
+            let boundary = &boundary_neighbors[index];
+            positions[index] = if boundary.len() == 2 {
+                0.75 * *position
+                    + 0.125 * (self.positions[boundary[0]] + self.positions[boundary[1]])
+            } else if !boundary.is_empty() {
+                //corners where more than two open edges meet are kept sharp
+                *position
+            } else {
+                //Warren's weights
+                let valence = neighbors[index].len();
+                let beta = if valence == 3 {
+                    3.0 / 16.0
+                } else {
+                    3.0 / (8.0 * valence as f64)
+                };
+                let around = neighbors[index]
+                    .iter()
+                    .fold(Vec3::default(), |sum, neighbor| {
+                        sum + self.positions[*neighbor]
+                    });
+                (1.0 - valence as f64 * beta) * *position + beta * around
+            };
+        }
+        if !uvs.is_empty() {
+            uvs[..vertex_count].copy_from_slice(&self.uvs);
+        }
+        if !colors.is_empty() {
+            colors[..vertex_count].copy_from_slice(&self.colors);
+        }
+
+        for ((a, b), edge) in &edges {
+            let (a, b) = (*a, *b);
+            positions[edge.vertex] = if edge.opposite.len() == 2 {
+                0.375 * (self.positions[a] + self.positions[b])
+                    + 0.125 * (self.positions[edge.opposite[0]] + self.positions[edge.opposite[1]])
+            } else {
+                0.5 * (self.positions[a] + self.positions[b])
+            };
+            if !uvs.is_empty() {
+                uvs[edge.vertex] = (
+                    0.5 * (self.uvs[a].0 + self.uvs[b].0),
+                    0.5 * (self.uvs[a].1 + self.uvs[b].1),
+                );
+            }
+            if !colors.is_empty() {
+                colors[edge.vertex] = 0.5 * &(self.colors[a] + self.colors[b]);
+            }
+        }
+
+        let middle = |a: usize, b: usize| edges[&(a.min(b), a.max(b))].vertex;
+        let faces = self
+            .faces
+            .iter()
+            .flat_map(|[a, b, c]| {
+                let (ab, bc, ca) = (middle(*a, *b), middle(*b, *c), middle(*c, *a));
+                vec![[*a, ab, ca], [*b, bc, ab], [*c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+
+        let mesh = TriangleMesh {
+            positions,
+            uvs,
+            normals: Vec::new(),
+            colors,
+            faces,
+        };
+        if self.normals.is_empty() {
+            mesh
+        } else {
+            mesh.with_computed_normals()
+        }
+    }
+
+    //moves every vertex along its normal by 'scale' times the brightness of 'heights' there,
+    //then recomputes the normals from the new shape
+    pub fn displace(self, heights: &dyn Texture, scale: f64) -> Self {
+        let normals = if self.normals.is_empty() {
+            self.vertex_normals()
+        } else {
+            self.normals.clone()
+        };
+
+        let positions = self
+            .positions
+            .iter()
+            .enumerate()
+            .map(|(index, position)| {
+                let (u, v) = self.uvs.get(index).cloned().unwrap_or((0.0, 0.0));
+                let height = heights.value(u, v, position).luminance();
+                *position + scale * height * normals[index]
+            })
+            .collect();
+
+        Self {
+            positions,
+            normals: Vec::new(),
+            ..self
+        }
+        .with_computed_normals()
+    }
+
+    //one triangle per face, ready to go into a BVH
+    pub fn triangles(&self, material: Material) -> Vec<Arc<dyn Hittable>> {
+        self.faces
+            .iter()
+            .map(|face| self.triangle(face, material))
+            .collect()
+    }
+
+    //the same, with each face's material made from the average of its vertex colors - white
+    //if the mesh has none
+    pub fn colored_triangles(
+        &self,
+        material: &dyn Fn(&Color) -> Material,
+    ) -> Vec<Arc<dyn Hittable>> {
+        self.faces
+            .iter()
+            .map(|face| {
+                let color = if self.colors.is_empty() {
+                    Color::new(1.0, 1.0, 1.0)
+                } else {
+                    let [a, b, c] = *face;
+                    &(self.colors[a] + self.colors[b] + self.colors[c]) / 3.0
+                };
+                self.triangle(face, material(&color))
+            })
+            .collect()
+    }
+
+    fn triangle(&self, face: &[usize; 3], material: Material) -> Arc<dyn Hittable> {
+        let [a, b, c] = *face;
+        let mut triangle = Triangle::new(
+            self.positions[a],
+            self.positions[b],
+            self.positions[c],
+            material,
+        );
+        if !self.uvs.is_empty() {
+            triangle = triangle.with_uvs([self.uvs[a], self.uvs[b], self.uvs[c]]);
+        }
+        if !self.normals.is_empty() {
+            triangle = triangle.with_normals([self.normals[a], self.normals[b], self.normals[c]]);
+        }
+        Arc::new(triangle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::{color::Color, mesh::*, texture::SolidColor};
+
+    fn octahedron() -> TriangleMesh {
+        TriangleMesh::new(
+            vec![
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(-1.0, 0.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+                Point3::new(0.0, -1.0, 0.0),
+                Point3::new(0.0, 0.0, 1.0),
+                Point3::new(0.0, 0.0, -1.0),
+            ],
+            vec![
+                [0, 2, 4],
+                [2, 1, 4],
+                [1, 3, 4],
+                [3, 0, 4],
+                [2, 0, 5],
+                [1, 2, 5],
+                [3, 1, 5],
+                [0, 3, 5],
+            ],
+        )
+    }
+
+    #[test]
+    fn test_loop_subdivision() {
+        let smooth = octahedron().subdivide(3);
+        assert_eq!(smooth.faces().len(), 8 * 4 * 4 * 4);
+        //every edge is shared by two faces, so vertices - edges + faces stays 2
+        assert_eq!(smooth.positions().len(), 2 + smooth.faces().len() / 2);
+
+        //the corners are pulled in and the surface rounds out towards a sphere
+        let distances: Vec<f64> = smooth.positions().iter().map(|p| p.length()).collect();
+        let (nearest, farthest) = distances
+            .iter()
+            .fold((f64::INFINITY, 0.0f64), |(low, high), d| {
+                (low.min(*d), high.max(*d))
+            });
+        assert!(farthest < 1.0);
+        assert!(farthest - nearest < 0.15);
+
+        //a single open triangle stays flat, its corners are pulled in along the open edges
+        let flat = TriangleMesh::new(
+            vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+            ],
+            vec![[0, 1, 2]],
+        )
+        .with_uvs(vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)])
+        .subdivide(1);
+        assert_eq!(flat.faces().len(), 4);
+        assert!((flat.positions()[0] - Point3::new(0.125, 0.125, 0.0)).length() < 1e-9);
+        assert!(flat.positions().iter().all(|p| p.z() == 0.0));
+        let middle = flat
+            .positions()
+            .iter()
+            .position(|p| (*p - Point3::new(0.5, 0.0, 0.0)).length() < 1e-9)
+            .unwrap();
+        assert_eq!(flat.uvs()[middle], (0.5, 0.0));
+    }
+
+    #[test]
+    fn test_displacement() {
+        let bumped = octahedron()
+            .subdivide(2)
+            .with_computed_normals()
+            .displace(&SolidColor::new(&Color::new(0.5, 0.5, 0.5)), 1.0);
+
+        //an even push outwards along the normals makes it bigger all round
+        let before = octahedron().subdivide(2);
+        for (moved, original) in bumped.positions().iter().zip(before.positions()) {
+            assert!((moved.length() - original.length() - 0.5).abs() < 0.1);
+        }
+        assert_eq!(bumped.normals().len(), bumped.positions().len());
+        assert_eq!(
+            bumped.triangles(Material::default()).len(),
+            bumped.faces().len()
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use crate::lib::vec3::*;
+
+use std::f64::consts::PI;
+
+//Trowbridge-Reitz (GGX) microfacet distribution. Everything here works in the local shading frame,
+//where the macro surface normal is +z
+#[derive(Copy, Clone, Debug)]
+pub struct Ggx {
+    alpha: f64,
+}
+
+//below this the surface is treated as a perfect mirror - the distribution becomes too peaked to sample
+pub const SMOOTH_ALPHA: f64 = 1e-3;
+
+#[allow(dead_code)]
+impl Ggx {
+    //artist friendly roughness in [0,1], squared as usual to get a perceptually even scale
+    pub fn from_roughness(roughness: f64) -> Self {
+        let roughness = roughness.clamp(0.0, 1.0);
+        Self {
+            alpha: (roughness * roughness).max(SMOOTH_ALPHA),
+        }
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    pub fn is_smooth(&self) -> bool {
+        self.alpha <= SMOOTH_ALPHA
+    }
+
+    //density of microfacet normals 'm'
+    pub fn d(&self, m: &Vec3) -> f64 {
+        if m.z() <= 0.0 {
+            return 0.0;
+        }
+        let a2 = self.alpha * self.alpha;
+        let cos2 = m.z() * m.z();
+        let denominator = cos2 * (a2 - 1.0) + 1.0;
+        a2 / (PI * denominator * denominator)
+    }
+
+    //Smith's auxiliary function
+    pub fn lambda(&self, v: &Vec3) -> f64 {
+        let cos2 = v.z() * v.z();
+        if cos2 <= 0.0 {
+            return f64::INFINITY;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        0.5 * ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0)
+    }
+
+    //fraction of microfacets visible from 'v'
+    pub fn g1(&self, v: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(v))
+    }
+
+    //height correlated fraction visible from both directions
+    pub fn g2(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    //microfacet normal sampled in proportion to how much of it 'wo' sees (Heitz, "Sampling the GGX
+    //Distribution of Visible Normals") - 'wo' must be above the surface
+    pub fn sample_visible_normal(&self, wo: &Vec3, sample: (f64, f64)) -> Vec3 {
+        //stretch the view direction so the distribution becomes a hemisphere
+        let vh = Vec3::new(self.alpha * wo.x(), self.alpha * wo.y(), wo.z()).unit_vector();
+
+        let length2 = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if length2 > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) / length2.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(&t1);
+
+        //sample the projected area of the hemisphere
+        let r = sample.0.sqrt();
+        let phi = 2.0 * PI * sample.1;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let p3 = (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+        let nh = p1 * t1 + p2 * t2 + p3 * vh;
+
+        //unstretch
+        Vec3::new(self.alpha * nh.x(), self.alpha * nh.y(), nh.z().max(1e-6)).unit_vector()
+    }
+}
+
+//unpolarized Fresnel reflectance of a conductor with complex index of refraction eta + ik
+pub fn fresnel_conductor(cos_i: f64, eta: f64, k: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let cos2 = cos_i * cos_i;
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_i * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rp + rs)
+}
+
+//unpolarized Fresnel reflectance of a dielectric boundary, for light going from a medium with index
+//eta_i into one with index eta_t - 'eta_ratio' is eta_i / eta_t
+pub fn fresnel_dielectric(cos_i: f64, eta_ratio: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = eta_ratio * eta_ratio * (1.0 - cos_i * cos_i);
+
+    //total internal reflection
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    let rs = (eta_ratio * cos_i - cos_t) / (eta_ratio * cos_i + cos_t);
+    let rp = (cos_i - eta_ratio * cos_t) / (cos_i + eta_ratio * cos_t);
+    0.5 * (rs * rs + rp * rp)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::microfacet::*;
+
+    #[test]
+    fn test_ggx_normalized() {
+        //the projected microfacet area has to add up to the macro surface: ∫ D(m) cosθ dω = 1
+        let ggx = Ggx::from_roughness(0.5);
+        let steps = 400;
+        let mut integral = 0.0;
+        for i in 0..steps {
+            let theta = (i as f64 + 0.5) / steps as f64 * PI / 2.0;
+            let m = Vec3::new(theta.sin(), 0.0, theta.cos());
+            integral +=
+                ggx.d(&m) * theta.cos() * theta.sin() * 2.0 * PI * (PI / 2.0 / steps as f64);
+        }
+        assert!((integral - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_fresnel_conductor_normal_incidence() {
+        //gold at 550nm
+        let (eta, k) = (0.374, 2.385);
+        let expected = ((eta - 1.0) * (eta - 1.0) + k * k) / ((eta + 1.0) * (eta + 1.0) + k * k);
+        assert!((fresnel_conductor(1.0, eta, k) - expected).abs() < 1e-9);
+        assert!((fresnel_conductor(0.0, eta, k) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_fresnel_dielectric() {
+        //4% of light reflects straight off glass
+        assert!((fresnel_dielectric(1.0, 1.0 / 1.5) - 0.04).abs() < 1e-12);
+        //from inside, past the critical angle everything reflects
+        assert_eq!(fresnel_dielectric(0.5, 1.5), 1.0);
+        assert!((fresnel_dielectric(0.0, 1.0 / 1.5) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_visible_normals_face_the_viewer() {
+        let ggx = Ggx::from_roughness(0.8);
+        let wo = Vec3::new(0.6, 0.0, 0.8);
+        for i in 0..16 {
+            for j in 0..16 {
+                let sample = ((i as f64 + 0.5) / 16.0, (j as f64 + 0.5) / 16.0);
+                let m = ggx.sample_visible_normal(&wo, sample);
+                assert!((m.length() - 1.0).abs() < 1e-9);
+                assert!(m.dot(&wo) > 0.0 && m.z() > 0.0);
+            }
+        }
+    }
+}
@@ -1,11 +1,44 @@
 pub mod aabb;
+pub mod adaptive;
+pub mod alpha_mask;
+pub mod aov;
+pub mod bump;
 pub mod bvh;
 pub mod camera;
 pub mod color;
+pub mod csg;
+pub mod curve;
+pub mod denoise;
+pub mod film;
+pub mod filter;
+pub mod framebuffer;
+pub mod gltf;
+pub mod hair;
+pub mod heightfield;
 pub mod hittable;
 pub mod hittable_list;
+pub mod integrator;
+pub mod json;
 pub mod material;
+pub mod mesh;
+pub mod microfacet;
+pub mod onb;
+pub mod plane;
+pub mod ply;
+pub mod principled;
+pub mod quadric;
 pub mod ray;
+pub mod render;
 pub mod rt_math;
+pub mod sampler;
+pub mod sdf;
+pub mod settings;
+pub mod spectrum;
 pub mod sphere;
+pub mod stl;
+pub mod texture;
+pub mod tonemap;
+pub mod torus;
+pub mod transform;
+pub mod triangle;
 pub mod vec3;
@@ -0,0 +1,89 @@
+use crate::lib::vec3::*;
+
+//orthonormal basis around a normal - lets BSDFs work in a local frame where the normal is +z
+#[derive(Copy, Clone, Debug)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+#[allow(dead_code)]
+impl Onb {
+    //'normal' has to be a unit vector
+    pub fn from_normal(normal: &Vec3) -> Self {
+        //branchless construction from Duff et al., "Building an Orthonormal Basis, Revisited"
+        let sign = 1f64.copysign(normal.z());
+        let a = -1.0 / (sign + normal.z());
+        let b = normal.x() * normal.y() * a;
+
+        Self {
+            u: Vec3::new(
+                1.0 + sign * normal.x() * normal.x() * a,
+                sign * b,
+                -sign * normal.x(),
+            ),
+            v: Vec3::new(b, sign + normal.y() * normal.y() * a, -normal.y()),
+            w: *normal,
+        }
+    }
+
+    //basis whose first axis follows 'tangent' as closely as possible while staying perpendicular to the normal
+    pub fn from_normal_and_tangent(normal: &Vec3, tangent: &Vec3) -> Self {
+        let u = *tangent - tangent.dot(normal) * *normal;
+        if u.length_squared() < 1e-12 {
+            return Onb::from_normal(normal);
+        }
+        let u = u.unit_vector();
+
+        Self {
+            u,
+            v: normal.cross(&u),
+            w: *normal,
+        }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.w
+    }
+
+    pub fn world_to_local(&self, world: &Vec3) -> Vec3 {
+        Vec3::new(world.dot(&self.u), world.dot(&self.v), world.dot(&self.w))
+    }
+
+    pub fn local_to_world(&self, local: &Vec3) -> Vec3 {
+        local.x() * self.u + local.y() * self.v + local.z() * self.w
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::onb::*;
+
+    #[test]
+    fn test_onb_round_trip() {
+        for normal in &[
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(1.0, 2.0, -3.0).unit_vector(),
+        ] {
+            let onb = Onb::from_normal(normal);
+            assert!(onb.u().dot(&onb.v()).abs() < 1e-12);
+            assert!(onb.u().dot(normal).abs() < 1e-12);
+            assert!((onb.u().cross(&onb.v()) - *normal).length() < 1e-12);
+
+            let direction = Vec3::new(0.3, -0.2, 0.9);
+            assert!(
+                (onb.local_to_world(&onb.world_to_local(&direction)) - direction).length() < 1e-12
+            );
+        }
+    }
+}
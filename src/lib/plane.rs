@@ -0,0 +1,200 @@
+use crate::lib::{aabb::*, hittable::*, material::Material, onb::Onb, ray::Ray, vec3::*};
+use std::{f64::consts::PI, sync::Arc};
+
+//infinite plane through 'point' - it has no bounding box, so a BVH keeps it outside the tree
+pub struct Plane {
+    point: Point3,
+    frame: Onb, //w is the normal, u and v run along the plane
+    material: Material,
+}
+
+//flat disk facing along 'normal'
+pub struct Disk {
+    center: Point3,
+    frame: Onb,
+    radius: f64,
+    material: Material,
+}
+
+//rays closer to parallel than this miss, rather than hitting very far away
+const PARALLEL_EPSILON: f64 = 1e-12;
+
+//distance along the ray to the plane through 'point' with normal 'normal', if it's in range
+fn hit_plane(ray: &Ray, point: &Point3, normal: &Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+    let denominator = ray.direction().dot(normal);
+    if denominator.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+
+    let t = (*point - ray.origin()).dot(normal) / denominator;
+    if t <= t_min || t >= t_max {
+        return None;
+    }
+    Some(t)
+}
+
+//tight box around a disk - it reaches radius * sin(angle between the normal and the axis) along
+//each axis, padded so axis aligned disks still have some thickness
+pub fn disk_bounds(center: &Point3, normal: &Vec3, radius: f64) -> AABB {
+    let extent = |component: f64| radius * (1.0 - component * component).max(0.0).sqrt() + 1e-4;
+    let half_size = Vec3::new(extent(normal.x()), extent(normal.y()), extent(normal.z()));
+
+    AABB::new(&(*center - half_size), &(*center + half_size))
+}
+
+#[allow(dead_code)]
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, material: Material) -> Self {
+        Self {
+            point,
+            frame: Onb::from_normal(&normal.unit_vector()),
+            material,
+        }
+    }
+
+    pub fn new_hittable(point: Point3, normal: Vec3, material: Material) -> Arc<dyn Hittable> {
+        Arc::new(Plane::new(point, normal, material))
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
+        let t = match hit_plane(ray, &self.point, &self.frame.w(), t_min, t_max) {
+            Some(t) => t,
+            None => return false,
+        };
+
+        //one unit of texture per unit of distance, repeating forever
+        let offset = ray.at(t) - self.point;
+        hit_rec.set_t(t);
+        hit_rec.set_p(ray.at(t));
+        hit_rec.set_face_normal(ray, &self.frame.w());
+        hit_rec.set_uv(offset.dot(&self.frame.u()), offset.dot(&self.frame.v()));
+        hit_rec.set_tangents(&self.frame.u(), &self.frame.v());
+        hit_rec.set_material(self.material);
+        true
+    }
+}
+
+#[allow(dead_code)]
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, material: Material) -> Self {
+        Self {
+            center,
+            frame: Onb::from_normal(&normal.unit_vector()),
+            radius,
+            material,
+        }
+    }
+
+    pub fn new_hittable(
+        center: Point3,
+        normal: Vec3,
+        radius: f64,
+        material: Material,
+    ) -> Arc<dyn Hittable> {
+        Arc::new(Disk::new(center, normal, radius, material))
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
+        let t = match hit_plane(ray, &self.center, &self.frame.w(), t_min, t_max) {
+            Some(t) => t,
+            None => return false,
+        };
+
+        let local = self.frame.world_to_local(&(ray.at(t) - self.center));
+        let distance = (local.x() * local.x() + local.y() * local.y()).sqrt();
+        if distance > self.radius {
+            return false;
+        }
+
+        //u goes around the disk, v from the rim (0) to the center (1)
+        let phi = local.y().atan2(local.x());
+        let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+        let around = self
+            .frame
+            .local_to_world(&Vec3::new(-local.y(), local.x(), 0.0));
+        let outwards = if distance > 0.0 {
+            self.frame
+                .local_to_world(&Vec3::new(local.x(), local.y(), 0.0))
+                / distance
+        } else {
+            self.frame.u()
+        };
+
+        hit_rec.set_t(t);
+        hit_rec.set_p(ray.at(t));
+        hit_rec.set_face_normal(ray, &self.frame.w());
+        hit_rec.set_uv(phi / (2.0 * PI), 1.0 - distance / self.radius);
+        hit_rec.set_tangents(&(2.0 * PI * around), &(-self.radius * outwards));
+        hit_rec.set_material(self.material);
+        true
+    }
+
+    fn bounding_box(&self, _t_0: f64, _t_1: f64, output_box: &mut AABB) -> bool {
+        *output_box = disk_bounds(&self.center, &self.frame.w(), self.radius);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::plane::*;
+
+    #[test]
+    fn test_plane_hit_from_both_sides() {
+        let plane = Plane::new(
+            Point3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Material::default(),
+        );
+        let mut hit_rec = HitRecord::new_invalid();
+
+        let down = Ray::new(
+            &Point3::new(3.0, 4.0, -7.0),
+            &Vec3::new(0.0, -1.0, 0.0),
+            0.0,
+        );
+        assert!(plane.hit(&down, 0.001, f64::INFINITY, &mut hit_rec));
+        assert!((hit_rec.t() - 5.0).abs() < 1e-9);
+        assert!(hit_rec.front_face());
+
+        let up = Ray::new(&Point3::new(0.0, -3.0, 0.0), &Vec3::new(0.0, 1.0, 0.0), 0.0);
+        assert!(plane.hit(&up, 0.001, f64::INFINITY, &mut hit_rec));
+        assert!(!hit_rec.front_face());
+        assert!((hit_rec.normal() - Vec3::new(0.0, -1.0, 0.0)).length() < 1e-9);
+
+        let parallel = Ray::new(&Point3::new(0.0, 1.0, 0.0), &Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(!plane.hit(&parallel, 0.001, f64::INFINITY, &mut hit_rec));
+
+        let mut bounds = AABB::default();
+        assert!(!plane.bounding_box(0.0, 1.0, &mut bounds));
+    }
+
+    #[test]
+    fn test_disk_hit_and_bounds() {
+        let disk = Disk::new(
+            Point3::new(0.0, 0.0, 2.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            1.0,
+            Material::default(),
+        );
+        let mut hit_rec = HitRecord::new_invalid();
+
+        let inside = Ray::new(&Point3::new(0.5, 0.0, 5.0), &Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(disk.hit(&inside, 0.001, f64::INFINITY, &mut hit_rec));
+        assert!((hit_rec.v() - 0.5).abs() < 1e-9);
+        let normal = hit_rec.dpdu().cross(&hit_rec.dpdv()).unit_vector();
+        assert!((normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+
+        let outside = Ray::new(&Point3::new(1.5, 0.0, 5.0), &Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(!disk.hit(&outside, 0.001, f64::INFINITY, &mut hit_rec));
+
+        let mut bounds = AABB::default();
+        assert!(disk.bounding_box(0.0, 1.0, &mut bounds));
+        assert!((bounds.max().x() - 1.0).abs() < 1e-3);
+        assert!((bounds.max().z() - 2.0).abs() < 1e-3);
+    }
+}
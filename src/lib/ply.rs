@@ -0,0 +1,424 @@
+use crate::lib::{color::Color, mesh::TriangleMesh, vec3::*};
+use std::io;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+//a property of an element - lists (eg. a face's vertex indices) have a count type as well
+struct Property {
+    name: String,
+    scalar: Scalar,
+    count: Option<Scalar>,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+//where the next value comes from, text or bytes
+struct Body<'a> {
+    format: Format,
+    data: &'a [u8],
+    position: usize,
+    tokens: std::str::SplitAsciiWhitespace<'a>,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+impl Scalar {
+    fn parse(name: &str) -> io::Result<Self> {
+        match name {
+            "char" | "int8" => Ok(Scalar::I8),
+            "uchar" | "uint8" => Ok(Scalar::U8),
+            "short" | "int16" => Ok(Scalar::I16),
+            "ushort" | "uint16" => Ok(Scalar::U16),
+            "int" | "int32" => Ok(Scalar::I32),
+            "uint" | "uint32" => Ok(Scalar::U32),
+            "float" | "float32" => Ok(Scalar::F32),
+            "double" | "float64" => Ok(Scalar::F64),
+            _ => Err(invalid("unknown PLY property type")),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    //what an integer color channel is divided by to get into [0,1]
+    fn color_scale(&self) -> f64 {
+        match self {
+            Scalar::I8 | Scalar::U8 => 255.0,
+            Scalar::I16 | Scalar::U16 => 65535.0,
+            _ => 1.0,
+        }
+    }
+
+    fn decode(&self, bytes: &[u8], big_endian: bool) -> f64 {
+        //bytes in big endian order from here on
+        let mut ordered = [0u8; 8];
+        ordered[..bytes.len()].copy_from_slice(bytes);
+        if !big_endian {
+            ordered[..bytes.len()].reverse();
+        }
+        let b = ordered;
+
+        match self {
+            Scalar::I8 => b[0] as i8 as f64,
+            Scalar::U8 => b[0] as f64,
+            Scalar::I16 => i16::from_be_bytes([b[0], b[1]]) as f64,
+            Scalar::U16 => u16::from_be_bytes([b[0], b[1]]) as f64,
+            Scalar::I32 => i32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::U32 => u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::F32 => f32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::F64 => f64::from_be_bytes(b),
+        }
+    }
+}
+
+impl<'a> Body<'a> {
+    fn next(&mut self, scalar: Scalar) -> io::Result<f64> {
+        if self.format == Format::Ascii {
+            return self
+                .tokens
+                .next()
+                .ok_or_else(|| invalid("truncated PLY data"))?
+                .parse::<f64>()
+                .map_err(|_| invalid("invalid number in PLY data"));
+        }
+
+        let bytes = self
+            .data
+            .get(self.position..self.position + scalar.size())
+            .ok_or_else(|| invalid("truncated PLY data"))?;
+        self.position += scalar.size();
+        Ok(scalar.decode(bytes, self.format == Format::BigEndian))
+    }
+}
+
+//reads ASCII and binary (either byte order) PLY files - vertex positions, and normals, colors
+//and texture coordinates when they're there. Faces with more than three corners are split into
+//fans of triangles, and any other elements are skipped
+pub fn read_ply(data: &[u8]) -> io::Result<TriangleMesh> {
+    //the header is text, ending with an 'end_header' line
+    let marker = b"end_header";
+    let header_end = data
+        .windows(marker.len())
+        .position(|window| window == marker)
+        .ok_or_else(|| invalid("not a PLY file"))?;
+    let body_start = data[header_end..]
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .map(|offset| header_end + offset + 1)
+        .unwrap_or(data.len());
+    let header = String::from_utf8_lossy(&data[..header_end]);
+
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err(invalid("not a PLY file"));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", "ascii", _] => format = Some(Format::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(Format::LittleEndian),
+            ["format", "binary_big_endian", _] => format = Some(Format::BigEndian),
+            ["format", ..] => return Err(invalid("unsupported PLY format")),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid("invalid PLY element count"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, scalar, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid("PLY property outside an element"))?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    scalar: Scalar::parse(scalar)?,
+                    count: Some(Scalar::parse(count)?),
+                }),
+            ["property", scalar, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid("PLY property outside an element"))?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    scalar: Scalar::parse(scalar)?,
+                    count: None,
+                }),
+            //comments, obj_info and blank lines
+            _ => {}
+        }
+    }
+
+    let format = format.ok_or_else(|| invalid("PLY file without a format"))?;
+    let body_data = &data[body_start.min(data.len())..];
+    let text = if format == Format::Ascii {
+        std::str::from_utf8(body_data).map_err(|_| invalid("invalid PLY data"))?
+    } else {
+        ""
+    };
+    let mut body = Body {
+        format,
+        data: body_data,
+        position: 0,
+        tokens: text.split_ascii_whitespace(),
+    };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut colors = Vec::new();
+    let mut uvs = Vec::new();
+    let mut faces = Vec::new();
+
+    for element in &elements {
+        let find = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|property| names.contains(&property.name.as_str()))
+        };
+        let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+        let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+        let color = [
+            find(&["red", "r", "diffuse_red"]),
+            find(&["green", "g", "diffuse_green"]),
+            find(&["blue", "b", "diffuse_blue"]),
+        ];
+        let uv = [
+            find(&["u", "s", "texture_u", "texture_s"]),
+            find(&["v", "t", "texture_v", "texture_t"]),
+        ];
+        let indices = find(&["vertex_indices", "vertex_index"]);
+
+        for _ in 0..element.count {
+            let mut values = vec![0.0; element.properties.len()];
+            let mut list = Vec::new();
+            for (index, property) in element.properties.iter().enumerate() {
+                match property.count {
+                    Some(count) => {
+                        let length = body.next(count)? as usize;
+                        let items = (0..length)
+                            .map(|_| body.next(property.scalar))
+                            .collect::<io::Result<Vec<f64>>>()?;
+                        if Some(index) == indices {
+                            list = items;
+                        }
+                    }
+                    None => values[index] = body.next(property.scalar)?,
+                }
+            }
+
+            let all = |found: &[Option<usize>]| found.iter().all(Option::is_some);
+            let get = |property: Option<usize>| values[property.unwrap()];
+            match element.name.as_str() {
+                "vertex" => {
+                    if !all(&position) {
+                        return Err(invalid("PLY vertex without a position"));
+                    }
+                    positions.push(Point3::new(
+                        get(position[0]),
+                        get(position[1]),
+                        get(position[2]),
+                    ));
+                    if all(&normal) {
+                        normals.push(Vec3::new(get(normal[0]), get(normal[1]), get(normal[2])));
+                    }
+                    if all(&color) {
+                        let channel = |property: Option<usize>| {
+                            let scalar = element.properties[property.unwrap()].scalar;
+                            get(property) / scalar.color_scale()
+                        };
+                        colors.push(Color::new(
+                            channel(color[0]),
+                            channel(color[1]),
+                            channel(color[2]),
+                        ));
+                    }
+                    if all(&uv) {
+                        uvs.push((get(uv[0]), get(uv[1])));
+                    }
+                }
+                "face" => {
+                    //indices are read as numbers like everything else, so check they are indices
+                    let indices = list
+                        .iter()
+                        .map(|index| {
+                            if *index >= 0.0 && index.fract() == 0.0 {
+                                Ok(*index as usize)
+                            } else {
+                                Err(invalid("PLY face has a bad vertex index"))
+                            }
+                        })
+                        .collect::<io::Result<Vec<usize>>>()?;
+                    for corner in 2..indices.len() {
+                        faces.push([indices[0], indices[corner - 1], indices[corner]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if faces
+        .iter()
+        .flatten()
+        .any(|index| *index >= positions.len())
+    {
+        return Err(invalid("PLY face refers to a missing vertex"));
+    }
+
+    let mut mesh = TriangleMesh::new(positions, faces);
+    if !normals.is_empty() {
+        mesh = mesh.with_normals(normals);
+    }
+    if !colors.is_empty() {
+        mesh = mesh.with_colors(colors);
+    }
+    if !uvs.is_empty() {
+        mesh = mesh.with_uvs(uvs);
+    }
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::ply::*;
+
+    const HEADER: &str = "element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+    //a unit square in the xy plane, red at the origin
+    fn binary(big_endian: bool) -> Vec<u8> {
+        let format = if big_endian {
+            "binary_big_endian"
+        } else {
+            "binary_little_endian"
+        };
+        let mut data = format!("ply\nformat {} 1.0\ncomment test\n{}", format, HEADER).into_bytes();
+        let corners = [(0.0f32, 0.0f32), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        for (index, (x, y)) in corners.iter().enumerate() {
+            for value in [*x, *y, 0.0].iter() {
+                let bytes = if big_endian {
+                    value.to_be_bytes()
+                } else {
+                    value.to_le_bytes()
+                };
+                data.extend_from_slice(&bytes);
+            }
+            let red = if index == 0 { 255 } else { 0 };
+            data.extend_from_slice(&[red, 0, 255 - red]);
+        }
+        data.push(4);
+        for index in 0..4i32 {
+            let bytes = if big_endian {
+                index.to_be_bytes()
+            } else {
+                index.to_le_bytes()
+            };
+            data.extend_from_slice(&bytes);
+        }
+        data
+    }
+
+    fn check_square(mesh: &TriangleMesh) {
+        assert_eq!(mesh.positions().len(), 4);
+        assert_eq!(mesh.faces(), &[[0, 1, 2], [0, 2, 3]]);
+        assert!((mesh.positions()[2] - Point3::new(1.0, 1.0, 0.0)).length() < 1e-9);
+        assert_eq!(mesh.colors().len(), 4);
+        assert!((mesh.colors()[0].r() - 1.0).abs() < 1e-9);
+        assert!((mesh.colors()[1].b() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_read_ply() {
+        let ascii = format!(
+            "ply\r\nformat ascii 1.0\r\n{}0 0 0 255 0 0\n1 0 0 0 0 255\n1 1 0 0 0 255\n0 1 0 0 0 255\n4 0 1 2 3\n",
+            HEADER
+        );
+        check_square(&read_ply(ascii.as_bytes()).unwrap());
+        check_square(&read_ply(&binary(false)).unwrap());
+        check_square(&read_ply(&binary(true)).unwrap());
+
+        //normals and texture coordinates, with an extra element that gets skipped
+        let extras = "ply
+format ascii 1.0
+element vertex 3
+property double x
+property double y
+property double z
+property float nx
+property float ny
+property float nz
+property float s
+property float t
+element edge 1
+property int vertex1
+property int vertex2
+element face 1
+property list uchar uint vertex_index
+end_header
+0 0 0 0 0 1 0 0
+1 0 0 0 0 1 1 0
+0 1 0 0 0 1 0 1
+0 1
+3 0 1 2
+";
+        let mesh = read_ply(extras.as_bytes()).unwrap();
+        assert_eq!(mesh.faces(), &[[0, 1, 2]]);
+        assert_eq!(mesh.normals().len(), 3);
+        assert_eq!(mesh.uvs()[1], (1.0, 0.0));
+        assert!(mesh.colors().is_empty());
+
+        assert!(read_ply(
+            b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nend_header\n"
+        )
+        .is_err());
+        let truncated = binary(false);
+        assert!(read_ply(&truncated[..truncated.len() - 5]).is_err());
+        assert!(read_ply(b"solid cube\n").is_err());
+        for face in ["3 0 1 -2", "3 0 1 2.5"].iter() {
+            let broken = extras.replace("3 0 1 2", face);
+            assert!(read_ply(broken.as_bytes()).is_err());
+        }
+    }
+}
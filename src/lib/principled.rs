@@ -0,0 +1,398 @@
+use crate::lib::{
+    color::*, hittable::HitRecord, material::Material, microfacet::*, onb::Onb, ray::Ray,
+    sampler::Sampler, vec3::*,
+};
+
+//Disney style "principled" uber-material - a diffuse base with sheen, a GGX specular layer that
+//blends into metal, a clear coat on top and glass-like transmission, all driven by [0,1] parameters
+#[derive(Copy, Clone)]
+pub struct Principled {
+    pub base_color: Color,
+    pub metallic: f64,
+    pub roughness: f64,
+    pub specular: f64,      //dielectric reflectance, 0.5 is the usual 4%
+    pub specular_tint: f64, //how much the dielectric reflection takes on the base color
+    pub clearcoat: f64,
+    pub clearcoat_roughness: f64,
+    pub sheen: f64, //extra reflection at grazing angles, for cloth
+    pub sheen_tint: f64,
+    pub transmission: f64,
+    pub ior: f64,
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Self {
+            base_color: Color::new(0.8, 0.8, 0.8),
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            specular_tint: 0.0,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.03,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            transmission: 0.0,
+            ior: 1.5,
+        }
+    }
+}
+
+//the layers one scattering event can pick from
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Lobe {
+    Diffuse,
+    Specular,
+    Clearcoat,
+    Transmission,
+}
+
+impl Principled {
+    //picks one layer in proportion to how much light it is expected to return, samples it, and
+    //divides its weight by the probability of picking it
+    pub fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        let frame = Onb::from_normal(&hit_rec.normal());
+        let wo = frame.world_to_local(&-ray_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return false;
+        }
+
+        let weights = self.lobe_weights(wo.z(), hit_rec.front_face());
+        let total: f64 = weights.iter().map(|(_, weight)| weight).sum();
+        if total <= 0.0 {
+            return false;
+        }
+
+        let mut choice = sampler.next_1d() * total;
+        let (lobe, weight) = weights
+            .iter()
+            .copied()
+            .find(|(_, weight)| {
+                choice -= weight;
+                choice < 0.0
+            })
+            .unwrap_or(weights[weights.len() - 1]);
+        let probability = weight / total;
+
+        let scattered_lobe = match lobe {
+            Lobe::Diffuse => self.scatter_diffuse(&wo, &frame, ray_in, hit_rec, sampler),
+            Lobe::Specular => {
+                let f0 = self.specular_f0();
+                scatter_ggx(&wo, &frame, self.roughness, &f0, ray_in, hit_rec, sampler)
+                    .map(|(color, ray)| (&color * self.specular_scale(), ray))
+            }
+            Lobe::Clearcoat => {
+                let f0 = Color::new(0.04, 0.04, 0.04);
+                scatter_ggx(
+                    &wo,
+                    &frame,
+                    self.clearcoat_roughness,
+                    &f0,
+                    ray_in,
+                    hit_rec,
+                    sampler,
+                )
+                .map(|(color, ray)| (&color * self.clearcoat, ray))
+            }
+            Lobe::Transmission => {
+                //the same rough glass as Material::Dielectric, tinted by the base color on the way in
+                let glass = Material::glass(self.ior, self.roughness, &Color::default());
+                let mut glass_attenuation = Color::default();
+                let mut glass_ray = Ray::default();
+                if Material::scatter(
+                    &glass,
+                    ray_in,
+                    hit_rec,
+                    &mut glass_attenuation,
+                    &mut glass_ray,
+                    sampler,
+                ) {
+                    //on the way out the glass is all there is, so it keeps all of its light
+                    let (tint, lobe_scale) = if hit_rec.front_face() {
+                        (self.base_color, (1.0 - self.metallic) * self.transmission)
+                    } else {
+                        (Color::new(1.0, 1.0, 1.0), 1.0)
+                    };
+                    Some((&(&glass_attenuation * &tint) * lobe_scale, glass_ray))
+                } else {
+                    None
+                }
+            }
+        };
+
+        match scattered_lobe {
+            Some((color, ray)) => {
+                *attenuation = &color * (1.0 / probability);
+                *scattered = ray;
+                true
+            }
+            None => false,
+        }
+    }
+
+    //rough estimate of the light each layer returns when seen at 'cos_o' - only used to pick layers
+    fn lobe_weights(&self, cos_o: f64, front_face: bool) -> [(Lobe, f64); 4] {
+        let dielectric = 1.0 - self.metallic;
+        let specular =
+            self.specular_scale() * schlick_color(&self.specular_f0(), cos_o).luminance();
+        //a ray inside a transmissive object can only leave through the glass, metallic or not
+        let inside = !front_face && self.transmission > 0.0;
+
+        [
+            (
+                Lobe::Diffuse,
+                if inside {
+                    0.0
+                } else {
+                    dielectric * (1.0 - self.transmission) * self.diffuse_color(cos_o).luminance()
+                },
+            ),
+            (Lobe::Specular, if inside { 0.0 } else { specular }),
+            (
+                Lobe::Clearcoat,
+                if inside {
+                    0.0
+                } else {
+                    self.clearcoat * schlick(0.04, cos_o)
+                },
+            ),
+            (
+                Lobe::Transmission,
+                if inside {
+                    1.0
+                } else {
+                    dielectric * self.transmission
+                },
+            ),
+        ]
+    }
+
+    //the glass already reflects its share of the light, so the specular layer only covers the rest
+    fn specular_scale(&self) -> f64 {
+        1.0 - (1.0 - self.metallic) * self.transmission
+    }
+
+    //reflectance of the specular layer looking straight at the surface - blends from a tinted
+    //dielectric highlight to the base color as the surface becomes metallic
+    fn specular_f0(&self) -> Color {
+        let dielectric = 0.08 * self.specular;
+        let tint = lerp_color(&Color::new(1.0, 1.0, 1.0), &self.tint(), self.specular_tint);
+        lerp_color(&(&tint * dielectric), &self.base_color, self.metallic)
+    }
+
+    //base color normalized to unit luminance, so tints change the hue but not the brightness
+    fn tint(&self) -> Color {
+        let luminance = self.base_color.luminance();
+        if luminance > 0.0 {
+            &self.base_color * (1.0 / luminance)
+        } else {
+            Color::new(1.0, 1.0, 1.0)
+        }
+    }
+
+    //diffuse base plus sheen, minus what the specular layer already reflected
+    fn diffuse_color(&self, cos: f64) -> Color {
+        let fresnel_weight = (1.0 - cos.clamp(0.0, 1.0)).powi(5);
+        let sheen_color = lerp_color(&Color::new(1.0, 1.0, 1.0), &self.tint(), self.sheen_tint);
+        let sheen = &sheen_color * (self.sheen * fresnel_weight);
+
+        let transmitted = 1.0 - schlick(0.08 * self.specular, cos);
+        &self.base_color * transmitted + sheen
+    }
+
+    fn scatter_diffuse(
+        &self,
+        wo: &Vec3,
+        frame: &Onb,
+        ray_in: &Ray,
+        hit_rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        //cosine weighted, so the Lambertian weight is just the color
+        let direction = hit_rec.normal() + Vec3::unit_vector_from_sample(sampler.next_2d());
+        if direction.length_squared() < 1e-12 {
+            return None;
+        }
+        let wi = frame.world_to_local(&direction.unit_vector());
+
+        //the sheen follows the angle between the light and the half vector
+        let half = (*wo + wi).unit_vector();
+        let color = &self.diffuse_color(wi.dot(&half))
+            * ((1.0 - self.metallic) * (1.0 - self.transmission));
+
+        Some((color, Ray::new(&hit_rec.point(), &direction, ray_in.time())))
+    }
+}
+
+//a GGX reflection layer with Schlick's Fresnel
+fn scatter_ggx(
+    wo: &Vec3,
+    frame: &Onb,
+    roughness: f64,
+    f0: &Color,
+    ray_in: &Ray,
+    hit_rec: &HitRecord,
+    sampler: &mut dyn Sampler,
+) -> Option<(Color, Ray)> {
+    let ggx = Ggx::from_roughness(roughness);
+    let microfacet_normal = if ggx.is_smooth() {
+        Vec3::new(0.0, 0.0, 1.0)
+    } else {
+        ggx.sample_visible_normal(wo, sampler.next_2d())
+    };
+
+    let wi = (-*wo).reflect(&microfacet_normal);
+    if wi.z() <= 0.0 {
+        return None;
+    }
+
+    let shadowing = if ggx.is_smooth() {
+        1.0
+    } else {
+        ggx.g2(wo, &wi) / ggx.g1(wo)
+    };
+    let fresnel = schlick_color(f0, wo.dot(&microfacet_normal));
+
+    Some((
+        &fresnel * shadowing,
+        Ray::new(&hit_rec.point(), &frame.local_to_world(&wi), ray_in.time()),
+    ))
+}
+
+fn schlick(f0: f64, cos: f64) -> f64 {
+    f0 + (1.0 - f0) * (1.0 - cos.clamp(0.0, 1.0)).powi(5)
+}
+
+fn schlick_color(f0: &Color, cos: f64) -> Color {
+    Color::new(
+        schlick(f0.r(), cos),
+        schlick(f0.g(), cos),
+        schlick(f0.b(), cos),
+    )
+}
+
+fn lerp_color(a: &Color, b: &Color, t: f64) -> Color {
+    a * (1.0 - t) + b * t
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::{hittable::Hittable, principled::*, sampler::RandomSampler, sphere::Sphere};
+
+    #[test]
+    fn test_white_furnace_does_not_gain_energy() {
+        //a white principled surface under uniform white light can't reflect more than it receives
+        let mut sampler = RandomSampler::new();
+        let mut hit_rec = HitRecord::new_invalid();
+        let ray = Ray::new(
+            &Point3::new(0.0, 1.0, 1.0),
+            &Vec3::new(0.0, -1.0, -0.5),
+            0.0,
+        );
+        hit_rec.set_p(Point3::new(0.0, 0.0, 0.0));
+        hit_rec.set_face_normal(&ray, &Vec3::new(0.0, 1.0, 0.0));
+
+        let principled = Principled {
+            base_color: Color::new(1.0, 1.0, 1.0),
+            roughness: 0.4,
+            ..Principled::default()
+        };
+
+        let samples = 20_000;
+        let mut sum = 0.0;
+        for _ in 0..samples {
+            let mut attenuation = Color::default();
+            let mut scattered = Ray::default();
+            if principled.scatter(
+                &ray,
+                &hit_rec,
+                &mut attenuation,
+                &mut scattered,
+                &mut sampler,
+            ) {
+                sum += attenuation.luminance();
+            }
+        }
+        assert!(sum / samples as f64 <= 1.02);
+    }
+
+    #[test]
+    fn test_transmissive_furnace_keeps_energy() {
+        //clear glass under uniform white light passes everything through, however often it bounces
+        let principled = Principled {
+            base_color: Color::new(1.0, 1.0, 1.0),
+            roughness: 0.0,
+            transmission: 1.0,
+            ..Principled::default()
+        };
+        let sphere = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Material::principled(&principled),
+        );
+        let mut sampler = RandomSampler::new();
+
+        let paths = 2_000;
+        let mut sum = 0.0;
+        for i in 0..paths {
+            let offset = (i as f64 + 0.5) / paths as f64 * 0.99;
+            let mut ray = Ray::new(
+                &Point3::new(offset, 0.0, 5.0),
+                &Vec3::new(0.0, 0.0, -1.0),
+                0.0,
+            );
+            let mut throughput = Color::new(1.0, 1.0, 1.0);
+            let mut hit_rec = HitRecord::new_invalid();
+            for _ in 0..100 {
+                if !sphere.hit(&ray, 1e-6, f64::INFINITY, &mut hit_rec) {
+                    break;
+                }
+                let mut attenuation = Color::default();
+                let mut scattered = Ray::default();
+                assert!(principled.scatter(
+                    &ray,
+                    &hit_rec,
+                    &mut attenuation,
+                    &mut scattered,
+                    &mut sampler,
+                ));
+                throughput = &throughput * &attenuation;
+                ray = scattered;
+            }
+            sum += throughput.luminance();
+        }
+        assert!((sum / paths as f64 - 1.0).abs() < 0.02);
+
+        //half transmissive glass, metallic or not, still lets all the light out from inside
+        let ray = Ray::new(&Point3::new(0.0, 0.0, 0.0), &Vec3::new(0.0, 0.0, 1.0), 0.0);
+        let mut hit_rec = HitRecord::new_invalid();
+        hit_rec.set_p(Point3::new(0.0, 0.0, 1.0));
+        hit_rec.set_face_normal(&ray, &Vec3::new(0.0, 0.0, 1.0));
+        assert!(!hit_rec.front_face());
+        for metallic in [0.0, 1.0].iter() {
+            let principled = Principled {
+                metallic: *metallic,
+                roughness: 0.0,
+                transmission: 0.5,
+                ..Principled::default()
+            };
+            let mut attenuation = Color::default();
+            let mut scattered = Ray::default();
+            assert!(principled.scatter(
+                &ray,
+                &hit_rec,
+                &mut attenuation,
+                &mut scattered,
+                &mut sampler,
+            ));
+            assert!((attenuation.luminance() - 1.0).abs() < 1e-9);
+        }
+    }
+}
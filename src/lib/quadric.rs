@@ -0,0 +1,534 @@
+use crate::lib::{
+    aabb::*, hittable::*, material::Material, onb::Onb, plane::disk_bounds, ray::Ray, rt_math::*,
+    vec3::*,
+};
+use std::{f64::consts::PI, sync::Arc};
+
+//the surfaces below are all symmetric about an axis. They are worked out in a local frame with the
+//axis along +z, starting at the base (z = 0) and ending 'height' further along. 'phi_max' cuts the
+//sweep around the axis short, for partial tubes and cut-away views. u goes around the axis and v
+//along it
+struct Shape {
+    base: Point3,
+    frame: Onb,
+    radius: f64,
+    height: f64,
+    phi_max: f64, //radians
+    caps: bool,
+    material: Material,
+}
+
+//an open tube, or a closed can with caps
+pub struct Cylinder {
+    shape: Shape,
+}
+
+//a cone with its base on the base point and its apex 'height' along the axis, capped at the base
+pub struct Cone {
+    shape: Shape,
+}
+
+//a bowl with its vertex at the base point, 'radius' wide at the rim, capped at the rim
+pub struct Paraboloid {
+    shape: Shape,
+}
+
+//where a ray meets a surface, in the local frame
+struct LocalHit {
+    t: f64,
+    outward_normal: Vec3,
+    uv: (f64, f64),
+    dpdu: Vec3,
+    dpdv: Vec3,
+}
+
+//the parts each surface supplies - the rest of the intersection is shared
+trait Quadric {
+    fn shape(&self) -> &Shape;
+
+    //coefficients of the quadratic in t for the local ray
+    fn coefficients(&self, origin: &Vec3, direction: &Vec3) -> (f64, f64, f64);
+
+    //outward normal, dpdu and dpdv at a local point on the surface
+    fn differentials(&self, point: &Vec3) -> (Vec3, Vec3, Vec3);
+
+    //height and radius of each cap, with the direction its outward normal points along z
+    fn caps(&self) -> Vec<(f64, f64, f64)>;
+
+    //radius of the surface at the base and at the top, for bounding it
+    fn end_radii(&self) -> (f64, f64);
+}
+
+impl Shape {
+    fn new(base: Point3, axis: Vec3, radius: f64, height: f64, material: Material) -> Self {
+        Self {
+            base,
+            frame: Onb::from_normal(&axis.unit_vector()),
+            radius,
+            height,
+            phi_max: 2.0 * PI,
+            caps: false,
+            material,
+        }
+    }
+
+    //angle around the axis in [0, 2pi)
+    fn phi(point: &Vec3) -> f64 {
+        let phi = point.y().atan2(point.x());
+        if phi < 0.0 {
+            phi + 2.0 * PI
+        } else {
+            phi
+        }
+    }
+
+    fn in_sweep(&self, point: &Vec3) -> bool {
+        Shape::phi(point) <= self.phi_max
+    }
+
+    fn around(&self, point: &Vec3) -> Vec3 {
+        self.phi_max * Vec3::new(-point.y(), point.x(), 0.0)
+    }
+
+    fn bounds(&self, base_radius: f64, top_radius: f64) -> AABB {
+        let axis = self.frame.w();
+        let whole = AABB::surrounding_box(
+            &disk_bounds(&self.base, &axis, base_radius),
+            &disk_bounds(&(self.base + self.height * axis), &axis, top_radius),
+        );
+        if self.phi_max >= 2.0 * PI {
+            return whole;
+        }
+
+        //a partial sweep only reaches as far as the ends of its arc and the local axes it crosses
+        //on the way. The caps and the narrow ends come in to the axis, so they bound it on the inside
+        let outer = base_radius.max(top_radius);
+        let inner = if self.caps {
+            0.0
+        } else {
+            base_radius.min(top_radius)
+        };
+        let crossings = (1..4)
+            .map(|quarter| quarter as f64 * PI / 2.0)
+            .filter(|angle| *angle < self.phi_max);
+        let mut low = (f64::INFINITY, f64::INFINITY);
+        let mut high = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for (angle, radius) in [
+            (0.0, inner),
+            (0.0, outer),
+            (self.phi_max, inner),
+            (self.phi_max, outer),
+        ]
+        .iter()
+        .copied()
+        .chain(crossings.map(|angle| (angle, outer)))
+        {
+            let (x, y) = (radius * angle.cos(), radius * angle.sin());
+            low = (low.0.min(x), low.1.min(y));
+            high = (high.0.max(x), high.1.max(y));
+        }
+
+        //the local box turned into the world, which is only tighter than the disks' for the parts
+        //the sweep leaves out
+        let corners = (0..8).map(|corner| {
+            let local = Vec3::new(
+                if corner & 1 == 0 { low.0 } else { high.0 },
+                if corner & 2 == 0 { low.1 } else { high.1 },
+                if corner & 4 == 0 { 0.0 } else { self.height },
+            );
+            self.base + self.frame.local_to_world(&local)
+        });
+        let swept = corners.fold(None, |bounds: Option<AABB>, corner| {
+            let point = AABB::new(&corner, &corner);
+            Some(bounds.map_or(point, |bounds| AABB::surrounding_box(&bounds, &point)))
+        });
+        AABB::overlapping_box(&whole, &swept.unwrap())
+    }
+}
+
+fn hit_quadric(
+    quadric: &dyn Quadric,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+    hit_rec: &mut HitRecord,
+) -> bool {
+    let shape = quadric.shape();
+    //the direction isn't normalized, so t means the same thing in both frames
+    let origin = shape.frame.world_to_local(&(ray.origin() - shape.base));
+    let direction = shape.frame.world_to_local(&ray.direction());
+    let at = |t: f64| origin + t * direction;
+
+    let mut closest: Option<LocalHit> = None;
+    let mut closest_so_far = t_max;
+
+    //the side - the near root can be cut away by the height or the sweep, leaving the far one
+    let (a, b, c) = quadric.coefficients(&origin, &direction);
+    if let Some((root_0, root_1)) = solve_quadratic(a, b, c) {
+        for t in [root_0, root_1].iter().copied() {
+            if t <= t_min || t >= closest_so_far {
+                continue;
+            }
+            let point = at(t);
+            if point.z() < 0.0 || point.z() > shape.height || !shape.in_sweep(&point) {
+                continue;
+            }
+
+            let (outward_normal, dpdu, dpdv) = quadric.differentials(&point);
+            closest = Some(LocalHit {
+                t,
+                outward_normal,
+                uv: (Shape::phi(&point) / shape.phi_max, point.z() / shape.height),
+                dpdu,
+                dpdv,
+            });
+            closest_so_far = t;
+            break;
+        }
+    }
+
+    if shape.caps && direction.z() != 0.0 {
+        for (height, cap_radius, facing) in quadric.caps() {
+            let t = (height - origin.z()) / direction.z();
+            if t <= t_min || t >= closest_so_far {
+                continue;
+            }
+            let point = at(t);
+            let distance = (point.x() * point.x() + point.y() * point.y()).sqrt();
+            if distance > cap_radius || !shape.in_sweep(&point) {
+                continue;
+            }
+
+            //v runs from the center of the cap out to the rim
+            let outwards = if distance > 0.0 {
+                Vec3::new(point.x(), point.y(), 0.0) / distance
+            } else {
+                Vec3::new(1.0, 0.0, 0.0)
+            };
+            closest = Some(LocalHit {
+                t,
+                outward_normal: Vec3::new(0.0, 0.0, facing),
+                uv: (Shape::phi(&point) / shape.phi_max, distance / cap_radius),
+                dpdu: shape.around(&point),
+                dpdv: cap_radius * outwards,
+            });
+            closest_so_far = t;
+        }
+    }
+
+    match closest {
+        Some(hit) => {
+            let frame = &shape.frame;
+            hit_rec.set_t(hit.t);
+            hit_rec.set_p(ray.at(hit.t));
+            hit_rec.set_face_normal(
+                ray,
+                &frame.local_to_world(&hit.outward_normal).unit_vector(),
+            );
+            hit_rec.set_uv(hit.uv.0, hit.uv.1);
+            hit_rec.set_tangents(
+                &frame.local_to_world(&hit.dpdu),
+                &frame.local_to_world(&hit.dpdv),
+            );
+            hit_rec.set_material(shape.material);
+            true
+        }
+        None => false,
+    }
+}
+
+macro_rules! quadric_hittable {
+    ($name:ident) => {
+        #[allow(dead_code)]
+        impl $name {
+            pub fn new(
+                base: Point3,
+                axis: Vec3,
+                radius: f64,
+                height: f64,
+                material: Material,
+            ) -> Self {
+                Self {
+                    shape: Shape::new(base, axis, radius, height, material),
+                }
+            }
+
+            //how far round the axis the surface goes, in degrees
+            pub fn with_sweep(mut self, degrees: f64) -> Self {
+                self.shape.phi_max = degrees_to_radians(degrees.clamp(0.0, 360.0));
+                self
+            }
+
+            pub fn with_caps(mut self, caps: bool) -> Self {
+                self.shape.caps = caps;
+                self
+            }
+
+            pub fn new_hittable(
+                base: Point3,
+                axis: Vec3,
+                radius: f64,
+                height: f64,
+                material: Material,
+            ) -> Arc<dyn Hittable> {
+                Arc::new($name::new(base, axis, radius, height, material))
+            }
+        }
+
+        impl Hittable for $name {
+            fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
+                hit_quadric(self, ray, t_min, t_max, hit_rec)
+            }
+
+            fn bounding_box(&self, _t_0: f64, _t_1: f64, output_box: &mut AABB) -> bool {
+                let (base_radius, top_radius) = self.end_radii();
+                *output_box = self.shape.bounds(base_radius, top_radius);
+                true
+            }
+        }
+    };
+}
+
+quadric_hittable!(Cylinder);
+quadric_hittable!(Cone);
+quadric_hittable!(Paraboloid);
+
+//x^2 + y^2 = r^2
+impl Quadric for Cylinder {
+    fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    fn coefficients(&self, origin: &Vec3, direction: &Vec3) -> (f64, f64, f64) {
+        let radius = self.shape.radius;
+        (
+            direction.x() * direction.x() + direction.y() * direction.y(),
+            2.0 * (origin.x() * direction.x() + origin.y() * direction.y()),
+            origin.x() * origin.x() + origin.y() * origin.y() - radius * radius,
+        )
+    }
+
+    fn differentials(&self, point: &Vec3) -> (Vec3, Vec3, Vec3) {
+        (
+            Vec3::new(point.x(), point.y(), 0.0) / self.shape.radius,
+            self.shape.around(point),
+            Vec3::new(0.0, 0.0, self.shape.height),
+        )
+    }
+
+    fn caps(&self) -> Vec<(f64, f64, f64)> {
+        vec![
+            (0.0, self.shape.radius, -1.0),
+            (self.shape.height, self.shape.radius, 1.0),
+        ]
+    }
+
+    fn end_radii(&self) -> (f64, f64) {
+        (self.shape.radius, self.shape.radius)
+    }
+}
+
+//x^2 + y^2 = (r (h - z) / h)^2
+impl Quadric for Cone {
+    fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    fn coefficients(&self, origin: &Vec3, direction: &Vec3) -> (f64, f64, f64) {
+        let k = (self.shape.radius / self.shape.height).powi(2);
+        let to_apex = self.shape.height - origin.z();
+        (
+            direction.x() * direction.x() + direction.y() * direction.y()
+                - k * direction.z() * direction.z(),
+            2.0 * (origin.x() * direction.x()
+                + origin.y() * direction.y()
+                + k * to_apex * direction.z()),
+            origin.x() * origin.x() + origin.y() * origin.y() - k * to_apex * to_apex,
+        )
+    }
+
+    fn differentials(&self, point: &Vec3) -> (Vec3, Vec3, Vec3) {
+        let k = (self.shape.radius / self.shape.height).powi(2);
+        //straight down the side to the base rim, which stays defined at the apex
+        let phi = Shape::phi(point);
+        let rim = self.shape.radius * Vec3::new(phi.cos(), phi.sin(), 0.0);
+        let to_apex = Vec3::new(0.0, 0.0, self.shape.height) - rim;
+        (
+            Vec3::new(point.x(), point.y(), k * (self.shape.height - point.z())).unit_vector(),
+            self.shape.around(point),
+            to_apex,
+        )
+    }
+
+    fn caps(&self) -> Vec<(f64, f64, f64)> {
+        vec![(0.0, self.shape.radius, -1.0)]
+    }
+
+    fn end_radii(&self) -> (f64, f64) {
+        (self.shape.radius, 0.0)
+    }
+}
+
+//z = h (x^2 + y^2) / r^2
+impl Quadric for Paraboloid {
+    fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    fn coefficients(&self, origin: &Vec3, direction: &Vec3) -> (f64, f64, f64) {
+        let k = self.shape.height / (self.shape.radius * self.shape.radius);
+        (
+            k * (direction.x() * direction.x() + direction.y() * direction.y()),
+            2.0 * k * (origin.x() * direction.x() + origin.y() * direction.y()) - direction.z(),
+            k * (origin.x() * origin.x() + origin.y() * origin.y()) - origin.z(),
+        )
+    }
+
+    fn differentials(&self, point: &Vec3) -> (Vec3, Vec3, Vec3) {
+        let k = self.shape.height / (self.shape.radius * self.shape.radius);
+        //up the side of the bowl, which goes flat at the vertex
+        let dpdv = if point.z() > 0.0 {
+            self.shape.height
+                * Vec3::new(
+                    point.x() / (2.0 * point.z()),
+                    point.y() / (2.0 * point.z()),
+                    1.0,
+                )
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        (
+            Vec3::new(2.0 * k * point.x(), 2.0 * k * point.y(), -1.0).unit_vector(),
+            self.shape.around(point),
+            dpdv,
+        )
+    }
+
+    fn caps(&self) -> Vec<(f64, f64, f64)> {
+        vec![(self.shape.height, self.shape.radius, 1.0)]
+    }
+
+    //every slice is inside the rim, so the bowl fits in the cylinder through it
+    fn end_radii(&self) -> (f64, f64) {
+        (self.shape.radius, self.shape.radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::quadric::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_cylinder() {
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let can = Cylinder::new(
+            Point3::new(0.0, 0.0, 0.0),
+            up,
+            1.0,
+            2.0,
+            Material::default(),
+        );
+
+        let side = trace(&can, Point3::new(5.0, 0.5, 0.0), Vec3::new(-1.0, 0.0, 0.0)).unwrap();
+        assert!(close(side.t(), 4.0));
+        assert!((side.normal() - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+        assert!(close(side.v(), 0.25));
+
+        //without caps a ray down the axis goes straight through, with them it stops on the top
+        assert!(trace(&can, Point3::new(0.2, 5.0, 0.0), -up).is_none());
+        let can = can.with_caps(true);
+        let top = trace(&can, Point3::new(0.2, 5.0, 0.0), -up).unwrap();
+        assert!(close(top.t(), 3.0));
+        assert!((top.normal() - up).length() < 1e-9);
+
+        let mut bounds = AABB::default();
+        assert!(can.bounding_box(0.0, 1.0, &mut bounds));
+        assert!((bounds.min() - Point3::new(-1.0, 0.0, -1.0)).length() < 1e-3);
+        assert!((bounds.max() - Point3::new(1.0, 2.0, 1.0)).length() < 1e-3);
+    }
+
+    #[test]
+    fn test_half_cylinder_shows_its_inside() {
+        let half = Cylinder::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            2.0,
+            Material::default(),
+        )
+        .with_sweep(180.0);
+
+        //from one side the near wall is there, from the other it's cut away and the ray carries on
+        //to the inside of the far wall
+        let from_front = trace(&half, Point3::new(0.0, 1.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let from_back = trace(&half, Point3::new(0.0, 1.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let (near, far) = match (from_front, from_back) {
+            (Some(front), Some(back)) if front.t() < back.t() => (front, back),
+            (Some(front), Some(back)) => (back, front),
+            _ => panic!("both rays should hit the half cylinder"),
+        };
+        assert!(close(near.t(), 4.0) && near.front_face());
+        assert!(close(far.t(), 6.0) && !far.front_face());
+
+        //and its bounds only cover the half that's there
+        let mut bounds = AABB::default();
+        assert!(half.bounding_box(0.0, 1.0, &mut bounds));
+        let size = bounds.max() - bounds.min();
+        assert!((size.x() * size.y() * size.z() - 4.0).abs() < 1e-2);
+        assert!(close(size.y(), 2.0));
+    }
+
+    #[test]
+    fn test_cone() {
+        let cone = Cone::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            2.0,
+            Material::default(),
+        )
+        .with_caps(true);
+
+        //halfway up the radius is 0.5, and the side leans back at atan(1 / 2)
+        let side = trace(&cone, Point3::new(5.0, 1.0, 0.0), Vec3::new(-1.0, 0.0, 0.0)).unwrap();
+        assert!(close(side.t(), 4.5));
+        assert!((side.normal() - Vec3::new(2.0, 1.0, 0.0).unit_vector()).length() < 1e-9);
+
+        let base = trace(&cone, Point3::new(0.3, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0)).unwrap();
+        assert!(close(base.t(), 5.0));
+        assert!((base.normal() - Vec3::new(0.0, -1.0, 0.0)).length() < 1e-9);
+
+        //the other nappe of the double cone isn't part of the surface
+        assert!(trace(&cone, Point3::new(5.0, 3.0, 0.0), Vec3::new(-1.0, 0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn test_paraboloid() {
+        let bowl = Paraboloid::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            1.0,
+            Material::default(),
+        );
+
+        //a quarter of the way up the radius is 0.5
+        let side = trace(
+            &bowl,
+            Point3::new(5.0, 0.25, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!(close(side.t(), 4.5));
+        assert!((side.normal() - Vec3::new(1.0, -1.0, 0.0).unit_vector()).length() < 1e-9);
+
+        //straight down into the bowl hits the inside of the vertex
+        let vertex = trace(&bowl, Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).unwrap();
+        assert!(close(vertex.t(), 5.0));
+        assert!(!vertex.front_face());
+    }
+}
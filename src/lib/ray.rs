@@ -4,6 +4,7 @@ pub struct Ray {
     orig: Point3,
     dir: Vec3,
     time: f64,
+    wavelength: f64, //in nanometers, 0 for rays that carry all of RGB
 }
 
 #[allow(dead_code)]
@@ -13,6 +14,15 @@ impl Ray {
             orig: *origin,
             dir: *direction,
             time,
+            wavelength: 0.0,
+        }
+    }
+
+    //the same ray, carrying a single wavelength for spectral rendering
+    pub fn with_wavelength(&self, wavelength: f64) -> Self {
+        Self {
+            wavelength,
+            ..*self
         }
     }
     pub fn origin(&self) -> Point3 {
@@ -37,4 +47,8 @@ impl Ray {
     pub fn time(&self) -> f64 {
         self.time
     }
+
+    pub fn wavelength(&self) -> f64 {
+        self.wavelength
+    }
 }
@@ -0,0 +1,229 @@
+use crate::lib::{
+    adaptive::*, aov::*, camera::Camera, color::*, film::Film, framebuffer::Framebuffer,
+    hittable::*, settings::Settings, spectrum::*, vec3::*,
+};
+
+use rayon::prelude::*;
+
+pub struct RenderOutput {
+    pub beauty: Framebuffer,
+    pub aovs: Option<AovBuffers>,
+    pub sample_counts: Vec<usize>, //samples actually taken for each pixel
+}
+
+//the image is rendered in square tiles, each splatting onto its own small film
+const TILE_SIZE: usize = 16;
+
+//everything rendered for one pixel, apart from its color which lives on the film
+#[derive(Copy, Clone, Default)]
+struct PixelResult {
+    aov: AovSample,
+    samples: usize,
+}
+
+struct TileResult {
+    x0: usize,
+    y0: usize,
+    width: usize,
+    film: Film,
+    pixels: Vec<PixelResult>,
+}
+
+pub fn render(settings: &Settings, world: &dyn Hittable, cam: &Camera) -> RenderOutput {
+    let image_width = settings.image_width();
+    let image_height = settings.image_height;
+
+    let tiles: Vec<(usize, usize)> = (0..image_height)
+        .step_by(TILE_SIZE)
+        .flat_map(|y0| (0..image_width).step_by(TILE_SIZE).map(move |x0| (x0, y0)))
+        .collect();
+
+    let tile_results: Vec<TileResult> = tiles
+        .par_iter()
+        .map(|&(x0, y0)| render_tile(settings, world, cam, x0, y0))
+        .collect();
+
+    let mut film = Film::new(0, 0, image_width, image_height, settings.filter);
+    let mut screen = vec![PixelResult::default(); image_width * image_height];
+
+    for tile in &tile_results {
+        film.merge(&tile.film);
+
+        for (index, pixel) in tile.pixels.iter().enumerate() {
+            let column = tile.x0 + index % tile.width;
+            let row = tile.y0 + index / tile.width;
+            screen[row * image_width + column] = *pixel;
+        }
+    }
+
+    let aovs = if settings.needs_aovs() {
+        let mut buffers = AovBuffers::new(image_width, image_height);
+
+        for (index, pixel) in screen.iter().enumerate() {
+            let (column, row) = (index % image_width, index / image_width);
+            let aov = &pixel.aov;
+
+            buffers
+                .depth
+                .set(column, row, Color::new(aov.depth, aov.depth, aov.depth));
+            buffers.normal.set(
+                column,
+                row,
+                Color::new(aov.normal.x(), aov.normal.y(), aov.normal.z()),
+            );
+            buffers.albedo.set(column, row, aov.albedo);
+            let id = aov.object_id as f64;
+            buffers.object_id.set(column, row, Color::new(id, id, id));
+        }
+        Some(buffers)
+    } else {
+        None
+    };
+
+    RenderOutput {
+        beauty: film.to_framebuffer(),
+        aovs,
+        sample_counts: screen.iter().map(|pixel| pixel.samples).collect(),
+    }
+}
+
+fn render_tile(
+    settings: &Settings,
+    world: &dyn Hittable,
+    cam: &Camera,
+    x0: usize,
+    y0: usize,
+) -> TileResult {
+    let image_width = settings.image_width();
+    let image_height = settings.image_height;
+    let width = TILE_SIZE.min(image_width - x0);
+    let height = TILE_SIZE.min(image_height - y0);
+
+    let integrator = settings.integrator();
+    let with_aovs = settings.needs_aovs();
+    let mut sampler = settings.sampler();
+
+    let mut film = Film::for_tile(x0, y0, width, height, settings.filter);
+    let mut pixels = vec![PixelResult::default(); width * height];
+
+    for (index, pixel) in pixels.iter_mut().enumerate() {
+        //row 0 is the top of the image
+        let column = x0 + index % width;
+        let row = y0 + index / width;
+
+        let mut aov = AovSample::default();
+        let mut normal_sum = Vec3::default();
+        let mut albedo_sum = Color::default();
+        let mut stats = PixelStats::default();
+
+        for sample in 0.. {
+            let done = match &settings.adaptive {
+                Some(params) => stats.converged(params),
+                None => sample >= settings.samples_per_pixel,
+            };
+            if done {
+                break;
+            }
+
+            sampler.start_sample((column, row), sample);
+            let (jitter_x, jitter_y) = sampler.next_2d();
+            let x = column as f64 + jitter_x;
+            let y = row as f64 + jitter_y;
+
+            //the camera's v runs up the image
+            let u = x / image_width as f64;
+            let v = 1.0 - y / image_height as f64;
+
+            let mut ray = cam.get_ray(u, v, sampler.as_mut());
+            let mut spectral_weight = Color::new(1.0, 1.0, 1.0);
+            if settings.spectral {
+                let wavelength = sample_wavelength(sampler.next_1d());
+                ray = ray.with_wavelength(wavelength);
+                spectral_weight = wavelength_to_rgb_weight(wavelength);
+            }
+            let ray = &ray;
+
+            let mut first_hit = None;
+            let sample_color =
+                &integrator.li(ray, world, sampler.as_mut(), &mut first_hit) * &spectral_weight;
+            film.add_sample(x, y, &sample_color);
+            stats.add(&sample_color);
+
+            if with_aovs {
+                let sample_aov = AovSample::from_hit(first_hit.as_ref(), cam);
+                normal_sum = normal_sum + sample_aov.normal;
+                albedo_sum = albedo_sum + sample_aov.albedo;
+
+                //depth and ids can't be blended across an edge, so keep the first sample's
+                if sample == 0 {
+                    aov = sample_aov;
+                }
+            }
+        }
+
+        let samples = stats.count().max(1) as f64;
+        aov.normal = normal_sum / samples;
+        aov.albedo = &albedo_sum / samples;
+
+        *pixel = PixelResult {
+            aov,
+            samples: stats.count(),
+        };
+    }
+
+    TileResult {
+        x0,
+        y0,
+        width,
+        film,
+        pixels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::{hittable_list::HittableList, material::Material, render::*, sphere::Sphere};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_render_fills_the_aov_passes() {
+        let mut world = HittableList::new();
+        world.add(Sphere::new_hittable(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Material::lambertian(&Color::new(0.2, 0.4, 0.6)),
+        ));
+        //a small sphere in the middle of a square image, with sky in the corners
+        let camera = Camera::new(
+            &Point3::new(0.0, 0.0, 3.0),
+            &Point3::new(0.0, 0.0, 0.0),
+            &Vec3::new(0.0, 1.0, 0.0),
+            45.0,
+            1.0,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        );
+        let settings = Settings {
+            aspect_ratio: 1.0,
+            image_height: 9,
+            samples_per_pixel: 4,
+            aov_dir: Some(PathBuf::from("unused")),
+            ..Settings::default()
+        };
+
+        let output = render(&settings, &world, &camera);
+        assert_eq!(output.sample_counts, vec![4; 81]);
+        let aovs = output.aovs.unwrap();
+        let depth = aovs.depth.get(4, 4).r();
+        assert!(depth > 2.0 && depth < 2.1);
+        assert!(aovs.normal.get(4, 4).b() > 0.95);
+        assert!((aovs.albedo.get(4, 4).g() - 0.4).abs() < 1e-9);
+        assert_eq!(aovs.object_id.get(4, 4).r(), 1.0);
+
+        assert_eq!(aovs.depth.get(0, 0).r(), 0.0);
+        assert_eq!(aovs.albedo.get(8, 8).luminance(), 0.0);
+        assert_eq!(aovs.object_id.get(0, 8).r(), 0.0);
+    }
+}
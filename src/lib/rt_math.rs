@@ -8,3 +8,192 @@ pub fn degrees_to_radians(degrees: f64) -> f64 {
     degrees * PI / 180.0
 }
 
+//real roots of a x^2 + b x + c = 0 in increasing order, avoiding the cancellation of the textbook
+//formula when b^2 is much bigger than 4ac. A single root is returned twice
+#[allow(dead_code)]
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let root = -c / b;
+        return Some((root, root));
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+    let (root_0, root_1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    Some(if root_0 < root_1 {
+        (root_0, root_1)
+    } else {
+        (root_1, root_0)
+    })
+}
+
+//real roots of a t^4 + b t^3 + c t^2 + d t + e, in increasing order
+#[allow(dead_code)]
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    polynomial_roots(&[a, b, c, d, e])
+}
+
+//real roots of a polynomial with its coefficients given highest power first, in increasing order.
+//The closed form solutions for cubics and quartics lose most of their precision in exactly the
+//cases rendering hits (grazing rays, roots far apart), so instead the roots of the derivative split
+//the line into pieces where the polynomial only goes up or down, and each piece with a sign change
+//holds exactly one root, which a Newton iteration kept inside the bracket then converges on.
+//Roots where the polynomial touches zero without crossing it are only found when they land exactly
+//on a turning point
+pub fn polynomial_roots(coefficients: &[f64]) -> Vec<f64> {
+    let first = match coefficients
+        .iter()
+        .position(|coefficient| *coefficient != 0.0)
+    {
+        Some(first) => first,
+        None => return Vec::new(),
+    };
+    let coefficients = &coefficients[first..];
+    let degree = coefficients.len() - 1;
+
+    match degree {
+        0 => return Vec::new(),
+        1 => return vec![-coefficients[1] / coefficients[0]],
+        2 => {
+            return match solve_quadratic(coefficients[0], coefficients[1], coefficients[2]) {
+                Some((root_0, root_1)) if root_0 == root_1 => vec![root_0],
+                Some((root_0, root_1)) => vec![root_0, root_1],
+                None => Vec::new(),
+            }
+        }
+        _ => {}
+    }
+
+    let derivative: Vec<f64> = coefficients[..degree]
+        .iter()
+        .enumerate()
+        .map(|(power, coefficient)| coefficient * (degree - power) as f64)
+        .collect();
+
+    //every root is within this distance of 0 (Cauchy's bound)
+    let bound = 1.0
+        + coefficients[1..]
+            .iter()
+            .map(|coefficient| (coefficient / coefficients[0]).abs())
+            .fold(0.0, f64::max);
+
+    let mut ends = vec![-bound];
+    ends.extend(
+        polynomial_roots(&derivative)
+            .into_iter()
+            .filter(|turning_point| turning_point.abs() < bound),
+    );
+    ends.push(bound);
+
+    let mut roots: Vec<f64> = Vec::new();
+    for pair in ends.windows(2) {
+        let (low, high) = (pair[0], pair[1]);
+        let (value_low, value_high) = (
+            evaluate_polynomial(coefficients, low),
+            evaluate_polynomial(coefficients, high),
+        );
+
+        if value_low == 0.0 {
+            if roots.last() != Some(&low) {
+                roots.push(low);
+            }
+        } else if value_low.signum() != value_high.signum() && value_high != 0.0 {
+            roots.push(bracketed_root(
+                coefficients,
+                &derivative,
+                low,
+                high,
+                value_low,
+            ));
+        }
+    }
+    if evaluate_polynomial(coefficients, bound) == 0.0 {
+        roots.push(bound);
+    }
+
+    roots
+}
+
+fn evaluate_polynomial(coefficients: &[f64], x: f64) -> f64 {
+    coefficients
+        .iter()
+        .fold(0.0, |value, coefficient| value * x + coefficient)
+}
+
+//Newton's method, falling back to bisection whenever a step would leave the bracket
+fn bracketed_root(
+    coefficients: &[f64],
+    derivative: &[f64],
+    mut low: f64,
+    mut high: f64,
+    value_low: f64,
+) -> f64 {
+    let rising = value_low < 0.0;
+    let mut x = 0.5 * (low + high);
+
+    for _ in 0..100 {
+        let value = evaluate_polynomial(coefficients, x);
+        if value == 0.0 {
+            return x;
+        }
+        if (value < 0.0) == rising {
+            low = x;
+        } else {
+            high = x;
+        }
+
+        let slope = evaluate_polynomial(derivative, x);
+        let newton = x - value / slope;
+        let next = if slope != 0.0 && newton > low && newton < high {
+            newton
+        } else {
+            0.5 * (low + high)
+        };
+
+        if (next - x).abs() <= 1e-15 * (1.0 + x.abs()) {
+            return next;
+        }
+        x = next;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::rt_math::*;
+
+    fn assert_roots(roots: &[f64], expected: &[f64]) {
+        assert_eq!(roots.len(), expected.len(), "roots {:?}", roots);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!(
+                (root - expected).abs() < 1e-9 * (1.0 + expected.abs()),
+                "roots {:?}",
+                roots
+            );
+        }
+    }
+
+    #[test]
+    fn test_solve_quartic() {
+        //(t - 1)(t - 2)(t - 3)(t - 4)
+        assert_roots(
+            &solve_quartic(1.0, -10.0, 35.0, -50.0, 24.0),
+            &[1.0, 2.0, 3.0, 4.0],
+        );
+
+        //(t + 5)(t - 1)(t - 1.001)(t - 100) - nearly double roots next to a distant one
+        let roots = polynomial_roots(&[1.0, -97.001, -308.904, 905.405, -500.5]);
+        assert_roots(&roots, &[-5.0, 1.0, 1.001, 100.0]);
+
+        //(t^2 + 1)(t^2 - 4) only has two real roots
+        assert_roots(&solve_quartic(1.0, 0.0, -3.0, 0.0, -4.0), &[-2.0, 2.0]);
+        assert!(solve_quartic(1.0, 0.0, 2.0, 0.0, 1.0).is_empty());
+    }
+}
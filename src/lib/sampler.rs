@@ -0,0 +1,385 @@
+use rand::prelude::*;
+
+//hands out the random numbers of one camera sample, one dimension at a time - pixel jitter, lens
+//position and time are drawn first, then every bounce draws what its material needs. Samplers other
+//than Random spread the values of each dimension evenly over the samples of a pixel
+pub trait Sampler {
+    //restart the dimensions for the given sample of the given pixel
+    fn start_sample(&mut self, pixel: (usize, usize), sample_index: usize);
+
+    //next value in [0,1)
+    fn next_1d(&mut self) -> f64;
+
+    fn next_2d(&mut self) -> (f64, f64) {
+        (self.next_1d(), self.next_1d())
+    }
+}
+
+//independent uniform random numbers
+pub struct RandomSampler {
+    rng: ThreadRng,
+}
+
+//jittered strata, shuffled independently for every dimension so dimensions don't correlate
+pub struct StratifiedSampler {
+    strata_per_axis: usize,
+    pixel_seed: u32,
+    sample_index: usize,
+    dimension: u32,
+    rng: ThreadRng,
+}
+
+//radical inverse in a different prime base per dimension, randomly shifted for every pixel
+pub struct HaltonSampler {
+    pixel_seed: u32,
+    sample_index: usize,
+    dimension: usize,
+    rng: ThreadRng,
+}
+
+//Owen scrambled Sobol points - every pair of dimensions uses the first two Sobol dimensions with its
+//own shuffle and scramble (Burley, "Practical Hash-based Owen Scrambling")
+pub struct SobolSampler {
+    pixel_seed: u32,
+    sample_index: u32,
+    dimension: u32,
+}
+
+//dimensions past the end of the prime table fall back to random numbers
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+//largest f64 below 1.0, so scaled integers never round up to 1
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+impl RandomSampler {
+    pub fn new() -> Self {
+        Self {
+            rng: rand::thread_rng(),
+        }
+    }
+}
+
+impl Default for RandomSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sampler for RandomSampler {
+    fn start_sample(&mut self, _pixel: (usize, usize), _sample_index: usize) {}
+
+    fn next_1d(&mut self) -> f64 {
+        self.rng.gen::<f64>()
+    }
+}
+
+impl StratifiedSampler {
+    //samples_per_pixel is rounded up to a square number of strata
+    pub fn new(samples_per_pixel: usize) -> Self {
+        Self {
+            strata_per_axis: ((samples_per_pixel as f64).sqrt().ceil() as usize).max(1),
+            pixel_seed: 0,
+            sample_index: 0,
+            dimension: 0,
+            rng: rand::thread_rng(),
+        }
+    }
+
+    fn strata(&self) -> usize {
+        self.strata_per_axis * self.strata_per_axis
+    }
+
+    //which stratum this sample lands in for the current dimension
+    fn stratum(&self) -> usize {
+        let seed = hash(self.pixel_seed ^ hash(self.dimension));
+        permute(
+            (self.sample_index % self.strata()) as u32,
+            self.strata() as u32,
+            seed,
+        ) as usize
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, pixel: (usize, usize), sample_index: usize) {
+        self.pixel_seed = pixel_seed(pixel);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f64 {
+        let stratum = self.stratum();
+        self.dimension += 1;
+
+        ((stratum as f64 + self.rng.gen::<f64>()) / self.strata() as f64).min(ONE_MINUS_EPSILON)
+    }
+
+    fn next_2d(&mut self) -> (f64, f64) {
+        let stratum = self.stratum();
+        self.dimension += 1;
+
+        let n = self.strata_per_axis as f64;
+        let x = (stratum % self.strata_per_axis) as f64;
+        let y = (stratum / self.strata_per_axis) as f64;
+        (
+            ((x + self.rng.gen::<f64>()) / n).min(ONE_MINUS_EPSILON),
+            ((y + self.rng.gen::<f64>()) / n).min(ONE_MINUS_EPSILON),
+        )
+    }
+}
+
+impl HaltonSampler {
+    pub fn new() -> Self {
+        Self {
+            pixel_seed: 0,
+            sample_index: 0,
+            dimension: 0,
+            rng: rand::thread_rng(),
+        }
+    }
+}
+
+impl Default for HaltonSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, pixel: (usize, usize), sample_index: usize) {
+        self.pixel_seed = pixel_seed(pixel);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        match PRIMES.get(dimension) {
+            Some(&base) => {
+                //Cranley-Patterson rotation, so neighbouring pixels don't share the same points
+                let shift = to_unit(hash(self.pixel_seed ^ hash(dimension as u32)));
+                let value = radical_inverse(self.sample_index as u64, base) + shift;
+                (value - value.floor()).min(ONE_MINUS_EPSILON)
+            }
+            None => self.rng.gen::<f64>(),
+        }
+    }
+}
+
+impl SobolSampler {
+    pub fn new() -> Self {
+        Self {
+            pixel_seed: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn scrambled(&self, sobol_dimension: usize, group_seed: u32) -> f64 {
+        let index = nested_uniform_scramble(self.sample_index, group_seed);
+        let value = sobol(index, sobol_dimension);
+        to_unit(nested_uniform_scramble(
+            value,
+            hash(group_seed ^ (sobol_dimension as u32 + 1)),
+        ))
+    }
+
+    fn group_seed(&mut self) -> u32 {
+        let seed = hash(self.pixel_seed ^ hash(self.dimension));
+        self.dimension += 1;
+        seed
+    }
+}
+
+impl Default for SobolSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, pixel: (usize, usize), sample_index: usize) {
+        self.pixel_seed = pixel_seed(pixel);
+        self.sample_index = sample_index as u32;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f64 {
+        let seed = self.group_seed();
+        self.scrambled(0, seed)
+    }
+
+    fn next_2d(&mut self) -> (f64, f64) {
+        let seed = self.group_seed();
+        (self.scrambled(0, seed), self.scrambled(1, seed))
+    }
+}
+
+//digits of 'index' in 'base', mirrored around the decimal point
+pub fn radical_inverse(mut index: u64, base: u32) -> f64 {
+    let base = base as u64;
+    let inverse_base = 1.0 / base as f64;
+    let mut reversed = 0u64;
+    let mut inverse_base_n = 1.0;
+
+    while index > 0 {
+        reversed = reversed * base + index % base;
+        inverse_base_n *= inverse_base;
+        index /= base;
+    }
+
+    (reversed as f64 * inverse_base_n).min(ONE_MINUS_EPSILON)
+}
+
+//one of the first two dimensions of the Sobol sequence as 32 fixed point bits
+pub fn sobol(index: u32, dimension: usize) -> u32 {
+    let mut result = 0u32;
+    //dimension 0 is the van der Corput sequence, dimension 1 comes from the primitive polynomial x + 1
+    let mut direction = 1u32 << 31;
+    let mut index = index;
+
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        direction = if dimension == 0 {
+            direction >> 1
+        } else {
+            direction ^ (direction >> 1)
+        };
+        index >>= 1;
+    }
+
+    result
+}
+
+//Laine-Karras style hash that only lets higher bits affect lower ones - with the bits reversed that
+//is exactly a nested uniform (Owen) scramble
+fn nested_uniform_scramble(value: u32, seed: u32) -> u32 {
+    let mut x = value.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x.reverse_bits()
+}
+
+//Kensler's hash based permutation of [0, length) - the same seed always gives the same shuffle
+fn permute(mut i: u32, length: u32, seed: u32) -> u32 {
+    if length <= 1 {
+        return 0;
+    }
+
+    let mut mask = length - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & mask) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & mask) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & mask) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= mask;
+        i ^= i >> 5;
+
+        if i < length {
+            break;
+        }
+    }
+
+    i.wrapping_add(seed) % length
+}
+
+//integer finalizer from MurmurHash3
+fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x85eb_ca6b);
+    x ^= x >> 13;
+    x = x.wrapping_mul(0xc2b2_ae35);
+    x ^= x >> 16;
+    x
+}
+
+fn pixel_seed(pixel: (usize, usize)) -> u32 {
+    hash(hash(pixel.0 as u32) ^ (pixel.1 as u32).wrapping_mul(0x9e37_79b9))
+}
+
+fn to_unit(bits: u32) -> f64 {
+    (bits as f64 / 4_294_967_296.0).min(ONE_MINUS_EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::sampler::*;
+
+    #[test]
+    fn test_radical_inverse() {
+        assert_eq!(radical_inverse(1, 2), 0.5);
+        assert_eq!(radical_inverse(6, 2), 0.375);
+        assert!((radical_inverse(5, 3) - 7.0 / 9.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_sobol_first_points() {
+        let points: Vec<(f64, f64)> = (0..4)
+            .map(|i| (to_unit(sobol(i, 0)), to_unit(sobol(i, 1))))
+            .collect();
+        assert_eq!(
+            points,
+            vec![(0.0, 0.0), (0.5, 0.5), (0.25, 0.75), (0.75, 0.25)]
+        );
+    }
+
+    #[test]
+    fn test_permute_is_a_permutation() {
+        let mut seen: Vec<u32> = (0..37).map(|i| permute(i, 37, 0x1234_5678)).collect();
+        seen.sort_unstable();
+        assert_eq!(seen, (0..37).collect::<Vec<u32>>());
+    }
+
+    //every one of the n x n strata of the first two dimensions gets exactly one of n x n samples
+    fn covers_every_stratum(sampler: &mut dyn Sampler, strata_per_axis: usize) -> bool {
+        let samples = strata_per_axis * strata_per_axis;
+        let mut hits = vec![0; samples];
+        for index in 0..samples {
+            sampler.start_sample((3, 7), index);
+            let (u, v) = sampler.next_2d();
+            assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+            let cell = (v * strata_per_axis as f64) as usize * strata_per_axis
+                + (u * strata_per_axis as f64) as usize;
+            hits[cell] += 1;
+        }
+        hits.iter().all(|&count| count == 1)
+    }
+
+    #[test]
+    fn test_stratified_covers_every_stratum() {
+        assert!(covers_every_stratum(&mut StratifiedSampler::new(16), 4));
+    }
+
+    #[test]
+    fn test_sobol_covers_every_stratum() {
+        assert!(covers_every_stratum(&mut SobolSampler::new(), 4));
+    }
+}
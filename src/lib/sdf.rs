@@ -0,0 +1,462 @@
+use crate::lib::{aabb::*, hittable::*, material::Material, onb::Onb, ray::Ray, vec3::*};
+use std::sync::Arc;
+
+//distance from a point to the nearest surface, negative inside. It only has to be a lower bound
+//for the ray marching to be safe, but an exact distance takes the fewest steps
+pub trait SignedDistance: Sync + Send {
+    fn distance(&self, point: &Point3) -> f64;
+
+    //a box around everything where the distance is negative, None if it's unbounded or unknown
+    fn bounds(&self) -> Option<AABB> {
+        None
+    }
+}
+
+//any function of the point will do
+impl<F: Fn(&Point3) -> f64 + Sync + Send> SignedDistance for F {
+    fn distance(&self, point: &Point3) -> f64 {
+        self(point)
+    }
+}
+
+//the built-in shapes and ways of combining distance functions
+pub enum Sdf {
+    Sphere {
+        center: Point3,
+        radius: f64,
+    },
+    //'rounding' takes that much off the corners without changing the outer size
+    Box {
+        center: Point3,
+        half_size: Vec3,
+        rounding: f64,
+    },
+    //around the y axis
+    Torus {
+        center: Point3,
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    Union(Arc<dyn SignedDistance>, Arc<dyn SignedDistance>),
+    Intersection(Arc<dyn SignedDistance>, Arc<dyn SignedDistance>),
+    //the first shape with the second cut out of it
+    Difference(Arc<dyn SignedDistance>, Arc<dyn SignedDistance>),
+    //union with the seam filled in over roughly 'blend' distance
+    SmoothUnion {
+        a: Arc<dyn SignedDistance>,
+        b: Arc<dyn SignedDistance>,
+        blend: f64,
+    },
+}
+
+#[allow(dead_code)]
+impl Sdf {
+    pub fn sphere(center: Point3, radius: f64) -> Arc<dyn SignedDistance> {
+        Arc::new(Sdf::Sphere { center, radius })
+    }
+
+    pub fn cuboid(center: Point3, half_size: Vec3, rounding: f64) -> Arc<dyn SignedDistance> {
+        Arc::new(Sdf::Box {
+            center,
+            half_size,
+            rounding,
+        })
+    }
+
+    pub fn torus(center: Point3, major_radius: f64, minor_radius: f64) -> Arc<dyn SignedDistance> {
+        Arc::new(Sdf::Torus {
+            center,
+            major_radius,
+            minor_radius,
+        })
+    }
+
+    pub fn union(
+        a: Arc<dyn SignedDistance>,
+        b: Arc<dyn SignedDistance>,
+    ) -> Arc<dyn SignedDistance> {
+        Arc::new(Sdf::Union(a, b))
+    }
+
+    pub fn intersection(
+        a: Arc<dyn SignedDistance>,
+        b: Arc<dyn SignedDistance>,
+    ) -> Arc<dyn SignedDistance> {
+        Arc::new(Sdf::Intersection(a, b))
+    }
+
+    pub fn difference(
+        a: Arc<dyn SignedDistance>,
+        b: Arc<dyn SignedDistance>,
+    ) -> Arc<dyn SignedDistance> {
+        Arc::new(Sdf::Difference(a, b))
+    }
+
+    pub fn smooth_union(
+        a: Arc<dyn SignedDistance>,
+        b: Arc<dyn SignedDistance>,
+        blend: f64,
+    ) -> Arc<dyn SignedDistance> {
+        Arc::new(Sdf::SmoothUnion { a, b, blend })
+    }
+}
+
+impl SignedDistance for Sdf {
+    fn distance(&self, point: &Point3) -> f64 {
+        match self {
+            Sdf::Sphere { center, radius } => (*point - *center).length() - radius,
+            Sdf::Box {
+                center,
+                half_size,
+                rounding,
+            } => {
+                let offset = *point - *center;
+                let q = Vec3::new(
+                    offset.x().abs() - half_size.x() + rounding,
+                    offset.y().abs() - half_size.y() + rounding,
+                    offset.z().abs() - half_size.z() + rounding,
+                );
+                let outside = Vec3::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0));
+                let inside = q.x().max(q.y()).max(q.z()).min(0.0);
+                outside.length() + inside - rounding
+            }
+            Sdf::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let offset = *point - *center;
+                let ring =
+                    (offset.x() * offset.x() + offset.z() * offset.z()).sqrt() - major_radius;
+                (ring * ring + offset.y() * offset.y()).sqrt() - minor_radius
+            }
+            Sdf::Union(a, b) => a.distance(point).min(b.distance(point)),
+            Sdf::Intersection(a, b) => a.distance(point).max(b.distance(point)),
+            Sdf::Difference(a, b) => a.distance(point).max(-b.distance(point)),
+            Sdf::SmoothUnion { a, b, blend } => {
+                //polynomial smooth minimum (Quilez)
+                let (a, b) = (a.distance(point), b.distance(point));
+                let h = (0.5 + 0.5 * (b - a) / blend).clamp(0.0, 1.0);
+                b + (a - b) * h - blend * h * (1.0 - h)
+            }
+        }
+    }
+
+    fn bounds(&self) -> Option<AABB> {
+        let around = |center: &Point3, half_size: Vec3| {
+            Some(AABB::new(&(*center - half_size), &(*center + half_size)))
+        };
+
+        match self {
+            Sdf::Sphere { center, radius } => around(center, Vec3::new(*radius, *radius, *radius)),
+            Sdf::Box {
+                center, half_size, ..
+            } => around(center, *half_size),
+            Sdf::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let outer = major_radius + minor_radius;
+                around(center, Vec3::new(outer, *minor_radius, outer))
+            }
+            Sdf::Union(a, b) => Some(AABB::surrounding_box(&a.bounds()?, &b.bounds()?)),
+            //either box would do, but the overlap is tighter
+            Sdf::Intersection(a, b) => match (a.bounds(), b.bounds()) {
+                (Some(a), Some(b)) => Some(AABB::overlapping_box(&a, &b)),
+                (Some(bounds), None) | (None, Some(bounds)) => Some(bounds),
+                (None, None) => None,
+            },
+            Sdf::Difference(a, _) => a.bounds(),
+            //the blend swells the surface by at most a quarter of its width
+            Sdf::SmoothUnion { a, b, blend } => {
+                let bounds = AABB::surrounding_box(&a.bounds()?, &b.bounds()?);
+                let swell = Vec3::new(blend / 4.0, blend / 4.0, blend / 4.0);
+                Some(AABB::new(&(bounds.min() - swell), &(bounds.max() + swell)))
+            }
+        }
+    }
+}
+
+//renders a signed distance function by sphere tracing (Hart, "Sphere Tracing") - each step moves
+//the ray forward by the distance to the nearest surface, which can't skip past it
+pub struct SdfObject {
+    sdf: Arc<dyn SignedDistance>,
+    bounds: Option<AABB>,
+    material: Material,
+}
+
+//close enough to the surface to call it a hit
+const HIT_DISTANCE: f64 = 1e-5;
+//offset used for the finite difference normals
+const NORMAL_EPSILON: f64 = 1e-5;
+const MAX_STEPS: usize = 512;
+//how far rays march when there's no box to stop them
+const MAX_DISTANCE: f64 = 1e4;
+
+#[allow(dead_code)]
+impl SdfObject {
+    pub fn new(sdf: Arc<dyn SignedDistance>, material: Material) -> Self {
+        Self {
+            bounds: sdf.bounds(),
+            sdf,
+            material,
+        }
+    }
+
+    pub fn new_hittable(sdf: Arc<dyn SignedDistance>, material: Material) -> Arc<dyn Hittable> {
+        Arc::new(SdfObject::new(sdf, material))
+    }
+
+    //for functions that don't know their own bounds
+    pub fn with_bounds(self, bounds: &AABB) -> Self {
+        Self {
+            bounds: Some(*bounds),
+            ..self
+        }
+    }
+
+    //padded so the marching can reach surfaces lying on the box
+    fn padded_bounds(&self) -> Option<AABB> {
+        let padding = Vec3::new(1e-3, 1e-3, 1e-3);
+        self.bounds
+            .map(|bounds| AABB::new(&(bounds.min() - padding), &(bounds.max() + padding)))
+    }
+
+    //the stretch of the ray worth marching, in t
+    fn march_range(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        match self.padded_bounds() {
+            Some(bounds) => bounds.intersect(ray, t_min, t_max),
+            None => Some((t_min, t_max.min(MAX_DISTANCE))),
+        }
+    }
+
+    fn record(&self, ray: &Ray, t: f64, hit_rec: &mut HitRecord) {
+        let point = ray.at(t);
+        let outward_normal = self.normal(&point);
+        //no natural surface coordinates, but a tangent frame still lets bump maps work
+        let frame = Onb::from_normal(&outward_normal);
+
+        hit_rec.set_t(t);
+        hit_rec.set_p(point);
+        hit_rec.set_face_normal(ray, &outward_normal);
+        hit_rec.set_uv(0.0, 0.0);
+        hit_rec.set_tangents(&frame.u(), &frame.v());
+        hit_rec.set_material(self.material);
+    }
+
+    //gradient of the distance by central differences on a tetrahedron, which takes four
+    //evaluations instead of six
+    fn normal(&self, point: &Point3) -> Vec3 {
+        let offsets = [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ];
+        let gradient = offsets.iter().fold(Vec3::default(), |sum, offset| {
+            sum + self.sdf.distance(&(*point + NORMAL_EPSILON * *offset)) * *offset
+        });
+
+        if gradient.length_squared() > 0.0 {
+            gradient.unit_vector()
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        }
+    }
+}
+
+impl Hittable for SdfObject {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
+        let (start, end) = match self.march_range(ray, t_min, t_max) {
+            Some(range) => range,
+            None => return false,
+        };
+
+        //march in units of distance rather than of t
+        let speed = ray.direction().length();
+        let (start, end) = (start * speed, end * speed);
+        let direction = ray.direction() / speed;
+        let at = |distance: f64| ray.origin() + distance * direction;
+
+        //a ray that starts on the surface (a search restarted just past a hit) goes on from the
+        //far side of it, or it would stop straight away
+        let mut travelled = start;
+        if self.sdf.distance(&at(start)).abs() < HIT_DISTANCE {
+            travelled += 2.0 * HIT_DISTANCE;
+        }
+
+        //rays that start inside (refracted ones) march out to where the distance changes sign
+        let side = if self.sdf.distance(&at(travelled)) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+
+        let mut hit = false;
+        for _ in 0..MAX_STEPS {
+            if travelled > end {
+                break;
+            }
+            let distance = side * self.sdf.distance(&at(travelled));
+            if distance < HIT_DISTANCE {
+                hit = true;
+                break;
+            }
+            travelled += distance;
+        }
+        if !hit {
+            return false;
+        }
+
+        let t = travelled / speed;
+        if t <= t_min || t >= t_max {
+            return false;
+        }
+
+        self.record(ray, t, hit_rec);
+        true
+    }
+
+    //the default walks from hit to hit, but a march starting on the surface stops straight away
+    //and never finds the way out. This one marches once, stepping through the surface at each
+    //crossing and going on with the distance negated while inside
+    fn hit_intervals(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        intervals: &mut Vec<(HitRecord, HitRecord)>,
+    ) -> bool {
+        let (start, end) = match self.march_range(ray, t_min, t_max) {
+            Some(range) => range,
+            None => return false,
+        };
+        let clipped = |t: f64| {
+            let mut hit_rec = HitRecord::new_invalid();
+            hit_rec.set_t(t);
+            hit_rec
+        };
+
+        let speed = ray.direction().length();
+        let (start, end) = (start * speed, end * speed);
+        let direction = ray.direction() / speed;
+        let at = |distance: f64| ray.origin() + distance * direction;
+
+        let found = intervals.len();
+        let mut inside = self.sdf.distance(&at(start)) < 0.0;
+        let mut entry = if inside { Some(clipped(t_min)) } else { None };
+        let mut travelled = start;
+        for _ in 0..MAX_STEPS {
+            if travelled > end {
+                break;
+            }
+            let distance = self.sdf.distance(&at(travelled));
+            let distance = if inside { -distance } else { distance };
+            if distance >= HIT_DISTANCE {
+                travelled += distance;
+                continue;
+            }
+
+            //at the surface - a grazing ray can come out on the side it went in
+            let through = travelled + 2.0 * HIT_DISTANCE;
+            if (self.sdf.distance(&at(through)) < 0.0) != inside {
+                let mut hit_rec = HitRecord::new_invalid();
+                self.record(ray, travelled / speed, &mut hit_rec);
+                if inside {
+                    let entry = entry.take().unwrap_or_else(|| clipped(t_min));
+                    intervals.push((entry, hit_rec));
+                } else {
+                    entry = Some(hit_rec);
+                }
+                inside = !inside;
+            }
+            travelled = through;
+        }
+        if let Some(entry) = entry {
+            intervals.push((entry, clipped(t_max)));
+        }
+
+        intervals.len() > found
+    }
+
+    fn bounding_box(&self, _t_0: f64, _t_1: f64, output_box: &mut AABB) -> bool {
+        match self.padded_bounds() {
+            Some(bounds) => {
+                *output_box = bounds;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::sdf::*;
+
+    #[test]
+    fn test_sphere_tracing_matches_analytic_sphere() {
+        let sphere = SdfObject::new(
+            Sdf::sphere(Point3::new(0.0, 0.0, 0.0), 1.0),
+            Material::default(),
+        );
+
+        let hit = trace(
+            &sphere,
+            Point3::new(0.3, 0.2, 5.0),
+            Vec3::new(0.0, 0.0, -2.0),
+        )
+        .unwrap();
+        let expected_z = (1.0f64 - 0.3 * 0.3 - 0.2 * 0.2).sqrt();
+        assert!((hit.t() - (5.0 - expected_z) / 2.0).abs() < 1e-4);
+        assert!((hit.normal() - hit.point()).length() < 1e-3);
+
+        //from inside the ray finds the way out
+        let inside = trace(
+            &sphere,
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!((inside.t() - 1.0).abs() < 1e-4);
+        assert!(!inside.front_face());
+
+        assert!(trace(
+            &sphere,
+            Point3::new(1.5, 0.0, 5.0),
+            Vec3::new(0.0, 0.0, -1.0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_combinators() {
+        let a = Sdf::sphere(Point3::new(-0.75, 0.0, 0.0), 1.0);
+        let b = Sdf::sphere(Point3::new(0.75, 0.0, 0.0), 1.0);
+        let origin = Point3::new(0.0, 0.0, 0.0);
+
+        assert!((Sdf::union(a.clone(), b.clone()).distance(&origin) + 0.25).abs() < 1e-9);
+        assert!((Sdf::intersection(a.clone(), b.clone()).distance(&origin) + 0.25).abs() < 1e-9);
+        assert!((Sdf::difference(a.clone(), b.clone()).distance(&origin) - 0.25).abs() < 1e-9);
+
+        //the blend fills in the waist between the two spheres
+        let waist = Point3::new(0.0, 0.7, 0.0);
+        let smooth = Sdf::smooth_union(a.clone(), b.clone(), 0.5);
+        assert!(smooth.distance(&waist) < Sdf::union(a, b).distance(&waist));
+
+        //plain functions work too, given bounds
+        let ball = |point: &Point3| point.length() - 0.5;
+        let object = SdfObject::new(Arc::new(ball), Material::default()).with_bounds(&AABB::new(
+            &Point3::new(-1.0, -1.0, -1.0),
+            &Point3::new(1.0, 1.0, 1.0),
+        ));
+        let hit = trace(
+            &object,
+            Point3::new(0.0, 3.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert!((hit.t() - 2.5).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,355 @@
+use crate::lib::{
+    adaptive::AdaptiveParams, denoise::DenoiseParams, filter::Filter, integrator::*, sampler::*,
+    tonemap::ToneMapping,
+};
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+//which integrator the renderer uses to shade each camera ray
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IntegratorKind {
+    PathTracer,
+    Normals,
+    Albedo,
+    Depth,
+    AmbientOcclusion,
+    DirectLighting,
+}
+
+//where the random numbers of each camera sample come from
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SamplerKind {
+    Random,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+pub struct Settings {
+    pub aspect_ratio: f64,
+    pub image_height: usize,
+    pub samples_per_pixel: usize,
+    pub max_depth: u16,
+    pub integrator: IntegratorKind,
+    pub ao_samples: usize, //occlusion probes per camera sample for the ao integrator
+    pub ao_distance: f64,  //how far away geometry still occludes
+    pub depth_max: f64,    //distance shown as black by the depth integrator
+    pub sampler: SamplerKind,
+    pub filter: Filter,
+    pub tone_mapping: ToneMapping,
+    pub spectral: bool, //trace a single wavelength per path so glass can disperse light
+    pub aov_dir: Option<PathBuf>, //where the AOV passes are written, if at all
+    pub denoise: Option<DenoiseParams>,
+    pub raw_output: Option<PathBuf>, //where to keep the image from before denoising
+    pub adaptive: Option<AdaptiveParams>, //replaces samples_per_pixel when set
+    pub heatmap: Option<PathBuf>,    //where to write the samples taken per pixel
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            aspect_ratio: 16.0 / 9.0,
+            image_height: 1080,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            integrator: IntegratorKind::PathTracer,
+            ao_samples: 16,
+            ao_distance: 2.0,
+            depth_max: 30.0,
+            sampler: SamplerKind::Random,
+            filter: Filter::default(),
+            tone_mapping: ToneMapping::default(),
+            spectral: false,
+            aov_dir: None,
+            denoise: None,
+            raw_output: None,
+            adaptive: None,
+            heatmap: None,
+        }
+    }
+}
+
+impl Settings {
+    //start from the defaults and override them with '--name value' pairs
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut settings = Settings::default();
+        //the adaptive options can come before --adaptive and the sample limits default to --spp,
+        //so they're only settled once everything is read
+        let mut min_samples = None;
+        let mut max_samples = None;
+        let mut threshold = None;
+        //and the radius goes on whichever filter is picked, before or after it
+        let mut filter_radius = None;
+
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for {}", flag))?;
+
+            match flag.as_str() {
+                "--height" => settings.image_height = parse_value(&flag, &value)?,
+                "--spp" => settings.samples_per_pixel = parse_value(&flag, &value)?,
+                "--max-depth" => settings.max_depth = parse_value(&flag, &value)?,
+                "--integrator" => settings.integrator = parse_value(&flag, &value)?,
+                "--ao-samples" => settings.ao_samples = parse_value(&flag, &value)?,
+                "--ao-distance" => settings.ao_distance = parse_value(&flag, &value)?,
+                "--depth-max" => settings.depth_max = parse_value(&flag, &value)?,
+                "--sampler" => settings.sampler = parse_value(&flag, &value)?,
+                "--filter" => settings.filter = parse_value(&flag, &value)?,
+                "--filter-radius" => filter_radius = Some(parse_value(&flag, &value)?),
+                "--exposure" => settings.tone_mapping.exposure = parse_value(&flag, &value)?,
+                "--tone-mapper" => settings.tone_mapping.tone_mapper = parse_value(&flag, &value)?,
+                "--dither" => settings.tone_mapping.dither = parse_value(&flag, &value)?,
+                "--spectral" => settings.spectral = parse_value(&flag, &value)?,
+                "--aov-dir" => settings.aov_dir = Some(PathBuf::from(value)),
+                "--denoise" => {
+                    settings.denoise = if parse_value(&flag, &value)? {
+                        Some(DenoiseParams::default())
+                    } else {
+                        None
+                    }
+                }
+                "--raw-output" => settings.raw_output = Some(PathBuf::from(value)),
+                "--adaptive" => {
+                    settings.adaptive = if parse_value(&flag, &value)? {
+                        Some(AdaptiveParams::default())
+                    } else {
+                        None
+                    }
+                }
+                "--min-spp" => min_samples = Some(parse_value(&flag, &value)?),
+                "--max-spp" => max_samples = Some(parse_value(&flag, &value)?),
+                "--noise-threshold" => threshold = Some(parse_value(&flag, &value)?),
+                "--heatmap" => settings.heatmap = Some(PathBuf::from(value)),
+                _ => return Err(format!("unknown option {}", flag)),
+            }
+        }
+
+        if let Some(radius) = filter_radius {
+            settings.filter = settings.filter.with_radius(radius);
+        }
+
+        if settings.samples_per_pixel == 0 {
+            return Err("--spp needs at least 1 sample".to_string());
+        }
+
+        //the most samples defaults to --spp, and the fewest to no more than that
+        let samples_per_pixel = settings.samples_per_pixel;
+        if let Some(params) = settings.adaptive.as_mut() {
+            params.max_samples = max_samples.unwrap_or(samples_per_pixel);
+            if params.max_samples == 0 {
+                return Err("--max-spp needs at least 1 sample".to_string());
+            }
+            params.min_samples = min_samples.unwrap_or_else(|| {
+                AdaptiveParams::default()
+                    .min_samples
+                    .min(params.max_samples)
+            });
+            if params.min_samples > params.max_samples {
+                return Err(format!(
+                    "--min-spp {} is more than the {} samples allowed at most",
+                    params.min_samples, params.max_samples
+                ));
+            }
+            if let Some(threshold) = threshold {
+                params.threshold = threshold;
+            }
+        } else if min_samples.is_some() || max_samples.is_some() || threshold.is_some() {
+            return Err("adaptive sampling options need --adaptive true".to_string());
+        }
+
+        Ok(settings)
+    }
+
+    //the AOVs are also needed to guide the denoiser
+    pub fn needs_aovs(&self) -> bool {
+        self.aov_dir.is_some() || self.denoise.is_some()
+    }
+
+    pub fn image_width(&self) -> usize {
+        (self.image_height as f64 * self.aspect_ratio) as usize
+    }
+
+    //samplers keep per-sample state, so every thread needs its own
+    pub fn sampler(&self) -> Box<dyn Sampler> {
+        let samples_per_pixel = self
+            .adaptive
+            .map_or(self.samples_per_pixel, |params| params.max_samples);
+
+        match self.sampler {
+            SamplerKind::Random => Box::new(RandomSampler::new()),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel)),
+            SamplerKind::Halton => Box::new(HaltonSampler::new()),
+            SamplerKind::Sobol => Box::new(SobolSampler::new()),
+        }
+    }
+
+    pub fn integrator(&self) -> Box<dyn Integrator> {
+        match self.integrator {
+            IntegratorKind::PathTracer => Box::new(PathTracer::new(self.max_depth)),
+            IntegratorKind::Normals => Box::new(DebugIntegrator::new(DebugChannel::Normals, 0.0)),
+            IntegratorKind::Albedo => Box::new(DebugIntegrator::new(DebugChannel::Albedo, 0.0)),
+            IntegratorKind::Depth => {
+                Box::new(DebugIntegrator::new(DebugChannel::Depth, self.depth_max))
+            }
+            IntegratorKind::AmbientOcclusion => {
+                Box::new(AmbientOcclusion::new(self.ao_samples, self.ao_distance))
+            }
+            IntegratorKind::DirectLighting => Box::new(DirectLighting),
+        }
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+impl FromStr for IntegratorKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "path" => Ok(IntegratorKind::PathTracer),
+            "normals" => Ok(IntegratorKind::Normals),
+            "albedo" => Ok(IntegratorKind::Albedo),
+            "depth" => Ok(IntegratorKind::Depth),
+            "ao" => Ok(IntegratorKind::AmbientOcclusion),
+            "direct" => Ok(IntegratorKind::DirectLighting),
+            _ => Err(format!("unknown integrator {}", name)),
+        }
+    }
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "random" => Ok(SamplerKind::Random),
+            "stratified" => Ok(SamplerKind::Stratified),
+            "halton" => Ok(SamplerKind::Halton),
+            "sobol" => Ok(SamplerKind::Sobol),
+            _ => Err(format!("unknown sampler {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::settings::*;
+
+    fn parse(args: &[&str]) -> Result<Settings, String> {
+        Settings::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_from_args() {
+        let settings = parse(&[
+            "--height",
+            "240",
+            "--spp",
+            "8",
+            "--integrator",
+            "ao",
+            "--ao-samples",
+            "4",
+            "--ao-distance",
+            "0.5",
+            "--depth-max",
+            "12",
+            "--sampler",
+            "sobol",
+            "--spectral",
+            "true",
+        ])
+        .unwrap();
+        assert_eq!(settings.image_height, 240);
+        assert_eq!(settings.samples_per_pixel, 8);
+        assert_eq!(settings.integrator, IntegratorKind::AmbientOcclusion);
+        assert_eq!(settings.ao_samples, 4);
+        assert_eq!(settings.ao_distance, 0.5);
+        assert_eq!(settings.depth_max, 12.0);
+        assert_eq!(settings.sampler, SamplerKind::Sobol);
+        assert!(settings.spectral);
+        assert!(!settings.needs_aovs());
+
+        //the filter radius applies to the filter whichever comes first
+        for args in [
+            ["--filter-radius", "2", "--filter", "gaussian"],
+            ["--filter", "gaussian", "--filter-radius", "2"],
+        ]
+        .iter()
+        {
+            let settings = parse(args).unwrap();
+            assert_eq!(
+                settings.filter,
+                "gaussian".parse::<Filter>().unwrap().with_radius(2.0)
+            );
+        }
+
+        //nothing given keeps the defaults
+        let settings = parse(&[]).unwrap();
+        assert_eq!(settings.image_height, 1080);
+        assert_eq!(settings.integrator, IntegratorKind::PathTracer);
+    }
+
+    #[test]
+    fn test_from_args_errors() {
+        assert_eq!(
+            parse(&["--colour", "red"]).err().unwrap(),
+            "unknown option --colour"
+        );
+        assert_eq!(
+            parse(&["--spp", "lots"]).err().unwrap(),
+            "invalid value 'lots' for --spp"
+        );
+        assert_eq!(
+            parse(&["--height"]).err().unwrap(),
+            "missing value for --height"
+        );
+        assert!(parse(&["--integrator", "magic"]).is_err());
+        assert!(parse(&["--ao-samples", "-1"]).is_err());
+    }
+
+    #[test]
+    fn test_adaptive_sample_limits() {
+        //the limit follows --spp wherever it comes on the command line
+        let settings = parse(&["--adaptive", "true", "--spp", "500"]).unwrap();
+        let params = settings.adaptive.unwrap();
+        assert_eq!((params.min_samples, params.max_samples), (16, 500));
+
+        let settings = parse(&["--spp", "500", "--adaptive", "true", "--max-spp", "64"]).unwrap();
+        assert_eq!(settings.adaptive.unwrap().max_samples, 64);
+
+        //fewer samples than the default minimum lowers the minimum with them
+        let settings = parse(&["--adaptive", "true", "--spp", "8"]).unwrap();
+        let params = settings.adaptive.unwrap();
+        assert_eq!((params.min_samples, params.max_samples), (8, 8));
+
+        assert!(parse(&["--adaptive", "true", "--min-spp", "64", "--max-spp", "32"]).is_err());
+        assert!(parse(&["--min-spp", "4"]).is_err());
+        assert!(parse(&["--adaptive", "false"]).unwrap().adaptive.is_none());
+
+        //the adaptive options can come before --adaptive, which doesn't reset them
+        let settings = parse(&[
+            "--noise-threshold",
+            "0.05",
+            "--min-spp",
+            "4",
+            "--max-spp",
+            "32",
+            "--adaptive",
+            "true",
+        ])
+        .unwrap();
+        let params = settings.adaptive.unwrap();
+        assert_eq!((params.min_samples, params.max_samples), (4, 32));
+        assert_eq!(params.threshold, 0.05);
+
+        assert!(parse(&["--spp", "0"]).is_err());
+        assert!(parse(&["--adaptive", "true", "--max-spp", "0"]).is_err());
+    }
+}
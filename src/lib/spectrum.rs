@@ -0,0 +1,149 @@
+use crate::lib::color::*;
+
+//visible range sampled in spectral mode, in nanometers
+pub const WAVELENGTH_MIN: f64 = 380.0;
+pub const WAVELENGTH_MAX: f64 = 780.0;
+
+//wavelength the plain refractive index of a material is quoted at (the sodium D line)
+pub const REFERENCE_WAVELENGTH: f64 = 589.3;
+
+//mean of linear_srgb_from_wavelength over the visible range, per channel - dividing by it makes an
+//equal energy spectrum come out white
+const RGB_NORMALIZATION: (f64, f64, f64) = (0.320_906_71, 0.253_871_59, 0.242_623_89);
+
+//how the index of refraction changes with wavelength
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum Dispersion {
+    #[default]
+    None,
+    //n(λ) = A + B / λ², with λ in micrometers and A picked so n matches the material's plain index
+    //at the reference wavelength
+    Cauchy {
+        b: f64,
+    },
+    //n²(λ) = 1 + Σ Bᵢλ² / (λ² - Cᵢ), with λ in micrometers - replaces the plain index entirely
+    Sellmeier {
+        b: [f64; 3],
+        c: [f64; 3],
+    },
+}
+
+#[allow(dead_code)]
+impl Dispersion {
+    //Schott N-BK7 crown glass
+    pub fn bk7() -> Self {
+        Dispersion::Sellmeier {
+            b: [1.039_612_12, 0.231_792_344, 1.010_469_45],
+            c: [0.006_000_698_67, 0.020_017_914_4, 103.560_653],
+        }
+    }
+
+    pub fn diamond() -> Self {
+        Dispersion::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.030_625, 0.011_236, 0.0],
+        }
+    }
+
+    //index of refraction at 'wavelength' in nanometers - a wavelength of 0 means the ray carries
+    //no wavelength, so the plain index applies
+    pub fn refractive_index(&self, base_index: f64, wavelength: f64) -> f64 {
+        if wavelength <= 0.0 {
+            return base_index;
+        }
+        let micrometers = wavelength / 1000.0;
+        let l2 = micrometers * micrometers;
+
+        match *self {
+            Dispersion::None => base_index,
+            Dispersion::Cauchy { b } => {
+                let reference = REFERENCE_WAVELENGTH / 1000.0;
+                base_index + b / l2 - b / (reference * reference)
+            }
+            Dispersion::Sellmeier { b, c } => {
+                let n2 = 1.0
+                    + b.iter()
+                        .zip(c.iter())
+                        .map(|(b, c)| b * l2 / (l2 - c))
+                        .sum::<f64>();
+                n2.max(1.0).sqrt()
+            }
+        }
+    }
+}
+
+//CIE 1931 color matching functions, from the multi-lobe fit of Wyman, Sloan and Shirley,
+//"Simple Analytic Approximations to the CIE XYZ Color Matching Functions"
+pub fn cie_xyz(wavelength: f64) -> (f64, f64, f64) {
+    let lobe = |mean: f64, sigma_below: f64, sigma_above: f64| {
+        let sigma = if wavelength < mean {
+            sigma_below
+        } else {
+            sigma_above
+        };
+        let t = (wavelength - mean) / sigma;
+        (-0.5 * t * t).exp()
+    };
+
+    let x = 1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7)
+        - 0.065 * lobe(501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8);
+
+    (x, y, z)
+}
+
+//what a path carrying only 'wavelength' contributes to each linear sRGB channel, scaled so that
+//averaging over uniformly sampled wavelengths gives 1 in every channel. Paths still carry RGB
+//throughput, which is multiplied by this weight at the film
+pub fn wavelength_to_rgb_weight(wavelength: f64) -> Color {
+    let (x, y, z) = cie_xyz(wavelength);
+
+    Color::new(
+        (3.2406 * x - 1.5372 * y - 0.4986 * z) / RGB_NORMALIZATION.0,
+        (-0.9689 * x + 1.8758 * y + 0.0415 * z) / RGB_NORMALIZATION.1,
+        (0.0557 * x - 0.2040 * y + 1.0570 * z) / RGB_NORMALIZATION.2,
+    )
+}
+
+//uniform sample in [0,1) to a wavelength in the visible range
+pub fn sample_wavelength(sample: f64) -> f64 {
+    WAVELENGTH_MIN + sample * (WAVELENGTH_MAX - WAVELENGTH_MIN)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::spectrum::*;
+
+    #[test]
+    fn test_rgb_weights_average_to_white() {
+        let steps = 4000;
+        let mut sum = Color::default();
+        for i in 0..steps {
+            let wavelength = sample_wavelength((i as f64 + 0.5) / steps as f64);
+            sum = sum + wavelength_to_rgb_weight(wavelength);
+        }
+        let mean = &sum / steps as f64;
+        assert!((mean.r() - 1.0).abs() < 1e-3);
+        assert!((mean.g() - 1.0).abs() < 1e-3);
+        assert!((mean.b() - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_sellmeier_bk7_reference_values() {
+        //catalogue values for N-BK7 at the sodium D line and the F and C hydrogen lines
+        let bk7 = Dispersion::bk7();
+        assert!((bk7.refractive_index(1.5, 587.6) - 1.5168).abs() < 1e-4);
+        assert!((bk7.refractive_index(1.5, 486.1) - 1.5224).abs() < 1e-4);
+        assert!((bk7.refractive_index(1.5, 656.3) - 1.5143).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_cauchy_matches_base_index_at_reference() {
+        let cauchy = Dispersion::Cauchy { b: 0.004 };
+        assert!((cauchy.refractive_index(1.5, REFERENCE_WAVELENGTH) - 1.5).abs() < 1e-12);
+        assert!(cauchy.refractive_index(1.5, 450.0) > 1.5);
+        assert_eq!(cauchy.refractive_index(1.5, 0.0), 1.5);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::lib::{aabb::*, hittable::*, material::Material, ray::Ray, vec3::*};
-use std::sync::Arc;
+use std::{f64::consts::PI, sync::Arc};
 
 pub struct Sphere {
     center: Point3,
@@ -55,6 +55,7 @@ impl Hittable for Sphere {
                 hit_rec.set_p(ray.at(hit_rec.t()));
                 let outward_normal = (hit_rec.point() - self.center) / self.radius;
                 hit_rec.set_face_normal(ray, &outward_normal);
+                set_surface_coordinates(hit_rec, &outward_normal, self.radius);
                 hit_rec.set_material(self.material);
 
                 return true;
@@ -66,6 +67,7 @@ impl Hittable for Sphere {
                 hit_rec.set_p(ray.at(hit_rec.t()));
                 let outward_normal = (hit_rec.point() - self.center) / self.radius;
                 hit_rec.set_face_normal(ray, &outward_normal);
+                set_surface_coordinates(hit_rec, &outward_normal, self.radius);
                 hit_rec.set_material(self.material);
 
                 return true;
@@ -83,6 +85,25 @@ impl Hittable for Sphere {
     }
 }
 
+//u goes around the y axis starting from -x, v goes from the bottom (-y) to the top, and the
+//tangents are the derivatives of the point with respect to them
+fn set_surface_coordinates(hit_rec: &mut HitRecord, outward_normal: &Vec3, radius: f64) {
+    let (x, y, z) = (outward_normal.x(), outward_normal.y(), outward_normal.z());
+    let theta = (-y).clamp(-1.0, 1.0).acos();
+    let phi = (-z).atan2(x) + PI;
+    hit_rec.set_uv(phi / (2.0 * PI), theta / PI);
+
+    let dpdu = 2.0 * PI * radius * Vec3::new(z, 0.0, -x);
+    //distance from the y axis - the v tangent is undefined at the poles
+    let ring = (x * x + z * z).sqrt();
+    let dpdv = if ring > 1e-9 {
+        PI * radius * Vec3::new(-y * x / ring, ring, -y * z / ring)
+    } else {
+        PI * radius * Vec3::new(1.0, 0.0, 0.0)
+    };
+    hit_rec.set_tangents(&dpdu, &dpdv);
+}
+
 impl MoveableSphere {
     pub fn new_hittable(
         center_time_start: Point3,
@@ -131,6 +152,7 @@ impl Hittable for MoveableSphere {
                 hit_rec.set_p(ray.at(hit_rec.t()));
                 let outward_normal = (hit_rec.point() - center) / self.radius;
                 hit_rec.set_face_normal(ray, &outward_normal);
+                set_surface_coordinates(hit_rec, &outward_normal, self.radius);
                 hit_rec.set_material(self.material);
 
                 return true;
@@ -142,6 +164,7 @@ impl Hittable for MoveableSphere {
                 hit_rec.set_p(ray.at(hit_rec.t()));
                 let outward_normal = (hit_rec.point() - center) / self.radius;
                 hit_rec.set_face_normal(ray, &outward_normal);
+                set_surface_coordinates(hit_rec, &outward_normal, self.radius);
                 hit_rec.set_material(self.material);
 
                 return true;
@@ -168,3 +191,26 @@ impl Hittable for MoveableSphere {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::sphere::*;
+
+    #[test]
+    fn test_sphere_uv_and_tangents() {
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 2.0, Material::default());
+        let ray = Ray::new(&Point3::new(5.0, 0.0, 0.0), &Vec3::new(-1.0, 0.0, 0.0), 0.0);
+        let mut hit_rec = HitRecord::new_invalid();
+        assert!(sphere.hit(&ray, 0.001, f64::INFINITY, &mut hit_rec));
+
+        //+x is halfway around from -x, on the equator
+        assert!((hit_rec.u() - 0.5).abs() < 1e-9);
+        assert!((hit_rec.v() - 0.5).abs() < 1e-9);
+
+        //the tangents follow the surface and their cross product points outwards
+        let normal = hit_rec.dpdu().cross(&hit_rec.dpdv()).unit_vector();
+        assert!(hit_rec.dpdu().dot(&hit_rec.normal()).abs() < 1e-9);
+        assert!(hit_rec.dpdv().dot(&hit_rec.normal()).abs() < 1e-9);
+        assert!((normal - hit_rec.normal()).length() < 1e-9);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::lib::{mesh::TriangleMesh, vec3::*};
+use std::{collections::HashMap, io};
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+//reads ASCII and binary STL files. STL stores every triangle with its own copies of the corners,
+//so corners at exactly the same place are merged back into shared vertices - that keeps the
+//mesh in one piece for subdivision. The facet normals are left out, they are the same as the
+//triangles' own
+pub fn read_stl(data: &[u8]) -> io::Result<TriangleMesh> {
+    //binary files may start with "solid" too, but their size always matches their triangle count
+    let binary = match data.get(80..84) {
+        Some(count) => {
+            let count = u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize;
+            data.len() == 84 + 50 * count || !data.trim_ascii_start().starts_with(b"solid")
+        }
+        None => false,
+    };
+
+    let corners = if binary {
+        read_binary(data)?
+    } else {
+        read_ascii(data)?
+    };
+
+    let mut positions = Vec::new();
+    let mut indices: HashMap<[u64; 3], usize> = HashMap::new();
+    let mut faces = Vec::new();
+    for triangle in corners.chunks_exact(3) {
+        let mut face = [0; 3];
+        for (slot, corner) in face.iter_mut().zip(triangle) {
+            //adding zero turns -0.0 into 0.0, so both weld to the same vertex
+            let key = [
+                (corner.x() + 0.0).to_bits(),
+                (corner.y() + 0.0).to_bits(),
+                (corner.z() + 0.0).to_bits(),
+            ];
+            *slot = *indices.entry(key).or_insert_with(|| {
+                positions.push(*corner);
+                positions.len() - 1
+            });
+        }
+        faces.push(face);
+    }
+    Ok(TriangleMesh::new(positions, faces))
+}
+
+//80 byte header, triangle count, then 50 bytes per triangle: normal, three corners and two bytes
+//of attributes
+fn read_binary(data: &[u8]) -> io::Result<Vec<Point3>> {
+    let count = data
+        .get(80..84)
+        .map(|count| u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize)
+        .ok_or_else(|| invalid("truncated STL header"))?;
+    let body = data
+        .get(84..84 + 50 * count)
+        .ok_or_else(|| invalid("truncated STL data"))?;
+
+    let float = |bytes: &[u8]| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64;
+    let mut corners = Vec::with_capacity(3 * count);
+    for record in body.chunks_exact(50) {
+        for corner in 0..3 {
+            let start = 12 + 12 * corner;
+            corners.push(Point3::new(
+                float(&record[start..]),
+                float(&record[start + 4..]),
+                float(&record[start + 8..]),
+            ));
+        }
+    }
+    Ok(corners)
+}
+
+//only the 'vertex x y z' lines matter, three to a facet
+fn read_ascii(data: &[u8]) -> io::Result<Vec<Point3>> {
+    let text = String::from_utf8_lossy(data);
+    if !text.trim_start().starts_with("solid") {
+        return Err(invalid("not an STL file"));
+    }
+    if !text.contains("endsolid") {
+        return Err(invalid("truncated STL data"));
+    }
+
+    let mut corners = Vec::new();
+    let mut tokens = text.split_whitespace();
+    while let Some(token) = tokens.next() {
+        if token != "vertex" {
+            continue;
+        }
+        let mut coordinate = || -> io::Result<f64> {
+            tokens
+                .next()
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| invalid("invalid STL vertex"))
+        };
+        corners.push(Point3::new(coordinate()?, coordinate()?, coordinate()?));
+    }
+
+    if corners.len() % 3 != 0 {
+        return Err(invalid("STL facet without three vertices"));
+    }
+    Ok(corners)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::stl::*;
+
+    //two triangles sharing an edge, making the unit square
+    const CORNERS: [[f32; 3]; 6] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+
+    fn check_square(mesh: &TriangleMesh) {
+        assert_eq!(mesh.positions().len(), 4);
+        assert_eq!(mesh.faces(), &[[0, 1, 2], [0, 2, 3]]);
+        assert!((mesh.positions()[3] - Point3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_read_stl() {
+        let mut ascii = String::from("solid square\n");
+        for triangle in CORNERS.chunks(3) {
+            ascii.push_str("  facet normal 0 0 1\n    outer loop\n");
+            for corner in triangle {
+                ascii.push_str(&format!(
+                    "      vertex {:e} {} {}\n",
+                    corner[0], corner[1], corner[2]
+                ));
+            }
+            ascii.push_str("    endloop\n  endfacet\n");
+        }
+        ascii.push_str("endsolid square\n");
+        check_square(&read_stl(ascii.as_bytes()).unwrap());
+
+        //CAD exports mix signed zeros, which mustn't split the shared corner in two
+        let mut signed = ascii.clone();
+        let shared = signed.rfind("vertex 0e0 0 0").unwrap();
+        signed.replace_range(shared..shared + 14, "vertex -0e0 -0 -0");
+        check_square(&read_stl(signed.as_bytes()).unwrap());
+
+        //blank lines before "solid" don't make it binary
+        check_square(&read_stl(format!("\n  {}", ascii).as_bytes()).unwrap());
+
+        //a binary header that happens to start with "solid"
+        let mut binary = b"solid exported from CAD".to_vec();
+        binary.resize(80, 0);
+        binary.extend_from_slice(&2u32.to_le_bytes());
+        for triangle in CORNERS.chunks(3) {
+            for value in [0.0f32, 0.0, 1.0].iter() {
+                binary.extend_from_slice(&value.to_le_bytes());
+            }
+            for value in triangle.iter().flatten() {
+                binary.extend_from_slice(&value.to_le_bytes());
+            }
+            binary.extend_from_slice(&[0, 0]);
+        }
+        check_square(&read_stl(&binary).unwrap());
+
+        assert!(read_stl(&binary[..binary.len() - 10]).is_err());
+        assert!(
+            read_stl(b"solid broken\nfacet normal 0 0 1\nouter loop\nvertex 0 0\nendsolid\n")
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use crate::lib::{color::*, vec3::*};
+
+use std::{fs, io, path::Path, sync::Arc};
+
+//a color that varies over a surface - looked up by the surface coordinates of a hit, or by the
+//point itself for solid textures
+pub trait Texture: Sync + Send {
+    fn value(&self, u: f64, v: f64, point: &Point3) -> Color;
+}
+
+pub struct SolidColor {
+    color: Color,
+}
+
+#[allow(dead_code)]
+impl SolidColor {
+    pub fn new(color: &Color) -> Self {
+        Self { color: *color }
+    }
+
+    pub fn new_texture(color: &Color) -> Arc<dyn Texture> {
+        Arc::new(SolidColor::new(color))
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _point: &Point3) -> Color {
+        self.color
+    }
+}
+
+//an image wrapped over the surface, repeating outside [0,1] - values are kept exactly as stored
+//(no sRGB decoding), which is what normal and height maps want
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+#[allow(dead_code)]
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(pixels.len(), width * height);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        ImageTexture::from_ppm(&fs::read(path)?)
+    }
+
+    //reads binary (P6) and plain (P3) PPM images, 8 or 16 bits per channel
+    pub fn from_ppm(data: &[u8]) -> io::Result<Self> {
+        let invalid =
+            |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+        //the header is four whitespace separated tokens, with '#' comments running to the end of the line
+        let mut position = 0;
+        let mut header = Vec::new();
+        while header.len() < 4 {
+            while position < data.len() && data[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            if position < data.len() && data[position] == b'#' {
+                while position < data.len() && data[position] != b'\n' {
+                    position += 1;
+                }
+                continue;
+            }
+            let start = position;
+            while position < data.len() && !data[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            if start == position {
+                return Err(invalid("truncated PPM header"));
+            }
+            header.push(String::from_utf8_lossy(&data[start..position]).into_owned());
+        }
+
+        let number = |token: &str| {
+            token
+                .parse::<usize>()
+                .map_err(|_| invalid("invalid number in PPM header"))
+        };
+        let width = number(&header[1])?;
+        let height = number(&header[2])?;
+        let max_value = number(&header[3])?;
+        if width == 0 || height == 0 || max_value == 0 || max_value > 65535 {
+            return Err(invalid("unsupported PPM dimensions"));
+        }
+
+        let channels = width * height * 3;
+        let values: Vec<usize> = match header[0].as_str() {
+            "P6" => {
+                //exactly one whitespace byte separates the header from the samples
+                let body = data.get(position + 1..).unwrap_or_default();
+                let bytes = if max_value < 256 { 1 } else { 2 };
+                if body.len() < channels * bytes {
+                    return Err(invalid("truncated PPM data"));
+                }
+                if bytes == 1 {
+                    body[..channels]
+                        .iter()
+                        .map(|&value| value as usize)
+                        .collect()
+                } else {
+                    body.chunks_exact(2)
+                        .take(channels)
+                        .map(|pair| (pair[0] as usize) << 8 | pair[1] as usize)
+                        .collect()
+                }
+            }
+            "P3" => {
+                let values = String::from_utf8_lossy(&data[position..])
+                    .split_whitespace()
+                    .take(channels)
+                    .map(number)
+                    .collect::<io::Result<Vec<usize>>>()?;
+                if values.len() < channels {
+                    return Err(invalid("truncated PPM data"));
+                }
+                values
+            }
+            _ => return Err(invalid("not a PPM image")),
+        };
+
+        let scale = 1.0 / max_value as f64;
+        let pixels = values
+            .chunks_exact(3)
+            .map(|rgb| {
+                Color::new(
+                    rgb[0] as f64 * scale,
+                    rgb[1] as f64 * scale,
+                    rgb[2] as f64 * scale,
+                )
+            })
+            .collect();
+
+        Ok(ImageTexture::new(width, height, pixels))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    //the stored color, row 0 at the top
+    pub fn pixel(&self, column: usize, row: usize) -> Color {
+        self.pixels[row * self.width + column]
+    }
+
+    fn texel(&self, column: isize, row: isize) -> Color {
+        let column = column.rem_euclid(self.width as isize) as usize;
+        let row = row.rem_euclid(self.height as isize) as usize;
+        self.pixels[row * self.width + column]
+    }
+}
+
+impl Texture for ImageTexture {
+    //bilinear filtering, with v = 0 at the bottom of the image
+    fn value(&self, u: f64, v: f64, _point: &Point3) -> Color {
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        let (column, row) = (x.floor() as isize, y.floor() as isize);
+        let (dx, dy) = (x - x.floor(), y - y.floor());
+
+        let top = &self.texel(column, row) * (1.0 - dx) + &self.texel(column + 1, row) * dx;
+        let bottom =
+            &self.texel(column, row + 1) * (1.0 - dx) + &self.texel(column + 1, row + 1) * dx;
+        &top * (1.0 - dy) + &bottom * dy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::texture::*;
+
+    #[test]
+    fn test_image_texture_from_ppm() {
+        let plain = ImageTexture::from_ppm(b"P3\n# comment\n2 1\n255\n255 0 0  0 0 255\n").unwrap();
+        let binary = ImageTexture::from_ppm(b"P6 2 1 255\n\xff\x00\x00\x00\x00\xff").unwrap();
+
+        for image in [plain, binary].iter() {
+            assert_eq!((image.width(), image.height()), (2, 1));
+            //texel centers give back the stored colors
+            let left = image.value(0.25, 0.5, &Point3::default());
+            let right = image.value(0.75, 0.5, &Point3::default());
+            assert!((left.r() - 1.0).abs() < 1e-9 && left.b().abs() < 1e-9);
+            assert!((right.b() - 1.0).abs() < 1e-9 && right.r().abs() < 1e-9);
+        }
+
+        assert!(ImageTexture::from_ppm(b"P6 2 1 255\n\xff").is_err());
+        assert!(ImageTexture::from_ppm(b"P5 1 1 255\n\x00").is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use crate::lib::color::*;
+
+use rand::prelude::*;
+use std::str::FromStr;
+
+//compresses the unbounded scene colors into the [0,1] a display can show
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ToneMapper {
+    Clamp,
+    Reinhard,
+    AcesFilmic,
+}
+
+//turns the linear float framebuffer into 8 bit sRGB
+#[derive(Copy, Clone, Debug)]
+pub struct ToneMapping {
+    pub exposure: f64, //in stops - every +1 doubles the brightness
+    pub tone_mapper: ToneMapper,
+    pub dither: bool, //add up to one 8 bit step of noise to break up banding in gradients
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            tone_mapper: ToneMapper::Clamp,
+            dither: false,
+        }
+    }
+}
+
+impl ToneMapping {
+    //linear display values in [0,1]
+    pub fn apply(&self, color: &Color) -> Color {
+        //negative lobes of the reconstruction filters can leave slightly negative pixels
+        let exposure = 2f64.powf(self.exposure);
+        let exposed = Color::new(
+            (color.r() * exposure).max(0.0),
+            (color.g() * exposure).max(0.0),
+            (color.b() * exposure).max(0.0),
+        );
+
+        let mapped = match self.tone_mapper {
+            ToneMapper::Clamp => exposed,
+            ToneMapper::Reinhard => {
+                //compress the luminance only, so colors keep their hue
+                let luminance = exposed.luminance();
+                if luminance <= 0.0 {
+                    exposed
+                } else {
+                    &exposed * (1.0 / (1.0 + luminance))
+                }
+            }
+            ToneMapper::AcesFilmic => aces_filmic(&exposed),
+        };
+
+        Color::new(
+            mapped.r().clamp(0.0, 1.0),
+            mapped.g().clamp(0.0, 1.0),
+            mapped.b().clamp(0.0, 1.0),
+        )
+    }
+
+    pub fn srgb8(&self, color: &Color, rng: &mut ThreadRng) -> (u8, u8, u8) {
+        let mapped = self.apply(color);
+        let mut quantize = |value: f64| {
+            //triangular noise centered on zero, one step wide either way
+            let noise = if self.dither {
+                rng.gen::<f64>() - rng.gen::<f64>()
+            } else {
+                0.0
+            };
+            (srgb_encode(value) * 255.0 + 0.5 + noise)
+                .floor()
+                .clamp(0.0, 255.0) as u8
+        };
+
+        (
+            quantize(mapped.r()),
+            quantize(mapped.g()),
+            quantize(mapped.b()),
+        )
+    }
+}
+
+//the sRGB transfer function - linear below a small toe, a 2.4 power curve above it
+pub fn srgb_encode(linear: f64) -> f64 {
+    if linear <= 0.003_130_8 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+//Stephen Hill's fit of the ACES reference rendering and output transforms, with the sRGB
+//primaries converted in and out of the ACES working space
+fn aces_filmic(color: &Color) -> Color {
+    let (r, g, b) = (color.r(), color.g(), color.b());
+
+    let a_r = 0.59719 * r + 0.35458 * g + 0.04823 * b;
+    let a_g = 0.07600 * r + 0.90834 * g + 0.01566 * b;
+    let a_b = 0.02840 * r + 0.13383 * g + 0.83777 * b;
+
+    let fit = |v: f64| {
+        (v * (v + 0.024_578_6) - 0.000_090_537) / (v * (0.983_729 * v + 0.432_951) + 0.238_081)
+    };
+    let (f_r, f_g, f_b) = (fit(a_r), fit(a_g), fit(a_b));
+
+    Color::new(
+        1.60475 * f_r - 0.53108 * f_g - 0.07367 * f_b,
+        -0.10208 * f_r + 1.10813 * f_g - 0.00605 * f_b,
+        -0.00327 * f_r - 0.07276 * f_g + 1.07602 * f_b,
+    )
+}
+
+impl FromStr for ToneMapper {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "clamp" => Ok(ToneMapper::Clamp),
+            "reinhard" => Ok(ToneMapper::Reinhard),
+            "aces" => Ok(ToneMapper::AcesFilmic),
+            _ => Err(format!("unknown tone mapper {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::tonemap::*;
+
+    #[test]
+    fn test_srgb_encode() {
+        assert_eq!(srgb_encode(0.0), 0.0);
+        assert!((srgb_encode(1.0) - 1.0).abs() < 1e-12);
+        assert!((srgb_encode(0.002) - 0.02584).abs() < 1e-5);
+        assert!((srgb_encode(0.5) - 0.735_357).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_bright_pixels_saturate_instead_of_overflowing() {
+        let mut rng = rand::thread_rng();
+
+        for &tone_mapper in &[
+            ToneMapper::Clamp,
+            ToneMapper::Reinhard,
+            ToneMapper::AcesFilmic,
+        ] {
+            let tone_mapping = ToneMapping {
+                tone_mapper,
+                ..ToneMapping::default()
+            };
+            let (r, _, _) = tone_mapping.srgb8(&Color::new(1000.0, 0.0, 0.0), &mut rng);
+            let (black, _, _) = tone_mapping.srgb8(&Color::new(-1.0, 0.0, 0.0), &mut rng);
+            assert!(r > 200, "{:?}", tone_mapper);
+            assert_eq!(black, 0, "{:?}", tone_mapper);
+        }
+    }
+
+    #[test]
+    fn test_exposure_doubles_per_stop() {
+        let tone_mapping = ToneMapping {
+            exposure: 1.0,
+            ..ToneMapping::default()
+        };
+        assert_eq!(tone_mapping.apply(&Color::new(0.25, 0.0, 0.0)).r(), 0.5);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::lib::{
+    aabb::*, hittable::*, material::Material, onb::Onb, plane::disk_bounds, ray::Ray,
+    rt_math::solve_quartic, vec3::*,
+};
+use std::{f64::consts::PI, sync::Arc};
+
+//ring shaped tube - the tube of radius 'minor_radius' follows a circle of radius 'major_radius'
+//around 'axis'. u goes around the axis, v around the tube starting from the outside
+pub struct Torus {
+    center: Point3,
+    frame: Onb, //w is the axis
+    major_radius: f64,
+    minor_radius: f64,
+    material: Material,
+}
+
+#[allow(dead_code)]
+impl Torus {
+    pub fn new(
+        center: Point3,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: Material,
+    ) -> Self {
+        Self {
+            center,
+            frame: Onb::from_normal(&axis.unit_vector()),
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+
+    pub fn new_hittable(
+        center: Point3,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: Material,
+    ) -> Arc<dyn Hittable> {
+        Arc::new(Torus::new(
+            center,
+            axis,
+            major_radius,
+            minor_radius,
+            material,
+        ))
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
+        //in the local frame with a unit direction, (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2)
+        //expands to a quartic in t
+        let length = ray.direction().length();
+        let origin = self.frame.world_to_local(&(ray.origin() - self.center));
+        let direction = self.frame.world_to_local(&ray.direction()) / length;
+
+        //far from the torus the coefficients lose the digits the roots depend on, so the quartic
+        //is set up from where the ray enters the torus's box and the roots are moved back after
+        let (major, minor) = (self.major_radius, self.minor_radius);
+        let extent = Vec3::new(major + minor, major + minor, minor);
+        let local_ray = Ray::new(&origin, &direction, ray.time());
+        let offset = match AABB::new(&-extent, &extent).intersect(
+            &local_ray,
+            t_min * length,
+            t_max * length,
+        ) {
+            Some((entry, _)) => entry,
+            None => return false,
+        };
+        let start = origin + offset * direction;
+
+        let four_major_squared = 4.0 * major * major;
+        let f = start.dot(&direction);
+        let e = start.length_squared() + major * major - minor * minor;
+        let planar_direction = direction.x() * direction.x() + direction.y() * direction.y();
+        let planar_cross = start.x() * direction.x() + start.y() * direction.y();
+        let planar_origin = start.x() * start.x() + start.y() * start.y();
+
+        let roots = solve_quartic(
+            1.0,
+            4.0 * f,
+            4.0 * f * f + 2.0 * e - four_major_squared * planar_direction,
+            4.0 * f * e - 2.0 * four_major_squared * planar_cross,
+            e * e - four_major_squared * planar_origin,
+        );
+
+        //back to the parameter of the original ray
+        let t = match roots
+            .into_iter()
+            .map(|root| (root + offset) / length)
+            .find(|t| *t > t_min && *t < t_max)
+        {
+            Some(t) => t,
+            None => return false,
+        };
+
+        let point = origin + (t * length) * direction;
+        let phi = point.y().atan2(point.x());
+        let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+        let around_axis = Vec3::new(phi.cos(), phi.sin(), 0.0);
+
+        //the normal points from the nearest point on the center circle
+        let from_ring = point - major * around_axis;
+        let theta = from_ring.z().atan2(from_ring.dot(&around_axis));
+        let theta = if theta < 0.0 { theta + 2.0 * PI } else { theta };
+        let outward_normal = from_ring.unit_vector();
+
+        let dpdu = 2.0 * PI * Vec3::new(-point.y(), point.x(), 0.0);
+        let dpdv =
+            2.0 * PI * minor * (theta.cos() * Vec3::new(0.0, 0.0, 1.0) - theta.sin() * around_axis);
+
+        hit_rec.set_t(t);
+        hit_rec.set_p(ray.at(t));
+        hit_rec.set_face_normal(ray, &self.frame.local_to_world(&outward_normal));
+        hit_rec.set_uv(phi / (2.0 * PI), theta / (2.0 * PI));
+        hit_rec.set_tangents(
+            &self.frame.local_to_world(&dpdu),
+            &self.frame.local_to_world(&dpdv),
+        );
+        hit_rec.set_material(self.material);
+        true
+    }
+
+    //the center circle's box, grown by the tube radius in every direction
+    fn bounding_box(&self, _t_0: f64, _t_1: f64, output_box: &mut AABB) -> bool {
+        let ring = disk_bounds(&self.center, &self.frame.w(), self.major_radius);
+        let tube = Vec3::new(self.minor_radius, self.minor_radius, self.minor_radius);
+        *output_box = AABB::new(&(ring.min() - tube), &(ring.max() + tube));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::torus::*;
+
+    #[test]
+    fn test_torus_hits() {
+        let torus = Torus::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            2.0,
+            0.5,
+            Material::default(),
+        );
+
+        //in the plane of the ring the outside of the tube is 2.5 from the center
+        let side = trace(
+            &torus,
+            Point3::new(5.0, 0.0, 0.0),
+            Vec3::new(-2.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!((side.t() - 1.25).abs() < 1e-9);
+        assert!((side.normal() - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+        assert!(side.v().abs() < 1e-9 || (side.v() - 1.0).abs() < 1e-9);
+
+        //straight down onto the top of the tube
+        let top = trace(
+            &torus,
+            Point3::new(0.0, 5.0, 2.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert!((top.t() - 4.5).abs() < 1e-9);
+        assert!((top.normal() - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+        assert!((top.v() - 0.25).abs() < 1e-9);
+
+        //from inside the tube, the far wall is hit from behind
+        let inside = trace(&torus, Point3::new(2.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!((inside.t() - 0.5).abs() < 1e-9);
+        assert!(!inside.front_face());
+
+        //through the hole, and grazing just past the outside
+        assert!(trace(
+            &torus,
+            Point3::new(0.0, 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0)
+        )
+        .is_none());
+        assert!(trace(
+            &torus,
+            Point3::new(5.0, 0.51, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0)
+        )
+        .is_none());
+
+        //from far away the hit is as precise as from close by
+        let far = trace(
+            &torus,
+            Point3::new(1e6, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!((far.t() - (1e6 - 2.5)).abs() < 1e-6);
+
+        let mut bounds = AABB::default();
+        assert!(torus.bounding_box(0.0, 1.0, &mut bounds));
+        assert!((bounds.max() - Point3::new(2.5, 0.5, 2.5)).length() < 1e-3);
+    }
+}
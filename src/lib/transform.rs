@@ -0,0 +1,511 @@
+use crate::lib::{aabb::*, hittable::*, ray::Ray, rt_math::degrees_to_radians, vec3::*};
+use std::sync::Arc;
+
+//unit quaternion for rotations - unlike matrices these interpolate without shearing
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quaternion {
+    w: f64,
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Quaternion::identity()
+    }
+}
+
+#[allow(dead_code)]
+impl Quaternion {
+    pub fn identity() -> Self {
+        Self {
+            w: 1.0,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    }
+
+    //counter-clockwise rotation about 'axis', in degrees
+    pub fn from_axis_angle(axis: &Vec3, degrees: f64) -> Self {
+        let half_angle = degrees_to_radians(degrees) / 2.0;
+        let axis = axis.unit_vector() * half_angle.sin();
+        Self {
+            w: half_angle.cos(),
+            x: axis.x(),
+            y: axis.y(),
+            z: axis.z(),
+        }
+    }
+
+    //rotation by 'other' followed by this one
+    pub fn then(&self, other: &Quaternion) -> Self {
+        Self {
+            w: self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            x: self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            y: self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            z: self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+        }
+    }
+
+    pub fn conjugate(&self) -> Self {
+        Self {
+            w: self.w,
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn rotate(&self, vector: &Vec3) -> Vec3 {
+        //v' = v + 2w(q x v) + 2q x (q x v), with q the vector part
+        let q = Vec3::new(self.x, self.y, self.z);
+        let t = 2.0 * q.cross(vector);
+        *vector + self.w * t + q.cross(&t)
+    }
+
+    //angle of the rotation that takes this orientation to 'other', in radians
+    pub fn angle_to(&self, other: &Quaternion) -> f64 {
+        2.0 * self.dot(other).abs().min(1.0).acos()
+    }
+
+    //spherical linear interpolation, along the shorter way round
+    pub fn slerp(&self, other: &Quaternion, t: f64) -> Self {
+        let mut cos_theta = self.dot(other);
+        let mut other = *other;
+        if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            other = Quaternion {
+                w: -other.w,
+                x: -other.x,
+                y: -other.y,
+                z: -other.z,
+            };
+        }
+
+        //nearly the same orientation - the weights below would divide by ~0
+        let (a, b) = if cos_theta > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                ((1.0 - t) * theta).sin() / sin_theta,
+                (t * theta).sin() / sin_theta,
+            )
+        };
+
+        Quaternion {
+            w: a * self.w + b * other.w,
+            x: a * self.x + b * other.x,
+            y: a * self.y + b * other.y,
+            z: a * self.z + b * other.z,
+        }
+        .normalized()
+    }
+
+    fn normalized(&self) -> Self {
+        let length = self.dot(self).sqrt();
+        Self {
+            w: self.w / length,
+            x: self.x / length,
+            y: self.y / length,
+            z: self.z / length,
+        }
+    }
+}
+
+//scale, then rotate, then translate
+#[derive(Copy, Clone, Debug)]
+pub struct Transform {
+    translation: Vec3,
+    rotation: Quaternion,
+    scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::default(),
+            rotation: Quaternion::identity(),
+            scale: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Transform {
+    pub fn new(translation: &Vec3, rotation: &Quaternion, scale: &Vec3) -> Self {
+        Self {
+            translation: *translation,
+            rotation: *rotation,
+            scale: *scale,
+        }
+    }
+
+    pub fn translation(&self) -> Vec3 {
+        self.translation
+    }
+
+    pub fn rotation(&self) -> Quaternion {
+        self.rotation
+    }
+
+    pub fn scale(&self) -> Vec3 {
+        self.scale
+    }
+
+    pub fn with_translation(self, translation: &Vec3) -> Self {
+        Self {
+            translation: *translation,
+            ..self
+        }
+    }
+
+    pub fn with_rotation(self, rotation: &Quaternion) -> Self {
+        Self {
+            rotation: *rotation,
+            ..self
+        }
+    }
+
+    pub fn with_scale(self, scale: &Vec3) -> Self {
+        Self {
+            scale: *scale,
+            ..self
+        }
+    }
+
+    //translation and scale are interpolated linearly, rotation spherically
+    pub fn interpolate(&self, other: &Transform, t: f64) -> Self {
+        Self {
+            translation: (1.0 - t) * self.translation + t * other.translation,
+            rotation: self.rotation.slerp(&other.rotation, t),
+            scale: (1.0 - t) * self.scale + t * other.scale,
+        }
+    }
+
+    pub fn apply_point(&self, point: &Point3) -> Point3 {
+        self.apply_vector(point) + self.translation
+    }
+
+    pub fn apply_vector(&self, vector: &Vec3) -> Vec3 {
+        self.rotation.rotate(&multiply(vector, &self.scale))
+    }
+
+    //normals go through the inverse transpose, which for scale-rotate is rotate(n / scale)
+    pub fn apply_normal(&self, normal: &Vec3) -> Vec3 {
+        self.rotation
+            .rotate(&divide(normal, &self.scale))
+            .unit_vector()
+    }
+
+    pub fn invert_point(&self, point: &Point3) -> Point3 {
+        self.invert_vector(&(*point - self.translation))
+    }
+
+    pub fn invert_vector(&self, vector: &Vec3) -> Vec3 {
+        divide(&self.rotation.conjugate().rotate(vector), &self.scale)
+    }
+}
+
+fn multiply(a: &Vec3, b: &Vec3) -> Vec3 {
+    Vec3::new(a.x() * b.x(), a.y() * b.y(), a.z() * b.z())
+}
+
+fn divide(a: &Vec3, b: &Vec3) -> Vec3 {
+    Vec3::new(a.x() / b.x(), a.y() / b.y(), a.z() / b.z())
+}
+
+//a transform that changes over time - held before the first keyframe and after the last one
+#[derive(Clone, Debug)]
+pub struct AnimatedTransform {
+    keyframes: Vec<(f64, Transform)>, //sorted by time
+}
+
+#[allow(dead_code)]
+impl AnimatedTransform {
+    pub fn new(mut keyframes: Vec<(f64, Transform)>) -> Self {
+        assert!(!keyframes.is_empty(), "an animation needs a keyframe");
+        keyframes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { keyframes }
+    }
+
+    pub fn fixed(transform: &Transform) -> Self {
+        AnimatedTransform::new(vec![(0.0, *transform)])
+    }
+
+    pub fn keyframes(&self) -> &[(f64, Transform)] {
+        &self.keyframes
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        let first = self.keyframes[0];
+        if time <= first.0 {
+            return first.1;
+        }
+
+        for pair in self.keyframes.windows(2) {
+            let ((time_0, transform_0), (time_1, transform_1)) = (pair[0], pair[1]);
+            if time < time_1 {
+                let t = (time - time_0) / (time_1 - time_0);
+                return transform_0.interpolate(&transform_1, t);
+            }
+        }
+
+        self.keyframes[self.keyframes.len() - 1].1
+    }
+}
+
+//places any hittable with a (possibly animated) transform - rays are moved into the object's space
+//at their own time, so everything wrapped this way gets motion blur
+pub struct Transformed {
+    object: Arc<dyn Hittable>,
+    animation: AnimatedTransform,
+}
+
+//transforms sampled over the shutter interval when bounding a moving object
+const BOUNDING_STEPS: usize = 32;
+
+#[allow(dead_code)]
+impl Transformed {
+    pub fn new_hittable(
+        object: Arc<dyn Hittable>,
+        animation: AnimatedTransform,
+    ) -> Arc<dyn Hittable> {
+        Arc::new(Self { object, animation })
+    }
+}
+
+impl Hittable for Transformed {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
+        let transform = self.animation.at(ray.time());
+
+        //the direction isn't renormalized, so t means the same thing in both spaces
+        let object_ray = Ray::new(
+            &transform.invert_point(&ray.origin()),
+            &transform.invert_vector(&ray.direction()),
+            ray.time(),
+        )
+        .with_wavelength(ray.wavelength());
+
+        if !self.object.hit(&object_ray, t_min, t_max, hit_rec) {
+            return false;
+        }
+
+        //the inverse transpose keeps the sign of the ray against the normal, so the side the
+        //object says was hit stays right - it comes from the geometry, not a smoothed normal
+        let outward_normal = transform.apply_normal(&hit_rec.outward_normal());
+        hit_rec.set_p(transform.apply_point(&hit_rec.point()));
+        hit_rec.set_shading_normal(&outward_normal);
+        hit_rec.set_tangents(
+            &transform.apply_vector(&hit_rec.dpdu()),
+            &transform.apply_vector(&hit_rec.dpdv()),
+        );
+        true
+    }
+
+    fn bounding_box(&self, t_0: f64, t_1: f64, output_box: &mut AABB) -> bool {
+        let mut object_box = AABB::default();
+        if !self.object.bounding_box(t_0, t_1, &mut object_box) {
+            return false;
+        }
+
+        //the transform at evenly spaced times plus every keyframe inside the interval
+        let mut times: Vec<f64> = (0..=BOUNDING_STEPS)
+            .map(|step| t_0 + (t_1 - t_0) * step as f64 / BOUNDING_STEPS as f64)
+            .chain(
+                self.animation
+                    .keyframes()
+                    .iter()
+                    .map(|(time, _)| *time)
+                    .filter(|time| *time > t_0 && *time < t_1),
+            )
+            .collect();
+        times.sort_by(f64::total_cmp);
+
+        let (min, max) = (object_box.min(), object_box.max());
+        let corners: Vec<Point3> = (0..8)
+            .map(|corner| {
+                Point3::new(
+                    if corner & 1 == 0 { min.x() } else { max.x() },
+                    if corner & 2 == 0 { min.y() } else { max.y() },
+                    if corner & 4 == 0 { min.z() } else { max.z() },
+                )
+            })
+            .collect();
+
+        let mut bounds: Option<AABB> = None;
+        let mut padding: f64 = 0.0;
+        let mut previous: Option<Transform> = None;
+        for time in times {
+            let transform = self.animation.at(time);
+            for corner in &corners {
+                let point = transform.apply_point(corner);
+                let point_box = AABB::new(&point, &point);
+                bounds = Some(match bounds {
+                    Some(bounds) => AABB::surrounding_box(&bounds, &point_box),
+                    None => point_box,
+                });
+            }
+
+            //between two samples a rotating corner bulges out of the straight line joining them by
+            //at most r * (1 - cos(angle / 2))
+            if let Some(previous) = previous {
+                let angle = previous.rotation().angle_to(&transform.rotation());
+                let radius = corners
+                    .iter()
+                    .map(|corner| transform.apply_vector(corner).length())
+                    .fold(0.0, f64::max);
+                padding = padding.max(radius * (1.0 - (angle / 2.0).cos()));
+            }
+            previous = Some(transform);
+        }
+
+        let bounds = bounds.unwrap();
+        let padding = Vec3::new(padding, padding, padding);
+        *output_box = AABB::new(&(bounds.min() - padding), &(bounds.max() + padding));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::{material::Material, sphere::Sphere, transform::*, triangle::Triangle};
+
+    #[test]
+    fn test_quaternion_rotation_and_slerp() {
+        let quarter_turn = Quaternion::from_axis_angle(&Vec3::new(0.0, 0.0, 1.0), 90.0);
+        let rotated = quarter_turn.rotate(&Vec3::new(1.0, 0.0, 0.0));
+        assert!((rotated - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+
+        let halfway = Quaternion::identity().slerp(&quarter_turn, 0.5);
+        let rotated = halfway.rotate(&Vec3::new(1.0, 0.0, 0.0));
+        assert!((rotated - Vec3::new(1.0, 1.0, 0.0).unit_vector()).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_transform_round_trip() {
+        let transform = Transform::new(
+            &Vec3::new(1.0, 2.0, 3.0),
+            &Quaternion::from_axis_angle(&Vec3::new(1.0, 1.0, 0.0), 40.0),
+            &Vec3::new(2.0, 0.5, 1.5),
+        );
+        let point = Point3::new(-0.3, 0.7, 2.0);
+        let back = transform.invert_point(&transform.apply_point(&point));
+        assert!((back - point).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_keyframed_translation_blurs_hits_and_bounds() {
+        let animation = AnimatedTransform::new(vec![
+            (0.0, Transform::default()),
+            (
+                1.0,
+                Transform::default().with_translation(&Vec3::new(4.0, 0.0, 0.0)),
+            ),
+        ]);
+        let moving = Transformed::new_hittable(
+            Sphere::new_hittable(Point3::new(0.0, 0.0, 0.0), 1.0, Material::default()),
+            animation,
+        );
+
+        //halfway through the shutter the sphere is centered at x = 2
+        let mut hit_rec = HitRecord::new_invalid();
+        let ray = Ray::new(&Point3::new(2.0, 0.0, 5.0), &Vec3::new(0.0, 0.0, -1.0), 0.5);
+        assert!(moving.hit(&ray, 0.001, f64::INFINITY, &mut hit_rec));
+        assert!((hit_rec.point() - Point3::new(2.0, 0.0, 1.0)).length() < 1e-9);
+        assert!((hit_rec.normal() - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+
+        let ray = Ray::new(&Point3::new(2.0, 0.0, 5.0), &Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(!moving.hit(&ray, 0.001, f64::INFINITY, &mut hit_rec));
+
+        let mut bounds = AABB::default();
+        assert!(moving.bounding_box(0.0, 1.0, &mut bounds));
+        assert!((bounds.min().x() + 1.0).abs() < 1e-9);
+        assert!((bounds.max().x() - 5.0).abs() < 1e-9);
+
+        //a keyframe at no time at all sorts last instead of taking the scene down
+        let animation = AnimatedTransform::new(vec![
+            (f64::NAN, Transform::default()),
+            (0.0, Transform::default()),
+        ]);
+        assert_eq!(animation.keyframes()[0].0, 0.0);
+    }
+
+    #[test]
+    fn test_smooth_triangle_keeps_its_side() {
+        //facing +z, with shading normals leaning so far over that a grazing ray from below
+        //looks like it's coming from the front
+        let leaning = Vec3::new(1.0, 0.0, 0.1).unit_vector();
+        let triangle = Triangle::new(
+            Point3::new(-1.0, -1.0, 0.0),
+            Point3::new(1.0, -1.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Material::default(),
+        )
+        .with_normals([leaning, leaning, leaning]);
+        let transform = Transform::default()
+            .with_translation(&Vec3::new(0.0, 0.0, 3.0))
+            .with_rotation(&Quaternion::from_axis_angle(
+                &Vec3::new(0.0, 0.0, 1.0),
+                90.0,
+            ));
+        let turned =
+            Transformed::new_hittable(Arc::new(triangle), AnimatedTransform::fixed(&transform));
+
+        let ray = Ray::new(
+            &Point3::new(0.0, 10.0, 2.5),
+            &Vec3::new(0.0, -1.0, 0.05),
+            0.0,
+        );
+        let mut hit_rec = HitRecord::new_invalid();
+        assert!(turned.hit(&ray, 0.001, f64::INFINITY, &mut hit_rec));
+        assert!((hit_rec.point() - Point3::new(0.0, 0.0, 3.0)).length() < 1e-9);
+        assert!(!hit_rec.front_face());
+        assert!((hit_rec.normal() + transform.apply_normal(&leaning)).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_rotating_bounds_contain_the_object() {
+        let animation = AnimatedTransform::new(vec![
+            (0.0, Transform::default()),
+            (
+                1.0,
+                Transform::default().with_rotation(&Quaternion::from_axis_angle(
+                    &Vec3::new(0.0, 1.0, 0.0),
+                    90.0,
+                )),
+            ),
+        ]);
+        //a small sphere far out on +x swings round to -z
+        let swinging = Transformed::new_hittable(
+            Sphere::new_hittable(Point3::new(3.0, 0.0, 0.0), 0.5, Material::default()),
+            animation.clone(),
+        );
+
+        let mut bounds = AABB::default();
+        assert!(swinging.bounding_box(0.0, 1.0, &mut bounds));
+        for step in 0..=100 {
+            let center = animation
+                .at(step as f64 / 100.0)
+                .apply_point(&Point3::new(3.0, 0.0, 0.0));
+            for axis_extent in [
+                center + Vec3::new(0.5, 0.5, 0.5),
+                center - Vec3::new(0.5, 0.5, 0.5),
+            ]
+            .iter()
+            {
+                assert!(axis_extent.x() <= bounds.max().x() + 1e-9);
+                assert!(axis_extent.z() >= bounds.min().z() - 1e-9);
+                assert!(axis_extent.x() >= bounds.min().x() - 1e-9);
+                assert!(axis_extent.z() <= bounds.max().z() + 1e-9);
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::lib::{aabb::*, hittable::*, material::Material, onb::Onb, ray::Ray, vec3::*};
+use std::sync::Arc;
+
+pub struct Triangle {
+    vertices: [Point3; 3],
+    uvs: [(f64, f64); 3],       //surface coordinates at each vertex
+    normals: Option<[Vec3; 3]>, //for smooth shading, interpolated across the triangle
+    material: Material,
+}
+
+#[allow(dead_code)]
+impl Triangle {
+    pub fn new(p0: Point3, p1: Point3, p2: Point3, material: Material) -> Self {
+        Self {
+            vertices: [p0, p1, p2],
+            uvs: [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)],
+            normals: None,
+            material,
+        }
+    }
+
+    pub fn new_hittable(
+        p0: Point3,
+        p1: Point3,
+        p2: Point3,
+        material: Material,
+    ) -> Arc<dyn Hittable> {
+        Arc::new(Triangle::new(p0, p1, p2, material))
+    }
+
+    pub fn with_uvs(self, uvs: [(f64, f64); 3]) -> Self {
+        Self { uvs, ..self }
+    }
+
+    pub fn with_normals(self, normals: [Vec3; 3]) -> Self {
+        Self {
+            normals: Some(normals),
+            ..self
+        }
+    }
+
+    //the tangents that make the uvs vary linearly over the triangle
+    fn tangents(&self, normal: &Vec3) -> (Vec3, Vec3) {
+        let [p0, p1, p2] = self.vertices;
+        let [uv0, uv1, uv2] = self.uvs;
+        let (du02, dv02) = (uv0.0 - uv2.0, uv0.1 - uv2.1);
+        let (du12, dv12) = (uv1.0 - uv2.0, uv1.1 - uv2.1);
+        let (dp02, dp12) = (p0 - p2, p1 - p2);
+
+        let determinant = du02 * dv12 - dv02 * du12;
+        if determinant.abs() < 1e-12 {
+            //degenerate uvs, any frame around the normal will do
+            let frame = Onb::from_normal(normal);
+            return (frame.u(), frame.v());
+        }
+
+        let inverse = 1.0 / determinant;
+        (
+            inverse * (dv12 * dp02 - dv02 * dp12),
+            inverse * (du02 * dp12 - du12 * dp02),
+        )
+    }
+}
+
+//Möller-Trumbore - the distance along the ray and the barycentric weights of the second and
+//third vertices, if the ray crosses the triangle in range
+pub fn intersect_triangle(
+    ray: &Ray,
+    vertices: &[Point3; 3],
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    let [p0, p1, p2] = *vertices;
+    let edge_1 = p1 - p0;
+    let edge_2 = p2 - p0;
+
+    let p = ray.direction().cross(&edge_2);
+    let determinant = edge_1.dot(&p);
+    //the ray is parallel to the triangle
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+
+    let s = ray.origin() - p0;
+    let b1 = s.dot(&p) * inverse;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let q = s.cross(&edge_1);
+    let b2 = ray.direction().dot(&q) * inverse;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge_2.dot(&q) * inverse;
+    if t <= t_min || t >= t_max {
+        return None;
+    }
+    Some((t, b1, b2))
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_rec: &mut HitRecord) -> bool {
+        let (t, b1, b2) = match intersect_triangle(ray, &self.vertices, t_min, t_max) {
+            Some(hit) => hit,
+            None => return false,
+        };
+
+        let [p0, p1, p2] = self.vertices;
+        let b0 = 1.0 - b1 - b2;
+        let [uv0, uv1, uv2] = self.uvs;
+        let outward_normal = (p1 - p0).cross(&(p2 - p0)).unit_vector();
+        let (dpdu, dpdv) = self.tangents(&outward_normal);
+
+        hit_rec.set_t(t);
+        hit_rec.set_p(ray.at(t));
+        hit_rec.set_face_normal(ray, &outward_normal);
+        if let Some([n0, n1, n2]) = self.normals {
+            hit_rec.set_shading_normal(&(b0 * n0 + b1 * n1 + b2 * n2).unit_vector());
+        }
+        hit_rec.set_uv(
+            b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+            b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+        );
+        hit_rec.set_tangents(&dpdu, &dpdv);
+        hit_rec.set_material(self.material);
+        true
+    }
+
+    fn bounding_box(&self, _t_0: f64, _t_1: f64, output_box: &mut AABB) -> bool {
+        let [p0, p1, p2] = self.vertices;
+        //padded so triangles lying in an axis plane still have a box with some thickness
+        let padding = 1e-4;
+        let min = Point3::new(
+            p0.x().min(p1.x()).min(p2.x()) - padding,
+            p0.y().min(p1.y()).min(p2.y()) - padding,
+            p0.z().min(p1.z()).min(p2.z()) - padding,
+        );
+        let max = Point3::new(
+            p0.x().max(p1.x()).max(p2.x()) + padding,
+            p0.y().max(p1.y()).max(p2.y()) + padding,
+            p0.z().max(p1.z()).max(p2.z()) + padding,
+        );
+        *output_box = AABB::new(&min, &max);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::triangle::*;
+
+    #[test]
+    fn test_triangle_hit() {
+        let triangle = Triangle::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(2.0, 0.0, 0.0),
+            Point3::new(0.0, 2.0, 0.0),
+            Material::default(),
+        )
+        .with_uvs([(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]);
+
+        let mut hit_rec = HitRecord::new_invalid();
+        let ray = Ray::new(&Point3::new(0.5, 0.5, 1.0), &Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(triangle.hit(&ray, 0.001, f64::INFINITY, &mut hit_rec));
+        assert!((hit_rec.t() - 1.0).abs() < 1e-9);
+        assert!(hit_rec.front_face());
+        assert!((hit_rec.u() - 0.25).abs() < 1e-9 && (hit_rec.v() - 0.25).abs() < 1e-9);
+
+        //u runs along x and v along y, two units of space per unit of uv
+        assert!((hit_rec.dpdu() - Vec3::new(2.0, 0.0, 0.0)).length() < 1e-9);
+        assert!((hit_rec.dpdv() - Vec3::new(0.0, 2.0, 0.0)).length() < 1e-9);
+
+        let miss = Ray::new(&Point3::new(1.5, 1.5, 1.0), &Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(!triangle.hit(&miss, 0.001, f64::INFINITY, &mut hit_rec));
+
+        //vertex normals tilt the shading normal, but not which side was hit
+        let smooth = Triangle::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(2.0, 0.0, 0.0),
+            Point3::new(0.0, 2.0, 0.0),
+            Material::default(),
+        )
+        .with_normals([
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(1.0, 0.0, 1.0).unit_vector(),
+            Vec3::new(0.0, 0.0, 1.0),
+        ]);
+        let from_below = Ray::new(&Point3::new(1.0, 0.5, -1.0), &Vec3::new(0.0, 0.0, 1.0), 0.0);
+        assert!(smooth.hit(&from_below, 0.001, f64::INFINITY, &mut hit_rec));
+        assert!(!hit_rec.front_face());
+        assert!(hit_rec.normal().z() < 0.0 && hit_rec.normal().x() < 0.0);
+    }
+}
@@ -85,6 +85,41 @@ impl Vec3 {
         Vec3::new(r * a.cos(), r * a.sin(), z)
     }
 
+    //the sample based versions below map uniform numbers from a Sampler without rejection, so
+    //well spread samples stay well spread
+
+    //concentric mapping of the unit square onto the unit disk (Shirley & Chiu)
+    pub fn in_unit_disk(sample: (f64, f64)) -> Vec3 {
+        let a = 2.0 * sample.0 - 1.0;
+        let b = 2.0 * sample.1 - 1.0;
+
+        if a == 0.0 && b == 0.0 {
+            return Vec3::default();
+        }
+
+        let (r, theta) = if a.abs() > b.abs() {
+            (a, FRAC_PI_4 * (b / a))
+        } else {
+            (b, FRAC_PI_2 - FRAC_PI_4 * (a / b))
+        };
+
+        Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+    }
+
+    //uniformly distributed direction
+    pub fn unit_vector_from_sample(sample: (f64, f64)) -> Vec3 {
+        let z = 1.0 - 2.0 * sample.0; //between -1.0 and 1.0
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * sample.1;
+
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    //uniformly distributed point inside the unit sphere
+    pub fn in_unit_sphere(direction_sample: (f64, f64), radius_sample: f64) -> Vec3 {
+        radius_sample.cbrt() * Vec3::unit_vector_from_sample(direction_sample)
+    }
+
     fn random() -> Self {
         let mut rng = rand::thread_rng();
 
@@ -1,58 +1,23 @@
+#![allow(special_module_name)]
+
 mod lib;
 use lib::{
-    camera::*, color::*, hittable::*, hittable_list::*, material::Material, ray::*, rt_math::*,
-    sphere::*, vec3::*,
+    adaptive::heatmap, bvh::BVH, camera::*, color::*, denoise::denoise, hittable_list::*,
+    material::Material, plane::Plane, render::render, settings::Settings, sphere::*,
+    tonemap::ToneMapping, vec3::*,
 };
 
 use rand::prelude::*;
-use rayon::prelude::*;
+use std::io;
 use std::time;
 
-fn ray_color(ray: &Ray, world: &dyn Hittable, depth: u16) -> Color {
-    let mut hit_rec = HitRecord::new_invalid();
-
-    //if we've exceeded the ray bounce limit, no more light is gathered
-    if depth == 0 {
-        return Color::new(0.0, 0.0, 0.0);
-    }
-
-    if world.hit(ray, 0.001, INFINITY as f64, &mut hit_rec) {
-        let mut scattered = Ray::default();
-        let mut attenuation = Color::default();
-
-        if Material::scatter(
-            &hit_rec.material(),
-            ray,
-            &hit_rec,
-            &mut attenuation,
-            &mut scattered,
-        ) {
-            return &attenuation * &ray_color(&scattered, world, depth - 1); //not real vector multiplication - just scaling by the attenuation values
-        }
-        return Color::new(0.0, 0.0, 0.0);
-    }
-
-    let unit_direction: Vec3 = Vec3::unit_vector(&ray.direction());
-    let t = 0.5 * (unit_direction.y() + 1.0); //blue to white blend
-    let start_value = Color::new(1.0, 1.0, 1.0);
-    let end_value = Color::new(0.5, 0.7, 1.0);
-
-    //linear blend
-    // blendedValue = (1 − t)⋅startValue + t⋅endValue
-    (1.0 - t) * &start_value + t * &end_value
-}
-
-fn render(aspect_ratio: f64, image_height: usize, samples_per_pixel: usize, max_depth: u16) {
-    let image_width = (image_height as f64 * aspect_ratio) as usize;
-
-    println!("P3\n{} {} \n255\n", image_width, image_height);
-
+fn build_scene(settings: &Settings) -> (BVH, Camera) {
     //let r: f64 = (PI / 4.0).cos();
     let mut world: HittableList = HittableList::new();
 
-    world.add(Sphere::new_hittable(
-        Point3::new(0.0, -1000.0, -1.0),
-        1000.0,
+    world.add(Plane::new_hittable(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
         Material::lambertian(&Color::new(0.5, 0.5, 0.5)),
     ));
 
@@ -81,63 +46,23 @@ fn render(aspect_ratio: f64, image_height: usize, samples_per_pixel: usize, max_
     let aperture = 0.1;
     let v_fov = 20.0;
 
+    let (time_0, time_1) = (0.0, 0.1);
     let cam = Camera::new(
         &look_from,
         &look_at,
         &v_up,
         v_fov,
-        aspect_ratio,
+        settings.aspect_ratio,
         aperture,
         focus,
-        0.0,
-        0.1,
+        time_0,
+        time_1,
     );
 
-    //let cam = Camera::default();
-
-    //vector of rgb tuples for each pixel
-    let mut screen = vec![(0usize, 0usize, 0usize); image_width * image_height];
-
-    screen
-        .par_iter_mut()
-        .enumerate()
-        .for_each(|(index, pixel)| {
-            let mut rng = rand::thread_rng();
-
-            let column = index % image_width;
-            let row = image_height - index / image_width;
-
-            let mut pixel_color = Color::default();
-
-            for _ in 0..samples_per_pixel {
-                let u = (column as f64 + rng.gen::<f64>()) / image_width as f64;
-                let v = (row as f64 + rng.gen::<f64>()) / image_height as f64;
-
-                let ray = &cam.get_ray(u, v);
-                pixel_color = pixel_color + ray_color(ray, &world, max_depth);
-            }
+    //the ground plane stays outside the tree, everything else goes in it
+    let world = BVH::new(world.objects().to_vec(), time_0, time_1);
 
-            pixel_color = &pixel_color / (samples_per_pixel as f64);
-            //sqrt to correct gamma (gamma = 4.0)
-            pixel_color = Color::new(
-                pixel_color.r().sqrt(),
-                pixel_color.g().sqrt(),
-                pixel_color.b().sqrt(),
-            );
-
-            //Write the tranlated [0,255] value of each color component
-            let r_temp = (255.99 * pixel_color.r()) as usize;
-            let g_temp = (255.99 * pixel_color.g()) as usize;
-            let b_temp = (255.99 * pixel_color.b()) as usize;
-
-            *pixel = (r_temp, g_temp, b_temp);
-        });
-
-    for (r, g, b) in screen {
-        println!("{} {} {}", r, g, b);
-    }
-
-    eprintln!("\nDone\n");
+    (world, cam)
 }
 
 fn make_random_spheres(world: &mut lib::hittable_list::HittableList) {
@@ -185,19 +110,66 @@ fn make_random_spheres(world: &mut lib::hittable_list::HittableList) {
 }
 
 fn main() {
-    const ASPECT_RATIO: f64 = 16.0 / 9.0;
-    const IMAGE_HEIGHT: usize = 1080;
-    const IMAGE_WIDTH: usize = (IMAGE_HEIGHT as f64 * ASPECT_RATIO) as usize;
-    const SAMPLE_PER_PIXEL: usize = 100;
-    const MAX_DEPTH: u16 = 50;
+    let settings = match Settings::from_args(std::env::args().skip(1)) {
+        Ok(settings) => settings,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
 
     let start_time = time::Instant::now();
 
-    render(ASPECT_RATIO, IMAGE_HEIGHT, SAMPLE_PER_PIXEL, MAX_DEPTH);
+    let (world, cam) = build_scene(&settings);
+    let output = render(&settings, &world, &cam);
+
+    if let Some(heatmap_path) = &settings.heatmap {
+        let max_samples = settings
+            .adaptive
+            .map_or(settings.samples_per_pixel, |params| params.max_samples);
+        let map = heatmap(
+            &output.sample_counts,
+            settings.image_width(),
+            settings.image_height,
+            max_samples,
+        );
+        if let Err(error) = map.save_ppm(heatmap_path, &ToneMapping::default()) {
+            eprintln!("failed to write the heatmap: {}", error);
+            std::process::exit(1);
+        }
+    }
+
+    let mut image = output.beauty;
+    if let (Some(params), Some(aovs)) = (&settings.denoise, &output.aovs) {
+        if let Some(raw_output) = &settings.raw_output {
+            if let Err(error) = image.save_ppm(raw_output, &settings.tone_mapping) {
+                eprintln!("failed to write the raw image: {}", error);
+                std::process::exit(1);
+            }
+        }
+        image = denoise(&image, &aovs.albedo, &aovs.normal, params);
+    }
+
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    if let Err(error) = image.write_ppm(&mut out, &settings.tone_mapping) {
+        eprintln!("failed to write the image: {}", error);
+        std::process::exit(1);
+    }
+
+    if let (Some(aovs), Some(aov_dir)) = (&output.aovs, &settings.aov_dir) {
+        if let Err(error) = aovs.write_pfm(aov_dir) {
+            eprintln!("failed to write the AOVs: {}", error);
+            std::process::exit(1);
+        }
+    }
+    eprintln!("\nDone\n");
 
     let duration = time::Instant::now() - start_time;
     eprintln!(
         "Rendered image with dimensions:\n {} x {}\n in {:?}",
-        IMAGE_WIDTH, IMAGE_HEIGHT, duration
+        settings.image_width(),
+        settings.image_height,
+        duration
     );
 }